@@ -1,11 +1,26 @@
 use anyhow::{anyhow, Result};
+use chrono::Datelike;
 use clap::{Parser, Subcommand};
+use crossterm::style::{
+    Attribute, Color, Colors, Print, ResetColor, SetAttribute, SetColors, SetForegroundColor,
+};
 use fancy_duration::FancyDuration;
 use saturn_cli::{
+    cli_processor::format_time,
     config::{Config, DBType},
-    db::{google::GoogleClient, memory::MemoryDB, remote::RemoteDBClient, DB},
+    db::{
+        google::GoogleClient, memory::MemoryDB, queue::WriteQueue, recorder::Transport,
+        remote::RemoteDBClient, GcReport, DB,
+    },
+    filenames::{
+        profile, saturn_config_for, saturn_profiles, saturn_recording, set_profile, DEFAULT_PROFILE,
+    },
+    free::Slot,
+    highlight::Highlighter,
+    output::{render, CalendarOutput, EntryOutput, Format, RecurringOutput},
     process_cli,
-    record::{Record, RecurringRecord, Schedule},
+    record::{records_on, Record, RecurringRecord, Schedule},
+    template::Template,
 };
 use ttygrid::{add_line, grid, header};
 
@@ -32,6 +47,12 @@ macro_rules! compose_grid {
 )]
 #[command(propagate_version = true)]
 struct ArgParser {
+    #[arg(
+        long,
+        global = true,
+        help = "Configuration profile to use; SATURN_PROFILE is used if not given"
+    )]
+    profile: Option<String>,
     #[command(subcommand)]
     command: Command,
 }
@@ -46,9 +67,18 @@ enum ConfigCommand {
         client_secret: String,
     },
     #[command(about = "Get an authentication token")]
-    GetToken {},
+    GetToken {
+        #[arg(
+            long,
+            help = "Enter a code on another device instead of logging in through a local browser"
+        )]
+        device: bool,
+    },
     #[command(about = "List Calendar Summaries and their IDs")]
-    ListCalendars,
+    ListCalendars {
+        #[arg(long, help = "Print as json, yaml, csv or tsv instead of a table")]
+        format: Option<Format>,
+    },
     #[command(about = "Set the calendar ID for remote requests.")]
     SetCalendarID { id: String },
     #[command(about = "Set the default duration for new calendar items that require a range.")]
@@ -59,6 +89,62 @@ enum ConfigCommand {
         about = "Set the minimum and maximum amount of time to query from the current date for Google Calendar"
     )]
     SetQueryWindow { set: String },
+    #[command(
+        about = "Set how long results from Google Calendar are cached before being fetched again"
+    )]
+    SetCacheTTL { set: String },
+    #[command(
+        about = "Set how many times a rate limited or failed request to Google Calendar is retried"
+    )]
+    SetMaxRetries { retries: u32 },
+    #[command(
+        about = "Set how long to wait before the first retry; each following retry waits up to twice as long"
+    )]
+    SetRetryDelay { delay: String },
+    #[command(
+        about = "Set the Google calendar ID a unixfile database is mirrored to with `saturn sync`. Omit to stop mirroring."
+    )]
+    SetMirror { calendar_id: Option<String> },
+    #[command(
+        about = "Set the working hours `saturn free` looks for open time in, e.g. `9am 5pm`"
+    )]
+    SetWorkingHours { start: String, end: String },
+    #[command(about = "Show every setting and its value")]
+    Show,
+    #[command(about = "Show the value of a setting")]
+    Get { key: String },
+    #[command(
+        about = "Change a setting, e.g. `saturn config set working_hours 9:00-17:00`. An empty value clears optional settings."
+    )]
+    Set { key: String, value: String },
+    #[command(about = "List profiles, or create one")]
+    Profiles {
+        #[command(subcommand)]
+        command: Option<ProfileCommand>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ProfileCommand {
+    #[command(about = "Create a new profile with the default configuration")]
+    Create { name: String },
+}
+
+#[derive(Debug, Subcommand)]
+enum DBCommand {
+    #[command(about = "Forget the IDs of Google Calendar events which were deleted")]
+    GC {
+        #[arg(long, help = "Renumber the remaining IDs so they start from 1")]
+        renumber: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum SyncCommand {
+    #[command(about = "Show writes waiting to be sent, and any that were refused")]
+    Status,
+    #[command(about = "Forget about writes that were refused")]
+    ClearConflicts,
 }
 
 #[derive(Debug, Subcommand)]
@@ -68,8 +154,24 @@ enum Command {
         #[command(subcommand)]
         command: ConfigCommand,
     },
+    #[command(
+        about = "List entries which overlap each other, over a date or range of dates like `today..friday`"
+    )]
+    Conflicts {
+        #[arg(default_value = "today")]
+        range: String,
+    },
+    #[command(about = "Maintain the database")]
+    DB {
+        #[command(subcommand)]
+        command: DBCommand,
+    },
     #[command(alias = "c", about = "Also `c`. Complete a Task")]
     Complete { id: u64 },
+    #[command(about = "Notify about an entry once more after a duration, e.g. `10m`")]
+    Snooze { id: u64, duration: String },
+    #[command(about = "Acknowledge an entry, so it no longer notifies")]
+    Ack { id: u64 },
     #[command(
         alias = "d",
         about = "Also `d`. Delete an event by ID. Pass `-r` to delete recurring IDs"
@@ -98,11 +200,84 @@ enum Command {
         include_completed: bool,
         #[arg(short = 'i', long, help = "Icon in XDG desktop format")]
         icon: Option<String>,
+        #[arg(
+            long,
+            help = "Template for the notification body, e.g. '{time}: {detail}'"
+        )]
+        template: Option<String>,
+        #[arg(
+            short = 'd',
+            long,
+            conflicts_with = "well",
+            help = "Keep running, sending each notification when it is due"
+        )]
+        daemon: bool,
+        #[arg(
+            long,
+            help = "Also send the notifications missed since saturn last looked for them"
+        )]
+        catch_up: bool,
     },
     #[command(alias = "e", about = "Also `e`. Enter a new entry into the calendar")]
-    Entry { args: Vec<String> },
+    Entry {
+        #[arg(
+            short = 'f',
+            long,
+            help = "Do not warn when it overlaps existing entries"
+        )]
+        force: bool,
+        args: Vec<String>,
+    },
     #[command(alias = "t", about = "Also `t`. Show today's calendar")]
-    Today {},
+    Today {
+        #[arg(long, help = "Print as json, yaml, csv or tsv instead of a table")]
+        format: Option<Format>,
+        #[arg(
+            long,
+            conflicts_with = "format",
+            help = "Print a line per entry from a template, e.g. '{time} {detail}'"
+        )]
+        template: Option<String>,
+    },
+    #[command(
+        alias = "a",
+        about = "Also `a`. Show the entries for the days ahead, a week by default"
+    )]
+    Agenda {
+        #[arg(
+            short = 'd',
+            long,
+            conflicts_with_all = ["week", "month"],
+            value_parser = clap::value_parser!(u32).range(1..),
+            help = "Number of days to show, starting today"
+        )]
+        days: Option<u32>,
+        #[arg(
+            short = 'w',
+            long,
+            conflicts_with = "month",
+            help = "Show the next week"
+        )]
+        week: bool,
+        #[arg(short = 'm', long, help = "Show the next month")]
+        month: bool,
+    },
+    #[command(about = "Show a month with the number of entries on each day")]
+    Cal {
+        #[arg(
+            value_parser = clap::value_parser!(u32).range(1..=12),
+            help = "Month from 1 to 12, the current month by default"
+        )]
+        month: Option<u32>,
+        #[arg(help = "Year, the current year by default")]
+        year: Option<i32>,
+        #[arg(
+            short = 'l',
+            long,
+            help = "List the month's entries below the calendar"
+        )]
+        list: bool,
+    },
     #[command(
         alias = "l",
         about = "Also `l`. List today's calendar by default, or --all to show the full calendar"
@@ -112,6 +287,14 @@ enum Command {
         recur: bool,
         #[arg(short = 'a', long, help = "List all tasks, not just today's")]
         all: bool,
+        #[arg(long, help = "Print as json, yaml, csv or tsv instead of a table")]
+        format: Option<Format>,
+        #[arg(
+            long,
+            conflicts_with = "format",
+            help = "Print a line per entry from a template, e.g. '{time} {detail}'"
+        )]
+        template: Option<String>,
     },
     #[command(
         about = "Edit the details of a specific calendar ID. Use `-r` to specify recurring tasks."
@@ -129,6 +312,8 @@ enum Command {
         #[arg(short = 'r', long, help = "ID is a recurring task")]
         recur: bool,
         id: u64,
+        #[arg(long, help = "Print as json, yaml, csv or tsv instead of a table")]
+        format: Option<Format>,
     },
     #[command(
         alias = "n",
@@ -143,12 +328,58 @@ enum Command {
         well: Option<String>,
         #[arg(short = 'c', long, help = "Include completed tasks (unixfile only)")]
         include_completed: bool,
+        #[arg(long, help = "Print as json, yaml, csv or tsv instead of a table")]
+        format: Option<Format>,
+        #[arg(
+            long,
+            conflicts_with = "format",
+            help = "Print a line per entry from a template, e.g. '{time} {detail}'"
+        )]
+        template: Option<String>,
     },
     #[command(
         alias = "/",
         about = "Also `/`. Search with terms to identify different calendar items."
     )]
-    Search { terms: Vec<String> },
+    Search {
+        terms: Vec<String>,
+        #[arg(long, help = "Print as json, yaml, csv or tsv instead of a table")]
+        format: Option<Format>,
+        #[arg(
+            long,
+            conflicts_with = "format",
+            help = "Print a line per entry from a template, e.g. '{time} {detail}'"
+        )]
+        template: Option<String>,
+    },
+    #[command(
+        about = "Find open time of at least a duration within working hours, e.g. `saturn free today..friday 1h`"
+    )]
+    Free {
+        #[arg(help = "A date, or a range of dates like `today..friday`")]
+        range: String,
+        duration: String,
+        #[arg(
+            short = 'c',
+            long = "calendar",
+            help = "Google calendar to check; may be given several times (defaults to the configured calendar)"
+        )]
+        calendars: Vec<String>,
+        #[arg(long, help = "Print as json, yaml, csv or tsv instead of a table")]
+        format: Option<Format>,
+    },
+    #[command(
+        about = "Synchronize with the mirror calendar, or inspect writes made while the remote calendar was unreachable"
+    )]
+    Sync {
+        #[command(subcommand)]
+        command: Option<SyncCommand>,
+        #[arg(
+            long,
+            help = "Which side wins when an item was changed on both sides (local or remote)"
+        )]
+        prefer: Option<String>,
+    },
 }
 
 fn get_well(well: Option<String>) -> Result<chrono::Duration> {
@@ -186,7 +417,10 @@ fn grid_all_day(grid: &mut ttygrid::TTYGrid, entry: Record) {
             if entry.detail().len() > 20 { "..." } else { "" }
         ),
         entry.primary_key().to_string(),
-        entry.date().to_string(),
+        match entry.end_date() {
+            Some(end_date) => format!("{} to {}", entry.date(), end_date),
+            None => entry.date().to_string(),
+        },
         entry.fields().to_string(),
         if entry.completed() { "X" } else { "" }.to_string()
     )
@@ -210,9 +444,70 @@ fn grid_scheduled(grid: &mut ttygrid::TTYGrid, entry: Record, schedule: Schedule
     .unwrap()
 }
 
-fn print_entries(entries: Vec<Record>) {
+// write_grid prints the grid with the colors compose_grid! sets, except for rows given a color of
+// their own. Without colors, nothing but the text is written.
+fn write_grid(grid: &mut ttygrid::TTYGrid, colors: Option<Vec<Option<Color>>>) -> Result<()> {
+    let text = grid.display()?;
+    let colors = match colors {
+        Some(colors) => colors,
+        None => {
+            print!("{}", text);
+            return Ok(());
+        }
+    };
+
+    let mut stdout = std::io::stdout();
+    for (idx, line) in text.lines().enumerate() {
+        let color = match idx {
+            0 => Color::DarkCyan,
+            1 => Color::Cyan,
+            idx => colors
+                .get(idx - 2)
+                .cloned()
+                .flatten()
+                .unwrap_or(if idx % 2 == 0 {
+                    Color::White
+                } else {
+                    Color::Grey
+                }),
+        };
+
+        crossterm::execute!(
+            stdout,
+            SetColors(Colors::new(color, Color::Reset)),
+            Print(format!("{}\n", line))
+        )?;
+    }
+
+    crossterm::execute!(stdout, ResetColor)?;
+    Ok(())
+}
+
+fn print_entries(
+    entries: Vec<Record>,
+    format: Option<Format>,
+    template: Option<Template>,
+    highlighter: Option<Highlighter>,
+) -> Result<()> {
+    if let Some(format) = format {
+        let entries = entries
+            .into_iter()
+            .map(EntryOutput::from)
+            .collect::<Vec<EntryOutput>>();
+        print!("{}", render(format, &entries)?);
+        return Ok(());
+    }
+
+    if let Some(template) = template {
+        let now = saturn_cli::time::now().naive_local();
+        for entry in entries {
+            println!("{}", template.render(&entry, now));
+        }
+        return Ok(());
+    }
+
     if entries.is_empty() {
-        return;
+        return Ok(());
     }
 
     let mut grid = compose_grid!(
@@ -224,7 +519,11 @@ fn print_entries(entries: Vec<Record>) {
         header!("DONE", 1)
     );
 
+    let mut colors = Vec::new();
+
     for entry in entries {
+        colors.push(highlighter.as_ref().and_then(|h| h.color(&entry)));
+
         if let Some(at) = entry.at() {
             grid_at(&mut grid, entry, at);
         } else if let Some(schedule) = entry.scheduled() {
@@ -234,14 +533,254 @@ fn print_entries(entries: Vec<Record>) {
         }
     }
 
+    write_grid(&mut grid, highlighter.map(|_| colors))
+}
+
+// print_agenda lists the entries under a heading for each day they take place on, with all day
+// events first.
+fn print_agenda(
+    entries: Vec<Record>,
+    start: chrono::NaiveDate,
+    end: chrono::NaiveDate,
+    use_24h_time: bool,
+    highlighter: Option<Highlighter>,
+) -> Result<()> {
+    let days = saturn_cli::record::by_day(&entries, start, end);
+    if days.is_empty() {
+        println!("Nothing scheduled.");
+        return Ok(());
+    }
+
+    let today = saturn_cli::time::now().date_naive();
+    let time = |t| format_time(t, use_24h_time);
+    let mut stdout = std::io::stdout();
+
+    for (idx, (date, entries)) in days.into_iter().enumerate() {
+        if idx > 0 {
+            println!();
+        }
+
+        let heading = format!(
+            "{}{}",
+            date.format("%A, %B %-d, %Y"),
+            if date == today { " (today)" } else { "" }
+        );
+
+        let mut lines = vec![(heading, highlighter.as_ref().map(|_| Color::DarkCyan))];
+
+        for entry in entries {
+            let when = if let Some(at) = entry.at() {
+                time(at)
+            } else if let Some(schedule) = entry.scheduled() {
+                format!("{} - {}", time(schedule.0), time(schedule.1))
+            } else {
+                "All Day".to_string()
+            };
+
+            // recurrences which have not been recorded yet only have the ID of their recurring task
+            let id = match (entry.primary_key(), entry.recurrence_key()) {
+                (0, Some(key)) => format!("recur {}", key),
+                (key, _) => key.to_string(),
+            };
+
+            lines.push((
+                format!("  {:<17}  {} [{}]", when, entry.detail(), id),
+                highlighter.as_ref().and_then(|h| h.color(&entry)),
+            ));
+        }
+
+        for (line, color) in lines {
+            match color {
+                Some(color) => crossterm::execute!(
+                    stdout,
+                    SetColors(Colors::new(color, Color::Reset)),
+                    Print(format!("{}\n", line)),
+                    ResetColor
+                )?,
+                None => println!("{}", line),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// print_cal prints the month starting on `first` as a grid of weeks, with the number of entries on
+// each day which has any. Today is reversed when colors are used, and marked with `*` when not.
+fn print_cal(entries: &[Record], first: chrono::NaiveDate, color: bool) -> Result<()> {
+    let today = saturn_cli::time::now().date_naive();
+    let mut stdout = std::io::stdout();
+
+    println!(
+        "{}",
+        format!("{:^48}", first.format("%B %Y").to_string()).trim_end()
+    );
+    let header = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"]
+        .iter()
+        .map(|d| format!("{:<7}", d))
+        .collect::<String>();
+    if color {
+        crossterm::execute!(
+            stdout,
+            SetForegroundColor(Color::DarkCyan),
+            Print(format!("{}\n", header.trim_end())),
+            ResetColor
+        )?;
+    } else {
+        println!("{}", header.trim_end());
+    }
+
+    print!(
+        "{}",
+        " ".repeat(7 * first.weekday().num_days_from_sunday() as usize)
+    );
+
+    for date in first.iter_days().take_while(|d| d.month() == first.month()) {
+        let count = records_on(entries, date).len();
+        let day = if date == today && !color {
+            format!("*{:>2}", date.day())
+        } else {
+            format!("{:>3}", date.day())
+        };
+        let count = if count > 0 {
+            format!("({})", count)
+        } else {
+            String::new()
+        };
+
+        if color && date == today {
+            crossterm::execute!(
+                stdout,
+                SetAttribute(Attribute::Reverse),
+                Print(&day),
+                SetAttribute(Attribute::Reset)
+            )?;
+        } else if color && !count.is_empty() {
+            crossterm::execute!(stdout, SetForegroundColor(Color::Yellow), Print(&day))?;
+        } else {
+            print!("{}", day);
+        }
+
+        if color && !count.is_empty() {
+            crossterm::execute!(
+                stdout,
+                SetForegroundColor(Color::Yellow),
+                Print(&count),
+                ResetColor
+            )?;
+        } else {
+            print!("{}", count);
+        }
+
+        if date.weekday() == chrono::Weekday::Sat {
+            println!();
+        } else {
+            print!("{}", " ".repeat(4usize.saturating_sub(count.len())));
+        }
+    }
+
+    if first
+        .checked_add_months(chrono::Months::new(1))
+        .and_then(|d| d.pred_opt())
+        .is_some_and(|last| last.weekday() != chrono::Weekday::Sat)
+    {
+        println!();
+    }
+
+    Ok(())
+}
+
+fn print_conflicts(conflicts: Vec<(Record, Record)>) {
+    if conflicts.is_empty() {
+        println!("No conflicts found.");
+        return;
+    }
+
+    let mut grid = compose_grid!(
+        header!("DATE"),
+        header!("ID"),
+        header!("DETAIL"),
+        header!("TIME"),
+        header!("ID"),
+        header!("DETAIL"),
+        header!("TIME")
+    );
+
+    let time = |record: &Record| match record.scheduled() {
+        Some(schedule) => format!("{} to {}", schedule.0, schedule.1),
+        None => record.at().map(|at| at.to_string()).unwrap_or_default(),
+    };
+
+    let detail = |record: &Record| {
+        format!(
+            "{0:.20}{1}",
+            record.detail(),
+            if record.detail().len() > 20 {
+                "..."
+            } else {
+                ""
+            }
+        )
+    };
+
+    for (first, second) in conflicts {
+        add_line!(
+            grid,
+            first.date().to_string(),
+            first.primary_key().to_string(),
+            detail(&first),
+            time(&first),
+            second.primary_key().to_string(),
+            detail(&second),
+            time(&second)
+        )
+        .unwrap()
+    }
+
     grid.write(std::io::stdout()).unwrap();
 }
 
-fn print_recurring(entries: Vec<RecurringRecord>) {
-    if entries.is_empty() {
+fn print_free(slots: Vec<Slot>) {
+    if slots.is_empty() {
+        println!("No open time found.");
         return;
     }
 
+    let mut grid = compose_grid!(
+        header!("DATE"),
+        header!("FROM"),
+        header!("TO"),
+        header!("LENGTH")
+    );
+
+    for slot in slots {
+        add_line!(
+            grid,
+            slot.start.date().to_string(),
+            slot.start.time().format("%H:%M").to_string(),
+            slot.end.time().format("%H:%M").to_string(),
+            FancyDuration::new(slot.duration()).to_string()
+        )
+        .unwrap()
+    }
+
+    grid.write(std::io::stdout()).unwrap();
+}
+
+fn print_recurring(entries: Vec<RecurringRecord>, format: Option<Format>) -> Result<()> {
+    if let Some(format) = format {
+        let entries = entries
+            .into_iter()
+            .map(RecurringOutput::from)
+            .collect::<Vec<RecurringOutput>>();
+        print!("{}", render(format, &entries)?);
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        return Ok(());
+    }
+
     let mut grid = compose_grid!(header!("INTERVAL"), header!("DETAIL"), header!("ID"));
 
     for mut entry in entries {
@@ -263,6 +802,123 @@ fn print_recurring(entries: Vec<RecurringRecord>) {
     }
 
     grid.write(std::io::stdout()).unwrap();
+    Ok(())
+}
+
+fn ids(keys: impl Iterator<Item = u64>) -> String {
+    keys.map(|k| k.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn print_gc_report(report: GcReport) {
+    if report.removed.is_empty()
+        && report.removed_recurring.is_empty()
+        && report.renumbered.is_empty()
+    {
+        println!("Nothing to reclaim.");
+        return;
+    }
+
+    if !report.removed.is_empty() {
+        println!(
+            "Removed {} IDs: {}",
+            report.removed.len(),
+            ids(report.removed.into_iter())
+        );
+    }
+
+    if !report.removed_recurring.is_empty() {
+        println!(
+            "Removed {} recurring IDs: {}",
+            report.removed_recurring.len(),
+            ids(report.removed_recurring.into_iter())
+        );
+    }
+
+    if !report.renumbered.is_empty() {
+        println!(
+            "Renumbered {} IDs: {}",
+            report.renumbered.len(),
+            report
+                .renumbered
+                .iter()
+                .map(|(old, new)| format!("{} -> {}", old, new))
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+    }
+}
+
+fn print_sync_status(queue: WriteQueue) {
+    let pending = queue.pending();
+    let conflicts = queue.conflicts();
+
+    if pending.is_empty() && conflicts.is_empty() {
+        println!("Nothing waiting to be sent.");
+        return;
+    }
+
+    if !pending.is_empty() {
+        println!("Pending:");
+        let mut grid = compose_grid!(header!("ACTION"), header!("ID"), header!("DETAIL"));
+        for write in pending {
+            add_line!(
+                grid,
+                write.action().to_string(),
+                write.primary_key().to_string(),
+                write.detail().unwrap_or_default()
+            )
+            .unwrap()
+        }
+        grid.write(std::io::stdout()).unwrap();
+    }
+
+    if !conflicts.is_empty() {
+        println!("Conflicts:");
+        let mut grid = compose_grid!(
+            header!("ACTION"),
+            header!("ID"),
+            header!("AT"),
+            header!("ERROR")
+        );
+        for conflict in conflicts {
+            add_line!(
+                grid,
+                conflict.write.action().to_string(),
+                conflict.write.primary_key().to_string(),
+                conflict.at.format("%Y-%m-%d %H:%M").to_string(),
+                conflict.error
+            )
+            .unwrap()
+        }
+        grid.write(std::io::stdout()).unwrap();
+    }
+}
+
+fn print_profiles() -> Result<()> {
+    let current = profile().unwrap_or(DEFAULT_PROFILE.to_string());
+    let mut grid = compose_grid!(
+        header!("NAME"),
+        header!("TYPE"),
+        header!("CALENDAR"),
+        header!("CURRENT")
+    );
+
+    for name in saturn_profiles()? {
+        let config = Config::load(Some(saturn_config_for(Some(&name))))?;
+        add_line!(
+            grid,
+            name.clone(),
+            format!("{:?}", config.db_type()),
+            config.calendar_id(),
+            if name == current { "X" } else { "" }.to_string()
+        )
+        .unwrap();
+    }
+
+    grid.write(std::io::stdout()).unwrap();
+    Ok(())
 }
 
 fn set_calendar_id(id: String, mut config: Config) -> Result<()> {
@@ -270,8 +926,18 @@ fn set_calendar_id(id: String, mut config: Config) -> Result<()> {
     config.save(None)
 }
 
-async fn list_calendars(mut client: GoogleClient) -> Result<()> {
+async fn list_calendars(mut client: GoogleClient, format: Option<Format>) -> Result<()> {
     let list = client.list_calendars().await?;
+
+    if let Some(format) = format {
+        let list = list
+            .into_iter()
+            .map(CalendarOutput::from)
+            .collect::<Vec<CalendarOutput>>();
+        print!("{}", render(format, &list)?);
+        return Ok(());
+    }
+
     let mut grid = compose_grid!(header!("ID"), header!("SUMMARY"));
     for item in list {
         add_line!(grid, item.id, item.summary).unwrap();
@@ -281,9 +947,10 @@ async fn list_calendars(mut client: GoogleClient) -> Result<()> {
 }
 
 async fn process_google(cli: ArgParser, config: Config) -> Result<()> {
-    let client = GoogleClient::new(config.clone())?;
+    let mut client = GoogleClient::new(config.clone())?;
+    client.set_transport(Transport::new(saturn_recording()));
 
-    let mut db = RemoteDBClient::new(config.calendar_id(), client.clone());
+    let mut db = RemoteDBClient::new(config.calendar_id(), config.cache_ttl(), client.clone());
     process_cli!(cli, config, db, Some(client.clone()));
 
     Ok(())
@@ -298,8 +965,9 @@ async fn process_file(cli: ArgParser, config: Config) -> Result<()> {
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = ArgParser::parse();
+    set_profile(cli.profile.clone())?;
 
-    let config = Config::load(None).unwrap_or_default();
+    let config = Config::load(None)?;
     match config.db_type() {
         DBType::UnixFile => process_file(cli, config).await,
         DBType::Google => process_google(cli, config).await,
@@ -8,7 +8,11 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::prelude::*;
-use saturn_cli::ui::{layout::draw_loop, state::ProtectedState};
+use saturn_cli::{
+    config::Config,
+    filenames::set_profile,
+    ui::{layout::draw_loop, state::ProtectedState},
+};
 use std::io::{self, Stdout};
 
 #[derive(Parser, Debug)]
@@ -20,8 +24,14 @@ use std::io::{self, Stdout};
 )]
 #[command(propagate_version = true)]
 struct ArgParser {
+    #[arg(
+        long,
+        global = true,
+        help = "Configuration profile to use; SATURN_PROFILE is used if not given"
+    )]
+    profile: Option<String>,
     #[command(subcommand)]
-    command: Command,
+    command: Option<Command>,
 }
 
 #[derive(Debug, Subcommand)]
@@ -33,15 +43,12 @@ enum Command {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = if std::env::args().len() > 1 {
-        ArgParser::parse()
-    } else {
-        ArgParser {
-            command: Command::Run,
-        }
-    };
+    let cli = ArgParser::parse();
+    set_profile(cli.profile)?;
+    // report problems with the configuration before taking over the terminal
+    Config::load(None)?;
 
-    match cli.command {
+    match cli.command.unwrap_or(Command::Run) {
         Command::Run => run().await?,
     }
 
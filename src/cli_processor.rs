@@ -30,26 +30,106 @@ pub fn format_scheduled(
     )
 }
 
+// format_time shows times the way they are entered: `9:30am` unless 24 hour time is used.
+pub fn format_time(time: chrono::NaiveTime, use_24h_time: bool) -> String {
+    if use_24h_time {
+        time.format("%H:%M").to_string()
+    } else {
+        time.format("%-I:%M%P").to_string()
+    }
+}
+
+// agenda_range is the days `saturn agenda` covers, starting today: a week unless a number of days
+// or a month is asked for.
+pub fn agenda_range(
+    today: chrono::NaiveDate,
+    days: Option<u32>,
+    month: bool,
+) -> (chrono::NaiveDate, chrono::NaiveDate) {
+    let end = if month {
+        today
+            .checked_add_months(chrono::Months::new(1))
+            .and_then(|d| d.pred_opt())
+            .unwrap_or(today)
+    } else {
+        today + chrono::TimeDelta::try_days(days.unwrap_or(7) as i64 - 1).unwrap_or_default()
+    };
+
+    (today, end)
+}
+
+// month_range is the first and last day of the month, which defaults to the current one. The year
+// defaults to the current year.
+pub fn month_range(
+    today: chrono::NaiveDate,
+    month: Option<u32>,
+    year: Option<i32>,
+) -> anyhow::Result<(chrono::NaiveDate, chrono::NaiveDate)> {
+    use chrono::Datelike;
+
+    let first = chrono::NaiveDate::from_ymd_opt(
+        year.unwrap_or(today.year()),
+        month.unwrap_or(today.month()),
+        1,
+    )
+    .ok_or_else(|| anyhow::anyhow!("Invalid month or year"))?;
+
+    let last = first
+        .checked_add_months(chrono::Months::new(1))
+        .and_then(|d| d.pred_opt())
+        .ok_or_else(|| anyhow::anyhow!("Invalid month or year"))?;
+
+    Ok((first, last))
+}
+
+// template picks the template given on the command line, or else the configured one.
+pub fn template(
+    template: Option<String>,
+    configured: Option<String>,
+) -> anyhow::Result<Option<crate::template::Template>> {
+    template.or(configured).map(|t| t.parse()).transpose()
+}
+
+pub async fn run_editor(path: &std::path::Path) -> anyhow::Result<bool> {
+    let mut cmd =
+        tokio::process::Command::new(std::env::var("EDITOR").unwrap_or("/usr/bin/vim".to_string()));
+    cmd.args([path]);
+    Ok(cmd.spawn()?.wait().await?.success())
+}
+
+// if the item was changed remotely while it was being edited, the edits are merged with the remote
+// changes and the editor is raised again, until the update goes through or the editor fails.
 #[macro_export]
 macro_rules! launch_editor {
     ($db: ident, $id: ident, $typ:ty, $fetch:ident, $recur: ident) => {{
-        let record = $db.$fetch($id).await?;
-        let presented: $typ = record.clone().into();
+        let mut record = $db.$fetch($id).await?;
+        let mut original: $typ = record.clone().into();
         let f = tempfile::NamedTempFile::new()?;
-        serde_yaml::to_writer(&f, &presented)?;
+        serde_yaml::to_writer(&f, &original)?;
         let (f, path) = f.keep()?;
         drop(f);
-        let mut cmd = tokio::process::Command::new(
-            std::env::var("EDITOR").unwrap_or("/usr/bin/vim".to_string()),
-        );
-        cmd.args([path.clone()]);
-        let mut child = cmd.spawn()?;
-        if child.wait().await?.success() {
-            let mut io = std::fs::OpenOptions::new();
-            io.read(true);
-            let f = io.open(path)?;
-            let presented: $typ = serde_yaml::from_reader(&f)?;
-            $crate::update_record!($db, presented, record, $recur);
+        while $crate::cli_processor::run_editor(&path).await? {
+            let text = std::fs::read_to_string(&path)?;
+            if $crate::merge::has_conflict_markers(&text) {
+                return Err(anyhow::anyhow!(
+                    "Conflicts were left unresolved in {}",
+                    path.display()
+                ));
+            }
+            let presented: $typ = serde_yaml::from_str(&text)?;
+            match $crate::update_record!($db, presented, record, $recur) {
+                Ok(()) => break,
+                Err(e) => match $crate::edit_conflict!(e, $recur) {
+                    Some(remote) => {
+                        let theirs: $typ = remote.clone().into();
+                        let merge = $crate::merge::three_way(&original, &presented, &theirs)?;
+                        std::fs::write(&path, merge.text)?;
+                        record = remote;
+                        original = theirs;
+                    }
+                    None => return Err(e),
+                },
+            }
         }
     }};
 }
@@ -70,22 +150,36 @@ macro_rules! map_record {
 #[macro_export]
 macro_rules! update_record {
     ($db: ident, $presented: ident, $record:ident, true) => {{
-        $db.update_recurring($presented.to_record(
+        let mut updated = $presented.clone().to_record(
             $record.clone().record().primary_key(),
             $record.recurrence_key(),
             $record.clone().record().internal_key(),
             $record.internal_key(),
-        ))
-        .await?;
+        );
+        updated.record().set_etag($record.clone().record().etag());
+        $db.update_recurring(updated).await
     }};
     ($db: ident, $presented: ident, $record:ident, false) => {{
-        $db.update($presented.to_record(
+        let mut updated = $presented.clone().to_record(
             $record.primary_key(),
             $record.recurrence_key(),
             $record.internal_key(),
             $record.internal_recurrence_key(),
-        ))
-        .await?;
+        );
+        updated.set_etag($record.etag());
+        $db.update(updated).await
+    }};
+}
+
+#[macro_export]
+macro_rules! edit_conflict {
+    ($err: ident, true) => {{
+        $err.downcast_ref::<$crate::db::EditConflict<$crate::record::RecurringRecord>>()
+            .map(|c| c.0.clone())
+    }};
+    ($err: ident, false) => {{
+        $err.downcast_ref::<$crate::db::EditConflict<$crate::record::Record>>()
+            .map(|c| c.0.clone())
     }};
 }
 
@@ -109,6 +203,48 @@ macro_rules! process_cli {
                     config.set_query_window(FancyDuration::parse(&set)?.duration());
                     config.save(None)?;
                 }
+                ConfigCommand::SetCacheTTL { set } => {
+                    let mut config = Config::load(None)?;
+                    config.set_cache_ttl(FancyDuration::parse(&set)?.duration());
+                    config.save(None)?;
+                }
+                ConfigCommand::SetMaxRetries { retries } => {
+                    let mut config = Config::load(None)?;
+                    config.set_max_retries(retries);
+                    config.save(None)?;
+                }
+                ConfigCommand::SetRetryDelay { delay } => {
+                    let mut config = Config::load(None)?;
+                    config.set_retry_delay(FancyDuration::parse(&delay)?.duration());
+                    config.save(None)?;
+                }
+                ConfigCommand::SetMirror { calendar_id } => {
+                    let mut config = Config::load(None)?;
+                    config.set_mirror(calendar_id);
+                    config.save(None)?;
+                }
+                ConfigCommand::SetWorkingHours { start, end } => {
+                    let mut config = Config::load(None)?;
+                    let (start, end) =
+                        $crate::parsers::time::parse_hours(start, end, config.use_24h_time())?;
+                    config.set_working_hours(start, end);
+                    config.save(None)?;
+                }
+                ConfigCommand::Show => {
+                    for (key, value) in Config::load(None)?.settings() {
+                        println!("{}: {}", key, value);
+                    }
+                }
+                ConfigCommand::Get { key } => println!("{}", Config::load(None)?.get(&key)?),
+                ConfigCommand::Set { key, value } => {
+                    let mut config = Config::load(None)?;
+                    config.set(&key, &value)?;
+                    config.save(None)?;
+                }
+                ConfigCommand::Profiles { command } => match command {
+                    None => print_profiles()?,
+                    Some(ProfileCommand::Create { name }) => Config::create_profile(&name)?,
+                },
                 ConfigCommand::Set24hTime { set } => {
                     let mut config = Config::load(None)?;
                     config.set_use_24h_time(set);
@@ -122,27 +258,23 @@ macro_rules! process_cli {
                     config.set_client_info(client_id, client_secret);
                     config.save(None)?;
                 }
-                ConfigCommand::GetToken {} => $crate::oauth::get_access_token().await?,
+                ConfigCommand::GetToken { device } => {
+                    if device {
+                        $crate::oauth::get_access_token_device().await?
+                    } else {
+                        $crate::oauth::get_access_token().await?
+                    }
+                }
                 ConfigCommand::DBType { db_type } => {
                     let mut config = Config::load(None)?;
-                    let typ = match db_type.as_str() {
-                        "google" => DBType::Google,
-                        "unixfile" => DBType::UnixFile,
-                        _ => {
-                            return Err(anyhow!(
-                                "Invalid db type: valid types are `google` and `unixfile`"
-                            ))
-                        }
-                    };
-
-                    config.set_db_type(typ);
+                    config.set_db_type(db_type.parse()?);
                     config.save(None)?;
                 }
-                ConfigCommand::ListCalendars => {
+                ConfigCommand::ListCalendars { format } => {
                     if $client.is_none() {
                         eprintln!("Not supported in unixfile mode");
                     } else {
-                        list_calendars($client.unwrap()).await?;
+                        list_calendars($client.unwrap(), format).await?;
                     }
                 }
                 ConfigCommand::SetCalendarID { id } => {
@@ -161,6 +293,27 @@ macro_rules! process_cli {
                 }
             },
             Command::Complete { id } => $db.complete_task(id).await?,
+            Command::Snooze { id, duration } => {
+                let duration = FancyDuration::<chrono::Duration>::parse(&duration)?.duration();
+                if duration <= chrono::TimeDelta::zero() {
+                    return Err(anyhow!("Snooze for a duration such as `10m`"));
+                }
+
+                let mut record = $db.get(id).await?;
+                $crate::notifications::snooze(
+                    &mut record,
+                    $crate::time::now().naive_local() + duration,
+                );
+                $db.update(record).await?;
+            }
+            Command::Ack { id } => {
+                let mut record = $db.get(id).await?;
+                $crate::notifications::acknowledge(
+                    &mut record,
+                    $crate::time::now().naive_local(),
+                );
+                $db.update(record).await?;
+            }
             Command::Delete { ids, recur } => {
                 for id in ids {
                     if recur {
@@ -175,101 +328,110 @@ macro_rules! process_cli {
                 timeout,
                 include_completed,
                 icon,
+                template,
+                daemon,
+                catch_up,
             } => {
+                use std::ops::Bound;
+
+                let template = $crate::cli_processor::template(template, $config.notify_template())?;
                 let timeout = timeout.map_or(std::time::Duration::new(60, 0), |t| {
                     fancy_duration::FancyDuration::<std::time::Duration>::parse(&t)
                         .expect("Invalid Duration")
                         .duration()
                 });
 
-                let mut notification = notify_rust::Notification::new();
-                notification.summary("Calendar Event");
-                notification.timeout(timeout);
-                let well = well.map_or_else(
-                    || chrono::TimeDelta::try_minutes(1).unwrap_or_default(),
-                    |x| {
-                        FancyDuration::<chrono::Duration>::parse(&x)
-                            .expect("Invalid duration")
-                            .duration()
-                    },
-                );
+                let desktop = $crate::notifications::Desktop::new(timeout, icon);
+                let sink = $config.notify_sink()?;
+                let show = |due: &$crate::notifications::Due| -> anyhow::Result<()> {
+                    let entry = &due.record;
+                    let body = match &template {
+                        Some(template) => template.render(entry, $crate::time::now().naive_local()),
+                        None => match (entry.at(), entry.scheduled()) {
+                            (Some(at), _) => format_at(entry, at),
+                            (None, Some(schedule)) => format_scheduled(entry, schedule),
+                            (None, None) => format_all_day(entry),
+                        },
+                    };
 
-                let time = $crate::time::now()
-                    .with_second(0)
-                    .unwrap()
-                    .with_nanosecond(0)
-                    .unwrap();
-
-                for entry in &$db.list_all(include_completed).await? {
-                    if let Some(notifications) = entry.notifications() {
-                        for duration in notifications.iter().map(FancyDuration::duration) {
-                            let notify = match entry.record_type() {
-                                $crate::record::RecordType::AllDay => {
-                                    let top = (entry.datetime() + chrono::TimeDelta::try_days(1).unwrap_or_default())
-                                        .with_hour(0)
-                                        .unwrap()
-                                        .with_minute(0)
-                                        .unwrap()
-                                        .with_second(0)
-                                        .unwrap()
-                                        .with_nanosecond(0)
-                                        .unwrap()
-                                        - duration;
-
-                                    if time - well < top && time + well > top {
-                                        Some(notification.body(&format_all_day(entry)))
-                                    } else {
-                                        None
-                                    }
-                                }
-                                $crate::record::RecordType::At => {
-                                    let at = entry.at().unwrap() - duration;
-                                    let top = (chrono::NaiveDateTime::new(entry.date(), at)
-                                        - duration)
-                                        .and_local_timezone(chrono::Local)
-                                        .unwrap();
-                                    if time - well < top && time + well > top {
-                                        Some(notification.body(&format_at(entry, at)))
-                                    } else {
-                                        None
-                                    }
-                                }
-                                $crate::record::RecordType::Schedule => {
-                                    let schedule = entry.scheduled().unwrap();
-                                    let top =
-                                        (chrono::NaiveDateTime::new(entry.date(), schedule.0)
-                                            - duration)
-                                            .and_local_timezone(chrono::Local)
-                                            .unwrap();
-
-                                    if time - well < top && time + well > top {
-                                        Some(notification.body(&format_scheduled(entry, schedule)))
-                                    } else {
-                                        None
-                                    }
-                                }
-                            };
-
-                            if let Some(mut notify) = notify {
-                                if let Some(icon) = icon.clone() {
-                                    notify = notify.icon(&icon);
-                                }
-
-                                notify.show()?;
-                            }
-                        }
-                    }
+                    $crate::notifications::send(due, &body, &sink, &desktop)
+                };
+
+                if daemon {
+                    $crate::notifications::daemon(
+                        &mut $db,
+                        include_completed,
+                        $config.cache_ttl(),
+                        catch_up,
+                        $config.default_duration().duration(),
+                        show,
+                    )
+                    .await?;
+                } else {
+                    let well = well.map_or_else(
+                        || chrono::TimeDelta::try_minutes(1).unwrap_or_default(),
+                        |x| {
+                            FancyDuration::<chrono::Duration>::parse(&x)
+                                .expect("Invalid duration")
+                                .duration()
+                        },
+                    );
+
+                    let time = $crate::time::now()
+                        .with_second(0)
+                        .unwrap()
+                        .with_nanosecond(0)
+                        .unwrap()
+                        .naive_local();
+
+                    let records = $db.list_all(include_completed).await?;
+                    let lock = $crate::notifications::Notified::lock()?;
+                    let mut notified = $crate::notifications::Notified::load(&lock)?;
+                    let start = match notified.checked() {
+                        Some(checked) if catch_up => checked.min(time - well),
+                        _ => time - well,
+                    };
+                    let res = notified.deliver(
+                        &records,
+                        start,
+                        Bound::Excluded(time + well),
+                        $crate::time::now().naive_local(),
+                        $config.default_duration().duration(),
+                        show,
+                    );
+                    notified.save(&lock)?;
+                    drop(lock);
+                    res?;
+
+                    desktop.wait();
+                    $db.load().await?;
                 }
             }
             Command::Now {
                 well,
                 include_completed,
+                format,
+                template,
             } => {
-                print_entries($db.events_now(get_well(well)?, include_completed).await?);
+                let highlighter = $crate::highlight::Highlighter::new(
+                    &$config,
+                    well.clone().map(|w| get_well(Some(w))).transpose()?,
+                );
+                print_entries(
+                    $db.events_now(get_well(well)?, include_completed).await?,
+                    format,
+                    $crate::cli_processor::template(template, $config.list_template())?,
+                    highlighter,
+                )?;
             }
-            Command::List { all, recur } => {
+            Command::List {
+                all,
+                recur,
+                format,
+                template,
+            } => {
                 if recur {
-                    print_recurring($db.list_recurrence().await?);
+                    print_recurring($db.list_recurrence().await?, format)?;
                 } else {
                     let mut list = if all {
                         $db.list_all(false).await?
@@ -277,19 +439,80 @@ macro_rules! process_cli {
                         $db.list_today(false).await?
                     };
                     list.sort_by($crate::record::sort_records);
-                    print_entries(list);
+                    print_entries(
+                        list,
+                        format,
+                        $crate::cli_processor::template(template, $config.list_template())?,
+                        $crate::highlight::Highlighter::new(&$config, None),
+                    )?;
                 }
             }
-            Command::Today {} => {
-                print_entries($db.list_today(false).await?);
+            Command::Today { format, template } => {
+                print_entries(
+                    $db.list_today(false).await?,
+                    format,
+                    $crate::cli_processor::template(template, $config.list_template())?,
+                    $crate::highlight::Highlighter::new(&$config, None),
+                )?;
             }
-            Command::Entry { args } => {
-                $db.list_all(false).await?;
-                $db.record_entry($crate::parsers::entry::EntryParser::new(
-                    args,
+            Command::Agenda { days, week: _, month } => {
+                let (start, end) = $crate::cli_processor::agenda_range(
+                    $crate::time::now().date_naive(),
+                    days,
+                    month,
+                );
+                print_agenda(
+                    $db.list_range(start, end, false).await?,
+                    start,
+                    end,
                     $config.use_24h_time(),
-                ))
-                .await?;
+                    $crate::highlight::Highlighter::new(&$config, None),
+                )?;
+            }
+            Command::Cal { month, year, list } => {
+                let (start, end) = $crate::cli_processor::month_range(
+                    $crate::time::now().date_naive(),
+                    month,
+                    year,
+                )?;
+                let entries = $db.list_range(start, end, false).await?;
+                print_cal(
+                    &entries,
+                    start,
+                    $crate::highlight::colors_enabled($config.use_color()),
+                )?;
+
+                if list {
+                    println!();
+                    print_agenda(
+                        entries,
+                        start,
+                        end,
+                        $config.use_24h_time(),
+                        $crate::highlight::Highlighter::new(&$config, None),
+                    )?;
+                }
+            }
+            Command::Entry { force, args } => {
+                $db.list_all(false).await?;
+                let overlap = $db
+                    .record_entry(
+                        $crate::parsers::entry::EntryParser::new(args, $config.use_24h_time()),
+                        force,
+                        &$config,
+                    )
+                    .await?;
+                if !overlap.is_empty() {
+                    eprintln!("{}", $crate::conflicts::Overlap(overlap));
+                }
+            }
+            Command::Conflicts { range } => {
+                let range = $crate::parsers::time::parse_date_range(range)?;
+                print_conflicts($crate::conflicts::find_conflicts(
+                    &$db.list_range(range.0, range.1, false).await?,
+                    range,
+                    $config.default_duration().duration(),
+                ));
             }
             Command::Edit { recur, id } => {
                 if recur {
@@ -304,21 +527,155 @@ macro_rules! process_cli {
                     $crate::launch_editor!($db, id, $crate::record::PresentedRecord, get, false);
                 }
             }
-            Command::Show { recur, id } => {
-                if recur {
-                    let presented: $crate::record::PresentedRecurringRecord =
-                        $db.get_recurring(id).await?.into();
-                    println!("{}", serde_yaml::to_string(&presented)?);
-                } else {
-                    let presented: $crate::record::PresentedRecord = $db.get(id).await?.into();
-                    println!("{}", serde_yaml::to_string(&presented)?);
+            Command::Show { recur, id, format } => {
+                use $crate::output::{render_one, EntryOutput, RecurringOutput};
+
+                match (recur, format) {
+                    (true, Some(format)) => print!(
+                        "{}",
+                        render_one(format, RecurringOutput::from($db.get_recurring(id).await?))?
+                    ),
+                    (false, Some(format)) => print!(
+                        "{}",
+                        render_one(format, EntryOutput::from($db.get(id).await?))?
+                    ),
+                    (true, None) => {
+                        let presented: $crate::record::PresentedRecurringRecord =
+                            $db.get_recurring(id).await?.into();
+                        println!("{}", serde_yaml::to_string(&presented)?);
+                    }
+                    (false, None) => {
+                        let presented: $crate::record::PresentedRecord = $db.get(id).await?.into();
+                        println!("{}", serde_yaml::to_string(&presented)?);
+                    }
                 }
             }
-            Command::Search { terms } => {
+            Command::Search {
+                terms,
+                format,
+                template,
+            } => {
                 let parser =
                     $crate::parsers::search::SearchParser::new(terms, $db.list_all(false).await?);
-                print_entries(parser.perform()?);
+                print_entries(
+                    parser.perform()?,
+                    format,
+                    $crate::cli_processor::template(template, $config.list_template())?,
+                    $crate::highlight::Highlighter::new(&$config, None),
+                )?;
             }
+            Command::Free {
+                range,
+                duration,
+                calendars,
+                format,
+            } => {
+                let range = $crate::parsers::time::parse_date_range(range)?;
+                let duration = FancyDuration::<chrono::Duration>::parse(&duration)?.duration();
+
+                let busy = match $client.clone() {
+                    Some(mut client) => {
+                        let calendars = if calendars.is_empty() {
+                            vec![$config.calendar_id()]
+                        } else {
+                            calendars
+                        };
+                        let start = range
+                            .0
+                            .and_time(chrono::NaiveTime::MIN)
+                            .and_local_timezone(chrono::Local)
+                            .unwrap();
+                        let end = (range.1 + chrono::TimeDelta::try_days(1).unwrap_or_default())
+                            .and_time(chrono::NaiveTime::MIN)
+                            .and_local_timezone(chrono::Local)
+                            .unwrap();
+                        client.free_busy(calendars, start, end).await?
+                    }
+                    None if !calendars.is_empty() => {
+                        return Err(anyhow!(
+                            "Other calendars can only be checked with Google Calendar"
+                        ))
+                    }
+                    None => $crate::free::busy_periods(
+                        &$db.list_all(false).await?,
+                        $config.default_duration().duration(),
+                    ),
+                };
+
+                let slots = $crate::free::free_slots(
+                    &busy,
+                    range,
+                    $config.working_hours(),
+                    duration,
+                    $crate::time::now().naive_local(),
+                );
+
+                match format {
+                    Some(format) => print!("{}", $crate::output::render(format, &slots)?),
+                    None => print_free(slots),
+                }
+            }
+            Command::DB { command } => match command {
+                DBCommand::GC { renumber } => match $db.gc(renumber).await? {
+                    Some(report) => {
+                        if !report.removed.is_empty() || !report.renumbered.is_empty() {
+                            let lock = $crate::notifications::Notified::lock()?;
+                            let mut notified = $crate::notifications::Notified::load(&lock)?;
+                            notified.renumber(&report);
+                            notified.save(&lock)?;
+                        }
+
+                        print_gc_report(report)
+                    }
+                    None => eprintln!("Not supported in unixfile mode"),
+                },
+            },
+            Command::Sync { command, prefer } => match command {
+                None => match $config.mirror() {
+                    Some(calendar_id) if $db.sync_state().is_some() => {
+                        let policy: $crate::db::sync::ConflictPolicy =
+                            prefer.map_or(Ok(Default::default()), |p| p.parse())?;
+                        let mut client = $crate::db::google::GoogleClient::new($config.clone())?;
+                        let report = $crate::db::sync::sync(
+                            &mut $db,
+                            &mut client,
+                            calendar_id,
+                            $crate::time::window(&$config),
+                            policy,
+                        )
+                        .await?;
+                        println!(
+                            "Pushed {}, pulled {}, deleted {} remotely and {} locally; {} conflicts resolved",
+                            report.pushed,
+                            report.pulled,
+                            report.deleted_remote,
+                            report.deleted_local,
+                            report.conflicts
+                        );
+                        if !report.errors.is_empty() {
+                            eprintln!("Some changes could not be made, and will be tried again on the next sync:");
+                            for error in report.errors {
+                                eprintln!("  {}", error);
+                            }
+                        }
+                    }
+                    _ => eprintln!("Set a mirror calendar with `saturn config set-mirror` in unixfile mode to synchronize"),
+                },
+                Some(SyncCommand::Status) => {
+                    if let Some(queue) = $db.write_queue() {
+                        print_sync_status(queue);
+                    } else {
+                        eprintln!("Not supported in unixfile mode");
+                    }
+                }
+                Some(SyncCommand::ClearConflicts) => {
+                    if $db.write_queue().is_some() {
+                        $db.clear_conflicts();
+                    } else {
+                        eprintln!("Not supported in unixfile mode");
+                    }
+                }
+            },
         }
 
         $db.dump().await?;
@@ -378,16 +735,29 @@ macro_rules! process_ui_command {
                 }
                 $crate::ui::types::CommandType::Entry(entry) => {
                     $db.list_all(false).await?;
-                    let parts = entry
+                    let mut parts = entry
                         .split(' ')
                         .filter(|x| !x.is_empty())
                         .map(|s| s.to_string())
                         .collect::<Vec<String>>();
-                    $db.record_entry($crate::parsers::entry::EntryParser::new(
-                        parts,
-                        $config.use_24h_time(),
-                    ))
-                    .await?;
+                    // `entry force ...` does not warn when it overlaps existing entries
+                    let force = parts.first().is_some_and(|x| x == "force");
+                    if force {
+                        parts.remove(0);
+                    }
+                    let overlap = $db
+                        .record_entry(
+                            $crate::parsers::entry::EntryParser::new(parts, $config.use_24h_time()),
+                            force,
+                            &$config,
+                        )
+                        .await?;
+                    if !overlap.is_empty() {
+                        $obj.lock()
+                            .await
+                            .errors
+                            .push($crate::conflicts::Overlap(overlap).to_string());
+                    }
                 }
                 $crate::ui::types::CommandType::Edit(recur, id) => {
                     if recur {
@@ -426,3 +796,29 @@ macro_rules! process_ui_command {
         lock.block_ui = false;
     }};
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_month_range() {
+        use super::month_range;
+
+        let date = |y, m, d| chrono::NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let today = date(2024, 2, 14);
+
+        assert_eq!(
+            month_range(today, None, None).unwrap(),
+            (date(2024, 2, 1), date(2024, 2, 29))
+        );
+        assert_eq!(
+            month_range(today, Some(12), None).unwrap(),
+            (date(2024, 12, 1), date(2024, 12, 31))
+        );
+        assert_eq!(
+            month_range(today, Some(2), Some(2023)).unwrap(),
+            (date(2023, 2, 1), date(2023, 2, 28))
+        );
+        assert!(month_range(today, Some(13), None).is_err());
+        assert!(month_range(today, None, Some(i32::MAX)).is_err());
+    }
+}
@@ -1,5 +1,11 @@
-use crate::filenames::saturn_config;
-use anyhow::Result;
+use crate::filenames::{
+    saturn_config, saturn_config_for, saturn_secrets, valid_profile, DEFAULT_PROFILE,
+};
+use crate::highlight::{parse_color, ColorScheme};
+use crate::notifications::SinkKind;
+use crate::parsers::time::parse_hours;
+use crate::template::Template;
+use anyhow::{anyhow, Context, Result};
 use chrono::Duration;
 use fancy_duration::FancyDuration;
 use gcal::ClientParameters;
@@ -12,20 +18,220 @@ pub enum DBType {
     Google,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Config {
-    db_type: DBType,
+impl std::str::FromStr for DBType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "google" => Ok(Self::Google),
+            "unixfile" => Ok(Self::UnixFile),
+            _ => Err(anyhow!(
+                "Invalid db type: valid types are `google` and `unixfile`"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for DBType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Google => "google",
+            Self::UnixFile => "unixfile",
+        })
+    }
+}
+
+// SETTINGS are the keys of the configuration file, which `saturn config get` and `saturn config
+// set` also use.
+pub const SETTINGS: &[&str] = &[
+    "db_type",
+    "calendar_id",
+    "redirect_url",
+    "sync_duration",
+    "default_duration",
+    "use_24h_time",
+    "query_window",
+    "cache_ttl",
+    "mirror",
+    "max_retries",
+    "retry_delay",
+    "working_hours",
+    "list_template",
+    "notify_template",
+    "notify_sink",
+    "use_color",
+    "color_in_progress",
+    "color_upcoming",
+    "color_overdue",
+    "color_completed",
+    "upcoming_window",
+];
+
+// LEGACY_SECRETS are the credentials older versions kept in the configuration file.
+const LEGACY_SECRETS: &[&str] = &[
+    "access_token",
+    "access_token_expires_at",
+    "refresh_token",
+    "refresh_token_expires_at",
+    "client_info",
+];
+
+// Secrets are the OAuth credentials. They are kept in their own file, which only the user can read,
+// and are never shown in debug output.
+#[derive(Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct Secrets {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     access_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     access_token_expires_at: Option<chrono::NaiveDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     refresh_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     refresh_token_expires_at: Option<chrono::NaiveDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     client_info: Option<(String, String)>,
+}
+
+impl std::fmt::Debug for Secrets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let redact = |s: &Option<String>| s.as_ref().map(|_| "<redacted>");
+
+        f.debug_struct("Secrets")
+            .field("access_token", &redact(&self.access_token))
+            .field("access_token_expires_at", &self.access_token_expires_at)
+            .field("refresh_token", &redact(&self.refresh_token))
+            .field("refresh_token_expires_at", &self.refresh_token_expires_at)
+            .field("client_id", &self.client_info.as_ref().map(|c| &c.0))
+            .field(
+                "client_secret",
+                &redact(&self.client_info.as_ref().map(|c| c.1.clone())),
+            )
+            .finish()
+    }
+}
+
+impl Secrets {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // or fills in anything missing from other.
+    fn or(self, other: Self) -> Self {
+        Self {
+            access_token: self.access_token.or(other.access_token),
+            access_token_expires_at: self
+                .access_token_expires_at
+                .or(other.access_token_expires_at),
+            refresh_token: self.refresh_token.or(other.refresh_token),
+            refresh_token_expires_at: self
+                .refresh_token_expires_at
+                .or(other.refresh_token_expires_at),
+            client_info: self.client_info.or(other.client_info),
+        }
+    }
+
+    fn load(path: &std::path::Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(s) => Ok(serde_yaml::from_str(&s)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, path: &std::path::Path) -> Result<()> {
+        let mut io = std::fs::OpenOptions::new();
+        io.write(true);
+        io.truncate(true);
+        io.create(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut io, 0o600);
+        create_parent(path)?;
+        let io = io.open(path)?;
+
+        // the mode is only applied to new files
+        #[cfg(unix)]
+        std::fs::set_permissions(path, std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+
+        Ok(serde_yaml::to_writer(io, self)?)
+    }
+}
+
+// create_parent makes the directory a file is saved in, for files in the XDG directories.
+fn create_parent(path: &std::path::Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    Ok(())
+}
+
+// show_duration formats a duration so it parses back the same. FancyDuration shows 30 days as
+// `1m`, which parses as a minute, so days are the largest unit used.
+fn show_duration(duration: Duration) -> String {
+    let days = duration.num_days();
+    let rest = duration - chrono::TimeDelta::try_days(days).unwrap_or_default();
+
+    match (days, rest.is_zero()) {
+        (0, _) => FancyDuration::new(duration).to_string(),
+        (_, true) => format!("{}d", days),
+        _ => format!("{}d {}", days, FancyDuration::new(rest).to_string()),
+    }
+}
+
+fn unknown_setting(key: &str) -> anyhow::Error {
+    anyhow!(
+        "Unknown setting `{}`; valid settings are {}",
+        key,
+        SETTINGS.join(", ")
+    )
+}
+
+// the secrets of a config file loaded from somewhere else are kept next to it.
+fn secrets_path(filename: &Option<std::path::PathBuf>) -> std::path::PathBuf {
+    filename
+        .as_ref()
+        .map_or_else(saturn_secrets, |f| f.with_extension("secrets"))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    db_type: DBType,
+    #[serde(skip)]
+    secrets: Secrets,
     redirect_url: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     sync_duration: Option<FancyDuration<Duration>>,
     default_duration: Option<FancyDuration<Duration>>,
     use_24h_time: Option<bool>,
     query_window: Option<FancyDuration<Duration>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cache_ttl: Option<FancyDuration<Duration>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mirror: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_retries: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    retry_delay: Option<FancyDuration<Duration>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    working_hours: Option<(chrono::NaiveTime, chrono::NaiveTime)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    list_template: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notify_template: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notify_sink: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    use_color: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color_in_progress: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color_upcoming: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color_overdue: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color_completed: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    upcoming_window: Option<FancyDuration<Duration>>,
     calendar_id: String,
 }
 
@@ -49,76 +255,292 @@ impl Default for Config {
             query_window: Some(FancyDuration::new(chrono::TimeDelta::try_days(30).unwrap_or_default())),
             use_24h_time: Some(false),
             db_type: DBType::UnixFile,
-            access_token: None,
-            access_token_expires_at: None,
-            refresh_token: None,
-            refresh_token_expires_at: None,
+            secrets: Secrets::default(),
             redirect_url: None,
-            client_info: None,
             sync_duration: None,
             default_duration: None,
+            cache_ttl: None,
+            mirror: None,
+            max_retries: None,
+            retry_delay: None,
+            working_hours: None,
+            list_template: None,
+            notify_template: None,
+            notify_sink: None,
+            use_color: None,
+            color_in_progress: None,
+            color_upcoming: None,
+            color_overdue: None,
+            color_completed: None,
+            upcoming_window: None,
             calendar_id: "primary".to_string(),
         }
     }
 }
 
 impl Config {
+    // load reads the config and its secrets. Credentials found in the config itself, where older
+    // versions kept them, are moved to the secrets file.
     pub fn load(filename: Option<std::path::PathBuf>) -> Result<Self> {
-        let path = filename.unwrap_or(saturn_config());
-        let mut io = std::fs::OpenOptions::new();
-        io.read(true);
+        let path = filename.clone().unwrap_or(saturn_config());
+
+        let (mut config, legacy) = match std::fs::read_to_string(&path) {
+            Ok(s) => Self::parse(&s)
+                .with_context(|| format!("Invalid configuration in {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                (Self::default(), Secrets::default())
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        config.secrets = Secrets::load(&secrets_path(&filename))?;
+
+        if !legacy.is_empty() {
+            config.secrets = config.secrets.or(legacy);
+            config.save(filename)?;
+        }
+
+        Ok(config)
+    }
 
-        match io.open(path) {
-            Ok(io) => Ok(serde_yaml::from_reader(io)?),
-            Err(_) => Ok(Self::default()),
+    // parse reads a configuration file, along with any credentials left in it by older versions.
+    // Settings saturn does not know about, usually typos, are refused along with the line they are
+    // on instead of being ignored.
+    fn parse(s: &str) -> Result<(Self, Secrets)> {
+        if let serde_yaml::Value::Mapping(map) = serde_yaml::from_str(s)? {
+            for key in map.keys() {
+                let key = key.as_str().unwrap_or_default();
+                if SETTINGS.contains(&key) || LEGACY_SECRETS.contains(&key) {
+                    continue;
+                }
+
+                let line = s
+                    .lines()
+                    .position(|l| {
+                        l.trim_start_matches(['\'', '"'])
+                            .strip_prefix(key)
+                            .is_some_and(|rest| {
+                                rest.trim_start_matches(['\'', '"'])
+                                    .trim_start()
+                                    .starts_with(':')
+                            })
+                    })
+                    .map_or_else(String::new, |l| format!("line {}: ", l + 1));
+
+                return Err(anyhow!("{}{}", line, unknown_setting(key)));
+            }
         }
+
+        Ok((serde_yaml::from_str(s)?, serde_yaml::from_str(s)?))
     }
 
     pub fn save(&self, filename: Option<std::path::PathBuf>) -> Result<()> {
+        self.secrets.save(&secrets_path(&filename))?;
+
         let path = filename.unwrap_or(saturn_config());
         let mut io = std::fs::OpenOptions::new();
         io.write(true);
         io.truncate(true);
         io.create(true);
+        create_parent(&path)?;
         let io = io.open(path)?;
 
         Ok(serde_yaml::to_writer(io, self)?)
     }
 
+    // create_profile writes the default configuration for a new profile.
+    pub fn create_profile(name: &str) -> Result<()> {
+        valid_profile(name)?;
+
+        let path = saturn_config_for(Some(name));
+        if name == DEFAULT_PROFILE || path.exists() {
+            return Err(anyhow!("Profile '{}' already exists", name));
+        }
+
+        Self::default().save(Some(path))
+    }
+
+    // settings are the values of every setting, as `saturn config show` prints them. Durations,
+    // retries and working hours are shown with their defaults when they are not set; credentials
+    // are redacted.
+    pub fn settings(&self) -> Vec<(&'static str, String)> {
+        let redact = |s: Option<String>| s.map(|_| "<redacted>".to_string()).unwrap_or_default();
+        let hours = self.working_hours();
+
+        vec![
+            ("db_type", self.db_type().to_string()),
+            ("calendar_id", self.calendar_id()),
+            ("redirect_url", self.redirect_url().unwrap_or_default()),
+            (
+                "sync_duration",
+                self.sync_duration
+                    .as_ref()
+                    .map(|d| show_duration(d.duration()))
+                    .unwrap_or_default(),
+            ),
+            (
+                "default_duration",
+                show_duration(self.default_duration().duration()),
+            ),
+            ("use_24h_time", self.use_24h_time().to_string()),
+            ("query_window", show_duration(self.query_window())),
+            ("cache_ttl", show_duration(self.cache_ttl())),
+            ("mirror", self.mirror().unwrap_or_default()),
+            ("max_retries", self.max_retries().to_string()),
+            ("retry_delay", show_duration(self.retry_delay())),
+            (
+                "working_hours",
+                format!("{}-{}", hours.0.format("%H:%M"), hours.1.format("%H:%M")),
+            ),
+            ("list_template", self.list_template().unwrap_or_default()),
+            (
+                "notify_template",
+                self.notify_template().unwrap_or_default(),
+            ),
+            (
+                "notify_sink",
+                self.notify_sink
+                    .clone()
+                    .unwrap_or(SinkKind::Desktop.to_string()),
+            ),
+            ("use_color", self.use_color().to_string()),
+            ("color_in_progress", self.color("color_in_progress")),
+            ("color_upcoming", self.color("color_upcoming")),
+            ("color_overdue", self.color("color_overdue")),
+            ("color_completed", self.color("color_completed")),
+            ("upcoming_window", show_duration(self.upcoming_window())),
+            ("client_id", self.client_id().unwrap_or_default()),
+            ("client_secret", redact(self.client_secret())),
+            ("access_token", redact(self.access_token())),
+            (
+                "access_token_expires_at",
+                self.access_token_expires_at()
+                    .map(|t| t.to_string())
+                    .unwrap_or_default(),
+            ),
+            ("refresh_token", redact(self.refresh_token())),
+            (
+                "refresh_token_expires_at",
+                self.refresh_token_expires_at()
+                    .map(|t| t.to_string())
+                    .unwrap_or_default(),
+            ),
+        ]
+    }
+
+    pub fn get(&self, key: &str) -> Result<String> {
+        self.settings()
+            .into_iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v)
+            .ok_or_else(|| unknown_setting(key))
+    }
+
+    // set changes a setting from its text form, the same form `settings` shows it in. An empty
+    // value clears the settings which may be left unset.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let optional = || Some(value.to_string()).filter(|v| !v.is_empty());
+        let duration =
+            || -> Result<Duration> { Ok(FancyDuration::<Duration>::parse(value)?.duration()) };
+
+        if !SETTINGS.contains(&key) && self.settings().iter().any(|(k, _)| *k == key) {
+            return Err(anyhow!(
+                "`{}` is kept with your credentials; use `saturn config set-client` or `saturn config get-token` to change it",
+                key
+            ));
+        }
+
+        match key {
+            "db_type" => self.set_db_type(value.parse()?),
+            "calendar_id" => self.set_calendar_id(value.to_string()),
+            "redirect_url" => self.set_redirect_url(optional()),
+            "sync_duration" => {
+                self.sync_duration = match optional() {
+                    Some(_) => Some(FancyDuration::new(duration()?)),
+                    None => None,
+                }
+            }
+            "default_duration" => self.set_default_duration(Some(FancyDuration::new(duration()?))),
+            "use_24h_time" => self.set_use_24h_time(
+                value
+                    .parse()
+                    .map_err(|_| anyhow!("`use_24h_time` must be `true` or `false`"))?,
+            ),
+            "query_window" => self.set_query_window(duration()?),
+            "cache_ttl" => self.set_cache_ttl(duration()?),
+            "mirror" => self.set_mirror(optional()),
+            "max_retries" => self.set_max_retries(value.parse()?),
+            "retry_delay" => self.set_retry_delay(duration()?),
+            "working_hours" => {
+                let (start, end) = value.split_once('-').ok_or_else(|| {
+                    anyhow!("Working hours are given as `start-end`, e.g. `9:00-17:00`")
+                })?;
+                let (start, end) = parse_hours(
+                    start.trim().to_string(),
+                    end.trim().to_string(),
+                    self.use_24h_time(),
+                )?;
+                self.set_working_hours(start, end)
+            }
+            "list_template" => self.set_list_template(optional())?,
+            "notify_template" => self.set_notify_template(optional())?,
+            "notify_sink" => self.set_notify_sink(optional())?,
+            "use_color" => self.set_use_color(
+                value
+                    .parse()
+                    .map_err(|_| anyhow!("`use_color` must be `true` or `false`"))?,
+            ),
+            "color_in_progress" | "color_upcoming" | "color_overdue" | "color_completed" => {
+                parse_color(value)?;
+                let color = Some(value.to_string());
+                match key {
+                    "color_in_progress" => self.color_in_progress = color,
+                    "color_upcoming" => self.color_upcoming = color,
+                    "color_overdue" => self.color_overdue = color,
+                    _ => self.color_completed = color,
+                }
+            }
+            "upcoming_window" => self.set_upcoming_window(duration()?),
+            _ => return Err(unknown_setting(key)),
+        }
+
+        Ok(())
+    }
+
     pub fn set_calendar_id(&mut self, calendar_id: String) {
         self.calendar_id = calendar_id;
     }
 
     pub fn set_access_token(&mut self, access_token: Option<String>) {
-        self.access_token = access_token;
+        self.secrets.access_token = access_token;
     }
 
     pub fn set_access_token_expires_at(&mut self, expires_at: Option<chrono::NaiveDateTime>) {
-        self.access_token_expires_at = expires_at;
+        self.secrets.access_token_expires_at = expires_at;
     }
 
     pub fn set_refresh_token(&mut self, refresh_token: Option<String>) {
-        self.refresh_token = refresh_token;
+        self.secrets.refresh_token = refresh_token;
     }
 
     pub fn set_refresh_token_expires_at(&mut self, expires_at: Option<chrono::NaiveDateTime>) {
-        self.refresh_token_expires_at = expires_at;
+        self.secrets.refresh_token_expires_at = expires_at;
     }
 
     pub fn access_token(&self) -> Option<String> {
-        self.access_token.clone()
+        self.secrets.access_token.clone()
     }
 
     pub fn access_token_expires_at(&self) -> Option<chrono::NaiveDateTime> {
-        self.access_token_expires_at
+        self.secrets.access_token_expires_at
     }
 
     pub fn refresh_token(&self) -> Option<String> {
-        self.refresh_token.clone()
+        self.secrets.refresh_token.clone()
     }
 
     pub fn refresh_token_expires_at(&self) -> Option<chrono::NaiveDateTime> {
-        self.refresh_token_expires_at
+        self.secrets.refresh_token_expires_at
     }
 
     pub fn set_redirect_url(&mut self, redirect_url: Option<String>) {
@@ -171,19 +593,250 @@ impl Config {
         self.query_window = Some(FancyDuration::new(window))
     }
 
+    pub fn cache_ttl(&self) -> chrono::Duration {
+        self.cache_ttl.clone().map_or_else(
+            || chrono::TimeDelta::try_minutes(5).unwrap_or_default(),
+            |x| x.duration(),
+        )
+    }
+
+    pub fn set_cache_ttl(&mut self, ttl: chrono::Duration) {
+        self.cache_ttl = Some(FancyDuration::new(ttl))
+    }
+
+    // max_retries is how many times a request to Google Calendar is retried when it is rate limited
+    // or fails on the server side. Retries back off exponentially from retry_delay.
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries.unwrap_or(5)
+    }
+
+    pub fn set_max_retries(&mut self, retries: u32) {
+        self.max_retries = Some(retries)
+    }
+
+    pub fn retry_delay(&self) -> chrono::Duration {
+        self.retry_delay.clone().map_or_else(
+            || chrono::TimeDelta::try_milliseconds(500).unwrap_or_default(),
+            |x| x.duration(),
+        )
+    }
+
+    pub fn set_retry_delay(&mut self, delay: chrono::Duration) {
+        self.retry_delay = Some(FancyDuration::new(delay))
+    }
+
+    // working_hours is the part of the day `saturn free` looks for open time in.
+    pub fn working_hours(&self) -> (chrono::NaiveTime, chrono::NaiveTime) {
+        self.working_hours.unwrap_or((
+            chrono::NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            chrono::NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        ))
+    }
+
+    pub fn set_working_hours(&mut self, start: chrono::NaiveTime, end: chrono::NaiveTime) {
+        self.working_hours = Some((start, end))
+    }
+
+    // list_template replaces the table `saturn list` and friends print with a line per entry; see
+    // the template module for the syntax.
+    pub fn list_template(&self) -> Option<String> {
+        self.list_template.clone()
+    }
+
+    pub fn set_list_template(&mut self, template: Option<String>) -> Result<()> {
+        if let Some(template) = &template {
+            template.parse::<Template>()?;
+        }
+
+        self.list_template = template;
+        Ok(())
+    }
+
+    // notify_template is the body of the notifications `saturn notify` sends.
+    pub fn notify_template(&self) -> Option<String> {
+        self.notify_template.clone()
+    }
+
+    pub fn set_notify_template(&mut self, template: Option<String>) -> Result<()> {
+        if let Some(template) = &template {
+            template.parse::<Template>()?;
+        }
+
+        self.notify_template = template;
+        Ok(())
+    }
+
+    // notify_sink is where notifications are sent, unless a record has a sink of its own.
+    pub fn notify_sink(&self) -> Result<SinkKind> {
+        self.notify_sink
+            .as_deref()
+            .map_or(Ok(SinkKind::Desktop), |sink| sink.parse())
+    }
+
+    pub fn set_notify_sink(&mut self, sink: Option<String>) -> Result<()> {
+        if let Some(sink) = &sink {
+            sink.parse::<SinkKind>()?;
+        }
+
+        self.notify_sink = sink;
+        Ok(())
+    }
+
+    // use_color turns the highlighting of listings by the state of each entry on or off. It is
+    // also off when NO_COLOR is set or the output is not a terminal.
+    pub fn use_color(&self) -> bool {
+        self.use_color.unwrap_or(true)
+    }
+
+    pub fn set_use_color(&mut self, use_color: bool) {
+        self.use_color = Some(use_color)
+    }
+
+    fn color(&self, key: &str) -> String {
+        let (color, default) = match key {
+            "color_in_progress" => (&self.color_in_progress, "green"),
+            "color_upcoming" => (&self.color_upcoming, "yellow"),
+            "color_overdue" => (&self.color_overdue, "red"),
+            _ => (&self.color_completed, "dark_grey"),
+        };
+
+        color.clone().unwrap_or(default.to_string())
+    }
+
+    pub fn color_scheme(&self) -> ColorScheme {
+        let color = |key| parse_color(&self.color(key)).unwrap_or_default();
+
+        ColorScheme {
+            in_progress: color("color_in_progress"),
+            upcoming: color("color_upcoming"),
+            overdue: color("color_overdue"),
+            completed: color("color_completed"),
+        }
+    }
+
+    // upcoming_window is how soon an entry has to start to be highlighted as upcoming.
+    pub fn upcoming_window(&self) -> chrono::Duration {
+        self.upcoming_window.clone().map_or_else(
+            || chrono::TimeDelta::try_minutes(15).unwrap_or_default(),
+            |x| x.duration(),
+        )
+    }
+
+    pub fn set_upcoming_window(&mut self, window: chrono::Duration) {
+        self.upcoming_window = Some(FancyDuration::new(window))
+    }
+
+    // mirror is the Google calendar a unixfile database is synchronized with by `saturn sync`.
+    pub fn mirror(&self) -> Option<String> {
+        self.mirror.clone()
+    }
+
+    pub fn set_mirror(&mut self, calendar_id: Option<String>) {
+        self.mirror = calendar_id
+    }
+
     pub fn set_client_info(&mut self, client_id: String, client_secret: String) {
-        self.client_info = Some((client_id, client_secret))
+        self.secrets.client_info = Some((client_id, client_secret))
     }
 
     pub fn has_client(&self) -> bool {
-        self.client_info.is_some()
+        self.secrets.client_info.is_some()
     }
 
     pub fn client_id(&self) -> Option<String> {
-        self.client_info.clone().map(|s| s.0)
+        self.secrets.client_info.clone().map(|s| s.0)
     }
 
     pub fn client_secret(&self) -> Option<String> {
-        self.client_info.clone().map(|s| s.1)
+        self.secrets.client_info.clone().map(|s| s.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_secrets() {
+        use super::Config;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("saturn.conf");
+        let secrets = dir.path().join("saturn.secrets");
+
+        // credentials in the config itself are moved out on load
+        std::fs::write(
+            &path,
+            "db_type: Google\ncalendar_id: primary\naccess_token: sekrit\nclient_info:\n- id\n- hush\n",
+        )
+        .unwrap();
+
+        let config = Config::load(Some(path.clone())).unwrap();
+        assert_eq!(config.access_token(), Some("sekrit".to_string()));
+        assert_eq!(config.client_id(), Some("id".to_string()));
+        assert_eq!(config.client_secret(), Some("hush".to_string()));
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("sekrit") && !text.contains("hush"));
+        assert!(std::fs::read_to_string(&secrets)
+            .unwrap()
+            .contains("sekrit"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&secrets).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let debug = format!("{:?}", config);
+        assert!(!debug.contains("sekrit") && !debug.contains("hush"));
+
+        let mut config = Config::load(Some(path.clone())).unwrap();
+        assert_eq!(config.access_token(), Some("sekrit".to_string()));
+
+        config.set_access_token(Some("fresh".to_string()));
+        config.save(Some(path.clone())).unwrap();
+        assert_eq!(
+            Config::load(Some(path)).unwrap().access_token(),
+            Some("fresh".to_string())
+        );
+    }
+
+    #[test]
+    fn test_settings() {
+        use super::{Config, SETTINGS};
+
+        let mut config = Config::default();
+        config.set("working_hours", "8:30-16:00").unwrap();
+        config.set("query_window", "30d 5m").unwrap();
+        config.set("mirror", "work@example.com").unwrap();
+        assert_eq!(config.get("working_hours").unwrap(), "08:30-16:00");
+        assert_eq!(config.get("query_window").unwrap(), "30d 5m");
+
+        // every setting takes back the value it shows
+        let mut copy = Config::default();
+        for key in SETTINGS {
+            copy.set(key, &config.get(key).unwrap()).unwrap();
+        }
+        assert_eq!(copy.settings(), config.settings());
+
+        config.set("mirror", "").unwrap();
+        assert_eq!(config.mirror(), None);
+
+        assert!(config.set("working_hours", "17:00-9:00").is_err());
+        assert!(config.set("use_24h_time", "yes").is_err());
+        assert!(config.set("notify_sink", "pager").is_err());
+        assert!(config.set("access_token", "sekrit").is_err());
+        assert!(config.set("cahce_ttl", "1m").is_err());
+        assert!(config.get("cahce_ttl").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("saturn.conf");
+        std::fs::write(
+            &path,
+            "db_type: UnixFile\ncalendar_id: primary\ncahce_ttl: 1m\n",
+        )
+        .unwrap();
+        let err = format!("{:#}", Config::load(Some(path)).unwrap_err());
+        assert!(err.contains("line 3: Unknown setting `cahce_ttl`"));
     }
 }
@@ -0,0 +1,143 @@
+use crate::{
+    free::{busy_period, Period},
+    record::Record,
+};
+
+// Overlap warns that a new entry takes place at the same time as existing records. It carries the
+// existing records.
+#[derive(Debug, Clone)]
+pub struct Overlap(pub Vec<Record>);
+
+impl std::fmt::Display for Overlap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!(
+            "Warning: this entry overlaps with {} {}; use --force (or `entry force` in sui) to skip this check",
+            if self.0.len() == 1 { "ID" } else { "IDs" },
+            self.0
+                .iter()
+                .map(|r| r.primary_key().to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ))
+    }
+}
+
+fn overlaps(a: &Period, b: &Period) -> bool {
+    a.0 < b.1 && b.0 < a.1
+}
+
+// overlapping returns the records which take place at the same time as the record. At records take
+// up the default duration; all day events never overlap anything.
+pub fn overlapping(
+    record: &Record,
+    records: &[Record],
+    default_duration: chrono::Duration,
+) -> Vec<Record> {
+    let period = match busy_period(record, default_duration) {
+        Some(period) => period,
+        None => return Vec::new(),
+    };
+
+    records
+        .iter()
+        .filter(|other| other.primary_key() != record.primary_key())
+        .filter(|other| busy_period(other, default_duration).is_some_and(|p| overlaps(&period, &p)))
+        .cloned()
+        .collect()
+}
+
+// find_conflicts returns each pair of records starting within the range which overlap, in the
+// order they start.
+pub fn find_conflicts(
+    records: &[Record],
+    range: (chrono::NaiveDate, chrono::NaiveDate),
+    default_duration: chrono::Duration,
+) -> Vec<(Record, Record)> {
+    let mut periods = records
+        .iter()
+        .filter(|r| r.date() >= range.0 && r.date() <= range.1)
+        .filter_map(|r| busy_period(r, default_duration).map(|p| (p, r)))
+        .collect::<Vec<(Period, &Record)>>();
+    periods.sort_by(|a, b| {
+        a.0.cmp(&b.0)
+            .then(a.1.primary_key().cmp(&b.1.primary_key()))
+    });
+
+    let mut conflicts = Vec::new();
+
+    for (i, (period, record)) in periods.iter().enumerate() {
+        for (other, other_record) in &periods[i + 1..] {
+            // sorted by start, so nothing after this can overlap either
+            if other.0 >= period.1 {
+                break;
+            }
+
+            if overlaps(period, other) {
+                conflicts.push(((*record).clone(), (*other_record).clone()));
+            }
+        }
+    }
+
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_conflicts() {
+        use super::{find_conflicts, overlapping};
+        use crate::record::Record;
+
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 11).unwrap();
+        let time = |h, m| chrono::NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let fifteen = chrono::TimeDelta::try_minutes(15).unwrap_or_default();
+
+        let mut standup = Record::build();
+        standup
+            .set_primary_key(1)
+            .set_date(date)
+            .set_at(Some(time(9, 0)));
+
+        let mut planning = Record::build();
+        planning
+            .set_primary_key(2)
+            .set_date(date)
+            .set_scheduled(Some((time(9, 10), time(10, 0))));
+
+        let mut review = Record::build();
+        review
+            .set_primary_key(3)
+            .set_date(date)
+            .set_scheduled(Some((time(10, 0), time(11, 0))));
+
+        let mut holiday = Record::build();
+        holiday.set_primary_key(4).set_date(date).set_all_day();
+
+        let records = vec![standup, planning, review, holiday.clone()];
+
+        let mut entry = Record::build();
+        entry
+            .set_primary_key(5)
+            .set_date(date)
+            .set_scheduled(Some((time(9, 50), time(10, 30))));
+
+        let found = overlapping(&entry, &records, fifteen)
+            .iter()
+            .map(|r| r.primary_key())
+            .collect::<Vec<u64>>();
+        assert_eq!(found, vec![2, 3]);
+
+        entry.set_at(Some(time(11, 0)));
+        assert!(overlapping(&entry, &records, fifteen).is_empty());
+        assert!(overlapping(&holiday, &records, fifteen).is_empty());
+
+        let conflicts = find_conflicts(&records, (date, date), fifteen)
+            .iter()
+            .map(|(a, b)| (a.primary_key(), b.primary_key()))
+            .collect::<Vec<(u64, u64)>>();
+        assert_eq!(conflicts, vec![(1, 2)]);
+
+        let tomorrow = date.succ_opt().unwrap();
+        assert!(find_conflicts(&records, (tomorrow, tomorrow), fifteen).is_empty());
+    }
+}
@@ -0,0 +1,218 @@
+use crate::{
+    record::{Record, RecurringRecord},
+    time::now,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// RecordCache holds the results of the last reads from a remote calendar, so that reads can be
+// answered locally until the TTL runs out. Stale data is kept around so that it can still be
+// served when the remote side cannot be reached.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RecordCache {
+    records: BTreeMap<u64, Record>,
+    recurring: BTreeMap<u64, RecurringRecord>,
+    listed_at: Option<chrono::DateTime<chrono::Local>>,
+    recurring_listed_at: Option<chrono::DateTime<chrono::Local>>,
+}
+
+fn fresh(at: Option<chrono::DateTime<chrono::Local>>, ttl: chrono::Duration) -> bool {
+    at.is_some_and(|at| at + ttl > now())
+}
+
+impl RecordCache {
+    pub fn is_fresh(&self, ttl: chrono::Duration) -> bool {
+        fresh(self.listed_at, ttl)
+    }
+
+    pub fn is_recurring_fresh(&self, ttl: chrono::Duration) -> bool {
+        fresh(self.recurring_listed_at, ttl)
+    }
+
+    pub fn is_populated(&self) -> bool {
+        self.listed_at.is_some() || !self.records.is_empty()
+    }
+
+    pub fn is_recurring_populated(&self) -> bool {
+        self.recurring_listed_at.is_some() || !self.recurring.is_empty()
+    }
+
+    pub fn replace(&mut self, records: Vec<Record>) {
+        self.records = records.into_iter().map(|r| (r.primary_key(), r)).collect();
+        self.listed_at = Some(now());
+    }
+
+    // merge applies the changes from an incremental sync on top of the current listing.
+    pub fn merge(&mut self, records: Vec<Record>, removed: Vec<u64>) {
+        for primary_key in removed {
+            self.records.remove(&primary_key);
+        }
+
+        for record in records {
+            self.records.insert(record.primary_key(), record);
+        }
+
+        self.listed_at = Some(now());
+    }
+
+    pub fn replace_recurring(&mut self, records: Vec<RecurringRecord>) {
+        self.recurring = records
+            .into_iter()
+            .map(|r| (r.recurrence_key(), r))
+            .collect();
+        self.recurring_listed_at = Some(now());
+    }
+
+    pub fn records(&self, include_completed: bool) -> Vec<Record> {
+        self.records
+            .values()
+            .filter(|r| include_completed || !r.completed())
+            .cloned()
+            .collect()
+    }
+
+    pub fn recurring(&self) -> Vec<RecurringRecord> {
+        self.recurring.values().cloned().collect()
+    }
+
+    pub fn get(&self, primary_key: u64) -> Option<Record> {
+        self.records.get(&primary_key).cloned()
+    }
+
+    pub fn get_recurring(&self, recurrence_key: u64) -> Option<RecurringRecord> {
+        self.recurring.get(&recurrence_key).cloned()
+    }
+
+    pub fn insert(&mut self, record: Record) {
+        self.records.insert(record.primary_key(), record);
+    }
+
+    pub fn insert_recurring(&mut self, record: RecurringRecord) {
+        self.recurring.insert(record.recurrence_key(), record);
+    }
+
+    // only replaces records that are already known; individually fetched records may fall outside
+    // of the listing window, and should not show up in listings.
+    pub fn refresh(&mut self, record: Record) {
+        if let Some(existing) = self.records.get_mut(&record.primary_key()) {
+            *existing = record;
+        }
+    }
+
+    pub fn set_completed(&mut self, primary_key: u64) {
+        if let Some(record) = self.records.get_mut(&primary_key) {
+            record.set_completed(true);
+        }
+    }
+
+    pub fn remove(&mut self, primary_key: u64) {
+        self.records.remove(&primary_key);
+    }
+
+    pub fn remove_recurrence(&mut self, recurrence_key: u64) {
+        self.recurring.remove(&recurrence_key);
+        self.records
+            .retain(|_, r| r.recurrence_key() != Some(recurrence_key));
+    }
+
+    // expire marks the listings as stale without discarding them.
+    pub fn expire(&mut self) {
+        self.listed_at = None;
+        self.recurring_listed_at = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_freshness() {
+        use super::RecordCache;
+        use crate::record::Record;
+
+        let ttl = chrono::TimeDelta::try_minutes(5).unwrap_or_default();
+        let mut cache = RecordCache::default();
+        assert!(!cache.is_fresh(ttl));
+        assert!(!cache.is_populated());
+
+        let mut record = Record::default();
+        record.set_primary_key(1);
+        cache.replace(vec![record]);
+        assert!(cache.is_fresh(ttl));
+        assert!(!cache.is_fresh(chrono::Duration::zero()));
+        assert!(!cache.is_recurring_fresh(ttl));
+
+        cache.expire();
+        assert!(!cache.is_fresh(ttl));
+        assert!(cache.is_populated());
+        assert_eq!(cache.records(false).len(), 1);
+    }
+
+    #[test]
+    fn test_merge() {
+        use super::RecordCache;
+        use crate::record::Record;
+
+        let ttl = chrono::TimeDelta::try_minutes(5).unwrap_or_default();
+        let mut cache = RecordCache::default();
+        let mut records = Vec::new();
+
+        for x in 1..=3 {
+            let mut record = Record::default();
+            record.set_primary_key(x);
+            records.push(record);
+        }
+
+        cache.replace(records);
+        cache.expire();
+
+        let mut changed = Record::default();
+        changed.set_primary_key(2);
+        changed.set_detail("changed".to_string());
+        let mut added = Record::default();
+        added.set_primary_key(4);
+
+        cache.merge(vec![changed, added], vec![1]);
+        assert!(cache.is_fresh(ttl));
+        assert!(cache.get(1).is_none());
+        assert_eq!(cache.get(2).unwrap().detail(), "changed");
+        assert!(cache.get(3).is_some());
+        assert!(cache.get(4).is_some());
+    }
+
+    #[test]
+    fn test_invalidation() {
+        use super::RecordCache;
+        use crate::record::Record;
+
+        let mut cache = RecordCache::default();
+        let mut records = Vec::new();
+
+        for x in 1..=6 {
+            let mut record = Record::default();
+            record.set_primary_key(x);
+            if x % 2 == 0 {
+                record.set_recurrence_key(Some(1));
+            }
+            records.push(record);
+        }
+
+        cache.replace(records);
+        cache.remove(1);
+        assert!(cache.get(1).is_none());
+        assert_eq!(cache.records(true).len(), 5);
+
+        cache.set_completed(3);
+        assert_eq!(cache.records(false).len(), 4);
+        assert_eq!(cache.records(true).len(), 5);
+
+        cache.remove_recurrence(1);
+        assert_eq!(cache.records(true).len(), 2);
+        assert!(cache.get(3).is_some());
+        assert!(cache.get(5).is_some());
+
+        let mut outside = Record::default();
+        outside.set_primary_key(100);
+        cache.refresh(outside);
+        assert!(cache.get(100).is_none());
+    }
+}
@@ -0,0 +1,580 @@
+use crate::{
+    config::Config,
+    db::{
+        events_now,
+        google::{is_connection_error, status_code},
+        EditConflict, RemoteClient, SyncResult,
+    },
+    record::{Record, RecurringRecord},
+    time::{now, window},
+};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use gcal::ClientError;
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+const URL: &str = "https://www.googleapis.com/calendar/v3";
+
+#[derive(Debug, Default)]
+struct Calendar {
+    events: BTreeMap<String, Record>,
+    recurring: BTreeMap<String, RecurringRecord>,
+    // instances of recurring events which were deleted, or replaced by an event of their own.
+    cancelled: BTreeSet<String>,
+    // the sequence number of the last change made to each event, for incremental syncs.
+    changes: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Default)]
+struct Server {
+    calendars: BTreeMap<String, Calendar>,
+    sequence: u64,
+    offline: bool,
+    // requests which fail with a status, in order; None lets a request through.
+    failures: VecDeque<Option<u16>>,
+    requests: usize,
+}
+
+impl Server {
+    fn change(&mut self, calendar_id: &str, ids: impl IntoIterator<Item = String>) -> String {
+        self.sequence += 1;
+        let sequence = self.sequence;
+        let calendar = self.calendars.entry(calendar_id.to_string()).or_default();
+        for id in ids {
+            calendar.changes.insert(id, sequence);
+        }
+
+        format!("\"{}\"", sequence)
+    }
+
+    fn calendar(&mut self, calendar_id: &str) -> &mut Calendar {
+        self.calendars.entry(calendar_id.to_string()).or_default()
+    }
+}
+
+// FakeClient is an in-process calendar server, which behaves like Google Calendar does as far as
+// saturn is concerned: it hands out its own event IDs and etags, expands recurring events into
+// instances within the query window, and supports incremental syncs. Clones share the same
+// calendars, so tests can keep a handle to inspect or change them behind a database's back.
+#[derive(Debug, Clone, Default)]
+pub struct FakeClient {
+    server: Arc<Mutex<Server>>,
+}
+
+fn http_error(status: u16) -> anyhow::Error {
+    let reason = reqwest::StatusCode::from_u16(status)
+        .ok()
+        .and_then(|s| s.canonical_reason())
+        .unwrap_or_default();
+
+    ClientError::UnknownError(format!(
+        "HTTP status {} error ({} {}) for url ({})",
+        if status >= 500 { "server" } else { "client" },
+        status,
+        reason,
+        URL
+    ))
+    .into()
+}
+
+// stored strips everything the remote side does not know about from a record.
+fn stored(mut record: Record, id: &str, etag: &str) -> Record {
+    record.set_primary_key(0);
+    record.set_recurrence_key(None);
+    record.set_fields(Default::default());
+    record.set_completed(false);
+    record.set_internal_key(Some(id.to_string()));
+    record.set_etag(Some(etag.to_string()));
+    record
+}
+
+fn instances(
+    id: &str,
+    recurring: &RecurringRecord,
+    until: chrono::DateTime<chrono::Local>,
+) -> Vec<Record> {
+    let mut recurring = recurring.clone();
+    let mut begin = recurring.record().datetime();
+    let mut v = Vec::new();
+
+    while begin <= until && recurring.recurrence().duration() > chrono::Duration::zero() {
+        let mut record = recurring.record_from(0, begin.naive_local());
+        record.set_recurrence_key(None);
+        record.set_internal_key(Some(format!(
+            "{}_{}",
+            id,
+            begin.naive_local().format("%Y%m%dT%H%M%S")
+        )));
+        record.set_internal_recurrence_key(Some(id.to_string()));
+        v.push(record);
+        begin += recurring.recurrence().duration();
+    }
+
+    v
+}
+
+// occurrences is every event up to `until`, keyed by event ID.
+fn occurrences(
+    calendar: &Calendar,
+    until: chrono::DateTime<chrono::Local>,
+) -> BTreeMap<String, Record> {
+    let mut map = BTreeMap::new();
+
+    for (id, record) in &calendar.events {
+        map.insert(id.clone(), record.clone());
+    }
+
+    for (id, recurring) in &calendar.recurring {
+        for record in instances(id, recurring, until) {
+            let key = record.internal_key().unwrap_or_default();
+            if !calendar.cancelled.contains(&key) {
+                map.insert(key, record);
+            }
+        }
+    }
+
+    map
+}
+
+// listing is every event in the window, keyed by event ID.
+fn listing(calendar: &Calendar) -> BTreeMap<String, Record> {
+    let (start, end) = window(&Config::default());
+    let mut map = occurrences(calendar, end);
+    map.retain(|_, r| r.datetime() >= start && r.datetime() <= end);
+    map
+}
+
+impl FakeClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // set_offline makes every request fail as if the server could not be reached.
+    pub fn set_offline(&self, offline: bool) {
+        self.server.lock().unwrap().offline = offline
+    }
+
+    // fail_next makes the next request fail with the HTTP status.
+    pub fn fail_next(&self, status: u16) {
+        self.fail_after(0, status)
+    }
+
+    // fail_after lets `requests` requests through, then makes the next one fail with the HTTP
+    // status.
+    pub fn fail_after(&self, requests: usize, status: u16) {
+        let mut server = self.server.lock().unwrap();
+        server.failures.extend(std::iter::repeat_n(None, requests));
+        server.failures.push_back(Some(status));
+    }
+
+    // requests is the number of requests made so far, including failed ones.
+    pub fn requests(&self) -> usize {
+        self.server.lock().unwrap().requests
+    }
+
+    pub fn events(&self, calendar_id: &str) -> Vec<Record> {
+        listing(self.server.lock().unwrap().calendar(calendar_id))
+            .into_values()
+            .collect()
+    }
+
+    pub fn recurring(&self, calendar_id: &str) -> Vec<RecurringRecord> {
+        self.server
+            .lock()
+            .unwrap()
+            .calendar(calendar_id)
+            .recurring
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    // edit changes an event as if it was done from another device.
+    pub fn edit(&self, calendar_id: &str, event_id: &str, f: impl FnOnce(&mut Record)) {
+        let mut server = self.server.lock().unwrap();
+        let etag = server.change(calendar_id, [event_id.to_string()]);
+        if let Some(record) = server.calendar(calendar_id).events.get_mut(event_id) {
+            f(record);
+            record.set_etag(Some(etag));
+        }
+    }
+
+    fn request(&self) -> Result<MutexGuard<'_, Server>> {
+        let mut server = self.server.lock().unwrap();
+        server.requests += 1;
+
+        if server.offline {
+            return Err(ClientError::UnknownError(format!(
+                "error sending request for url ({})",
+                URL
+            ))
+            .into());
+        }
+
+        if let Some(Some(status)) = server.failures.pop_front() {
+            return Err(http_error(status));
+        }
+
+        Ok(server)
+    }
+
+    fn list(&self, calendar_id: &str) -> Result<Vec<Record>> {
+        let mut server = self.request()?;
+        Ok(listing(server.calendar(calendar_id))
+            .into_values()
+            .collect())
+    }
+}
+
+#[async_trait]
+impl RemoteClient for FakeClient {
+    fn is_offline(&self, error: &anyhow::Error) -> bool {
+        is_connection_error(error)
+    }
+
+    async fn sync(&mut self, calendar_id: String, token: Option<String>) -> Result<SyncResult> {
+        let mut server = self.request()?;
+        let sequence = server.sequence;
+        let calendar = server.calendar(&calendar_id);
+        let current = listing(calendar);
+
+        let since = match token {
+            Some(token) => token.parse::<u64>().map_err(|_| http_error(410))?,
+            None => {
+                return Ok(SyncResult {
+                    records: current.into_values().collect(),
+                    token: Some(sequence.to_string()),
+                    full: true,
+                    ..Default::default()
+                })
+            }
+        };
+
+        let mut result = SyncResult {
+            token: Some(sequence.to_string()),
+            ..Default::default()
+        };
+
+        for (id, _) in calendar.changes.iter().filter(|(_, s)| **s > since) {
+            if let Some(record) = current.get(id) {
+                result.records.push(record.clone());
+            } else if calendar.recurring.contains_key(id) {
+                result.records.extend(
+                    current
+                        .values()
+                        .filter(|r| r.internal_recurrence_key().as_ref() == Some(id))
+                        .cloned(),
+                );
+            } else {
+                result.removed.push(id.clone());
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn update(&mut self, calendar_id: String, record: Record) -> Result<Option<String>> {
+        let mut server = self.request()?;
+        let id = record
+            .internal_key()
+            .ok_or(anyhow!("Record has no event ID"))?;
+        let calendar = server.calendar(&calendar_id);
+
+        let current = match calendar.events.get(&id) {
+            Some(current) => current.clone(),
+            // changing an instance of a recurring event turns it into an event of its own.
+            None => listing(calendar).remove(&id).ok_or(http_error(404))?,
+        };
+
+        if record.etag().is_some() && record.etag() != current.etag() {
+            return Err(EditConflict(current).into());
+        }
+
+        let etag = server.change(&calendar_id, [id.clone()]);
+        let calendar = server.calendar(&calendar_id);
+        calendar.cancelled.insert(id.clone());
+        calendar
+            .events
+            .insert(id.clone(), stored(record, &id, &etag));
+        Ok(Some(etag))
+    }
+
+    async fn update_recurring(
+        &mut self,
+        calendar_id: String,
+        mut record: RecurringRecord,
+    ) -> Result<Option<String>> {
+        let mut server = self.request()?;
+        let id = record
+            .internal_key()
+            .ok_or(anyhow!("Record has no event ID"))?;
+
+        let mut current = server
+            .calendar(&calendar_id)
+            .recurring
+            .get(&id)
+            .cloned()
+            .ok_or(http_error(404))?;
+
+        if record.record().etag().is_some() && record.record().etag() != current.record().etag() {
+            return Err(EditConflict(current).into());
+        }
+
+        let etag = server.change(&calendar_id, [id.clone()]);
+        record.set_recurrence_key(0);
+        let inner = stored(record.record().clone(), &id, &etag);
+        record.set_record(inner);
+        record.set_internal_key(Some(id.clone()));
+        server.calendar(&calendar_id).recurring.insert(id, record);
+        Ok(Some(etag))
+    }
+
+    async fn get(&mut self, calendar_id: String, event_id: String) -> Result<Record> {
+        let mut server = self.request()?;
+        let calendar = server.calendar(&calendar_id);
+        calendar
+            .events
+            .get(&event_id)
+            .cloned()
+            .or_else(|| listing(calendar).remove(&event_id))
+            .or_else(|| {
+                calendar
+                    .recurring
+                    .get(&event_id)
+                    .cloned()
+                    .map(|mut r| r.record().clone())
+            })
+            .ok_or(http_error(404))
+    }
+
+    // deleted events are forgotten right away; instances are gone once they were cancelled.
+    async fn is_gone(&mut self, calendar_id: String, event_id: String) -> Result<bool> {
+        let mut server = self.request()?;
+        let calendar = server.calendar(&calendar_id);
+        let instance = event_id
+            .rsplit_once('_')
+            .is_some_and(|(id, _)| calendar.recurring.contains_key(id))
+            && !calendar.cancelled.contains(&event_id);
+
+        Ok(!calendar.events.contains_key(&event_id)
+            && !calendar.recurring.contains_key(&event_id)
+            && !instance)
+    }
+
+    async fn get_recurring(
+        &mut self,
+        calendar_id: String,
+        event_id: String,
+    ) -> Result<RecurringRecord> {
+        let mut server = self.request()?;
+        server
+            .calendar(&calendar_id)
+            .recurring
+            .get(&event_id)
+            .cloned()
+            .ok_or(http_error(404))
+    }
+
+    async fn delete(&mut self, calendar_id: String, event_id: String) -> Result<()> {
+        let mut server = self.request()?;
+        let calendar = server.calendar(&calendar_id);
+
+        if calendar.events.remove(&event_id).is_none() {
+            if !listing(calendar).contains_key(&event_id) {
+                return Err(http_error(410));
+            }
+
+            calendar.cancelled.insert(event_id.clone());
+        }
+
+        server.change(&calendar_id, [event_id]);
+        Ok(())
+    }
+
+    async fn delete_recurrence(
+        &mut self,
+        calendar_id: String,
+        event_id: String,
+    ) -> Result<Vec<String>> {
+        let mut server = self.request()?;
+        let calendar = server.calendar(&calendar_id);
+
+        let recurring = calendar
+            .recurring
+            .remove(&event_id)
+            .ok_or(http_error(410))?;
+
+        let (_, end) = window(&Config::default());
+        let mut ids = instances(&event_id, &recurring, end)
+            .into_iter()
+            .filter_map(|r| r.internal_key())
+            .collect::<Vec<String>>();
+
+        // instances which became events of their own go along with the series.
+        calendar
+            .events
+            .retain(|_, r| r.internal_recurrence_key().as_ref() != Some(&event_id));
+
+        let mut changed = ids.clone();
+        changed.push(event_id);
+        server.change(&calendar_id, changed);
+        ids.sort();
+        Ok(ids)
+    }
+
+    // like Google Calendar's batch deletes, events which are already gone are not an error.
+    async fn delete_many(&mut self, calendar_id: String, event_ids: Vec<String>) -> Result<()> {
+        for event_id in event_ids {
+            match self.delete(calendar_id.clone(), event_id).await {
+                Err(e) if matches!(status_code(&e), Some(404) | Some(410)) => {}
+                res => res?,
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn record(&mut self, calendar_id: String, record: Record) -> Result<String> {
+        let mut server = self.request()?;
+        let id = format!("fake{}", server.sequence + 1);
+        let etag = server.change(&calendar_id, [id.clone()]);
+        server
+            .calendar(&calendar_id)
+            .events
+            .insert(id.clone(), stored(record, &id, &etag));
+        Ok(id)
+    }
+
+    async fn record_recurrence(
+        &mut self,
+        calendar_id: String,
+        mut record: RecurringRecord,
+    ) -> Result<(String, String)> {
+        let mut server = self.request()?;
+        let id = format!("fake{}", server.sequence + 1);
+        let etag = server.change(&calendar_id, [id.clone()]);
+        record.set_recurrence_key(0);
+        let inner = stored(record.record().clone(), &id, &etag);
+        record.set_record(inner);
+        record.set_internal_key(Some(id.clone()));
+        server
+            .calendar(&calendar_id)
+            .recurring
+            .insert(id.clone(), record);
+        Ok((id.clone(), id))
+    }
+
+    async fn list_recurrence(&mut self, calendar_id: String) -> Result<Vec<RecurringRecord>> {
+        let mut server = self.request()?;
+        Ok(server
+            .calendar(&calendar_id)
+            .recurring
+            .values()
+            .cloned()
+            .collect())
+    }
+
+    async fn update_recurrence(&mut self, _calendar_id: String) -> Result<()> {
+        Ok(())
+    }
+
+    async fn list_today(
+        &mut self,
+        calendar_id: String,
+        _include_completed: bool,
+    ) -> Result<Vec<Record>> {
+        let today = now().date_naive();
+        Ok(self
+            .list(&calendar_id)?
+            .into_iter()
+            .filter(|r| r.on_date(today))
+            .collect())
+    }
+
+    async fn list_all(
+        &mut self,
+        calendar_id: String,
+        _include_completed: bool,
+    ) -> Result<Vec<Record>> {
+        self.list(&calendar_id)
+    }
+
+    async fn list_range(
+        &mut self,
+        calendar_id: String,
+        start: chrono::NaiveDate,
+        end: chrono::NaiveDate,
+        _include_completed: bool,
+    ) -> Result<Vec<Record>> {
+        let mut server = self.request()?;
+        let until = (end + chrono::TimeDelta::try_days(1).unwrap_or_default())
+            .and_time(chrono::NaiveTime::MIN)
+            .and_local_timezone(chrono::Local)
+            .unwrap();
+
+        Ok(occurrences(server.calendar(&calendar_id), until)
+            .into_values()
+            .filter(|r| r.in_range(start, end))
+            .collect())
+    }
+
+    async fn events_now(
+        &mut self,
+        calendar_id: String,
+        last: chrono::Duration,
+        _include_completed: bool,
+    ) -> Result<Vec<Record>> {
+        Ok(events_now(self.list(&calendar_id)?, last))
+    }
+
+    async fn complete_task(&mut self, _calendar_id: String, _primary_key: u64) -> Result<()> {
+        drop(self.request()?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[tokio::test]
+    async fn test_incremental_sync() {
+        use super::FakeClient;
+        use crate::{db::RemoteClient, record::Record, time::now};
+
+        let mut client = FakeClient::new();
+        let mut record = Record::default();
+        record.set_date(now().date_naive());
+        record.set_detail("first".to_string());
+        let first = client
+            .record("primary".to_string(), record.clone())
+            .await
+            .unwrap();
+
+        let result = client.sync("primary".to_string(), None).await.unwrap();
+        assert!(result.full);
+        assert_eq!(result.records.len(), 1);
+
+        record.set_detail("second".to_string());
+        let second = client.record("primary".to_string(), record).await.unwrap();
+        client
+            .delete("primary".to_string(), first.clone())
+            .await
+            .unwrap();
+
+        let result = client
+            .sync("primary".to_string(), result.token)
+            .await
+            .unwrap();
+        assert!(!result.full);
+        assert_eq!(result.records.len(), 1);
+        assert_eq!(result.records[0].internal_key(), Some(second));
+        assert_eq!(result.removed, vec![first]);
+
+        assert!(client
+            .sync("primary".to_string(), Some("garbage".to_string()))
+            .await
+            .is_err());
+    }
+}
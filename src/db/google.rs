@@ -1,56 +1,208 @@
 use crate::{
     config::{Config, DBType},
-    db::RemoteClient,
+    db::{
+        events_now,
+        google_batch::{self, BatchPart, BatchResponse, BATCH_LIMIT},
+        google_requests::{
+            CalendarListPage, CalendarListRequest, EventListRequest, EventPage, FreeBusyRequest,
+            FreeBusyResponse,
+        },
+        recorder::{Response, Transport},
+        EditConflict, RemoteClient, SyncResult,
+    },
     do_client,
-    record::{Record, RecordType, RecurringRecord},
+    free::Period,
+    notifications::{anchored, fires_at},
+    oauth::store_token,
+    record::{Anchor, Notification, Record, RecordType, RecurringRecord},
     time::{now, window},
 };
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use chrono::Timelike;
 use gcal::{
     oauth::{request_access_token, AccessToken},
     resources::{
-        CalendarListClient, CalendarListItem, DefaultReminder, Event, EventCalendarDate,
-        EventClient, EventReminder, EventStatus,
+        CalendarListItem, DefaultReminder, Event, EventCalendarDate, EventReminder, EventStatus,
     },
-    Client, ClientError,
+    sendable::Sendable,
+    ClientError,
 };
+use reqwest::Method;
+use serde::de::DeserializeOwned;
 use std::collections::{BTreeMap, BTreeSet};
 
+const MAX_BACKOFF: u64 = 32;
+
+// status_code extracts the HTTP status from an error raised by gcal, which only reports it as part
+// of the message.
+pub fn status_code(error: &anyhow::Error) -> Option<u16> {
+    error.downcast_ref::<ClientError>().and_then(client_status)
+}
+
+// reminder_minutes is how long before the start of the event Google reminds of the notification.
+// Google's reminders are always before the start, so notifications after it have none. Plain
+// durations are sent as they are, which keeps all day events as they always were.
+pub fn reminder_minutes(record: &Record, notification: &Notification) -> Option<u16> {
+    let minutes = if notification.anchor().is_none() && notification.time().is_none() {
+        notification.duration().num_minutes()
+    } else {
+        (anchored(record, Some(Anchor::Start)) - fires_at(record, notification)).num_minutes()
+    };
+
+    minutes.try_into().ok()
+}
+
+// is_connection_error reports whether the request never reached Google Calendar.
+pub fn is_connection_error(error: &anyhow::Error) -> bool {
+    match error.downcast_ref::<ClientError>() {
+        Some(ClientError::UnknownError(msg)) => msg.starts_with("error sending request"),
+        _ => false,
+    }
+}
+
+fn client_status(error: &ClientError) -> Option<u16> {
+    match error {
+        ClientError::UnknownError(msg) => regex::Regex::new(r"\((\d{3}) ")
+            .unwrap()
+            .captures(msg)
+            .and_then(|c| c[1].parse().ok()),
+        _ => None,
+    }
+}
+
+// Google reports exceeded rate limits as either 403 or 429. gcal drops the response body, so a 403
+// cannot be told apart from a lack of permission, and is only retried where the body is available.
+fn is_retryable_status(status: u16) -> bool {
+    matches!(status, 429 | 500..=599)
+}
+
+fn is_retryable_response(response: &BatchResponse) -> bool {
+    is_retryable_status(response.status)
+        || (response.status == 403
+            && ["\"rateLimitExceeded\"", "\"userRateLimitExceeded\""]
+                .iter()
+                .any(|reason| response.body.contains(reason)))
+}
+
+pub fn is_retryable(error: &ClientError) -> bool {
+    client_status(error).is_some_and(is_retryable_status)
+}
+
+// backoff is how long to wait before a retry: exponential in the number of attempts made, capped,
+// and with full jitter so that several clients do not retry in lockstep.
+pub fn backoff(config: &Config, attempt: u32) -> std::time::Duration {
+    use rand::Rng;
+
+    let max = config
+        .retry_delay()
+        .to_std()
+        .unwrap_or_default()
+        .saturating_mul(2_u32.saturating_pow(attempt))
+        .min(std::time::Duration::from_secs(MAX_BACKOFF));
+
+    rand::thread_rng().gen_range(std::time::Duration::ZERO..=max)
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct GoogleClient {
-    client: Option<Client>,
+    transport: Transport,
     config: Config,
     ical_map: BTreeMap<String, u64>,
 }
 
 impl GoogleClient {
     pub fn new(config: Config) -> Result<Self> {
-        if !matches!(config.db_type(), DBType::Google) {
-            return Err(anyhow!("DBType must be set to google"));
+        if !matches!(config.db_type(), DBType::Google) && config.mirror().is_none() {
+            return Err(anyhow!(
+                "DBType must be set to google, or a mirror must be configured"
+            ));
         }
 
         if !config.has_client() {
             return Err(anyhow!("Must have client information configured"));
         }
 
-        let client = if let Some(access_token) = config.access_token() {
-            Client::new(access_token)?
-        } else {
+        if config.access_token().is_none() {
             return Err(anyhow!("You must have an access token to make calls. Use `saturn config get-token` to retrieve one."));
-        };
+        }
 
         Ok(Self {
-            client: Some(client),
+            transport: Transport::default(),
             config,
             ical_map: Default::default(),
         })
     }
 
-    // this should be safe? lol
-    pub fn client(&self) -> Client {
-        self.client.clone().unwrap()
+    // set_transport replaces how requests are sent, to record them or replay a recording.
+    pub fn set_transport(&mut self, transport: Transport) {
+        self.transport = transport;
+    }
+
+    // send makes a request for the target the same way gcal's Client would, but through the
+    // transport, so it can be recorded and replayed.
+    async fn send(
+        &self,
+        method: Method,
+        target: &(impl Sendable + Sync),
+        action: Option<String>,
+    ) -> Result<Response, ClientError> {
+        let url = target.url(action)?;
+        let mut req = self
+            .transport
+            .request(method.clone(), url.clone())
+            .bearer_auth(self.config.access_token().unwrap_or_default());
+
+        if !matches!(method, Method::GET | Method::DELETE) {
+            req = req
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(target.body_bytes()?);
+        }
+
+        self.transport.send(req).await?.error_for_status(&url)
+    }
+
+    async fn fetch<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        target: &(impl Sendable + Sync),
+        action: Option<String>,
+    ) -> Result<T, ClientError> {
+        self.send(method, target, action).await?.json()
+    }
+
+    async fn get_event(&self, calendar_id: String, event_id: String) -> Result<Event, ClientError> {
+        let mut event = Event::default();
+        event.id = Some(event_id);
+        event.calendar_id = Some(calendar_id);
+        self.fetch(Method::GET, &event, None).await
+    }
+
+    // update_if_match writes the event, sending If-Match so Google refuses the write when the
+    // event was changed since the version described by the etag was read. None is returned when
+    // the write was refused.
+    async fn update_if_match(
+        &self,
+        event: &Event,
+        etag: Option<String>,
+    ) -> Result<Option<Event>, ClientError> {
+        let url = event.url(None)?;
+        let mut req = self
+            .transport
+            .request(Method::PUT, url.clone())
+            .bearer_auth(self.config.access_token().unwrap_or_default())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(event.body_bytes()?);
+
+        if let Some(etag) = etag {
+            req = req.header(reqwest::header::IF_MATCH, etag);
+        }
+
+        let resp = self.transport.send(req).await?;
+        if resp.status == 412 {
+            return Ok(None);
+        }
+
+        Ok(Some(resp.error_for_status(&url)?.json()?))
     }
 
     pub fn pick_uid(&self) -> u64 {
@@ -58,13 +210,77 @@ impl GoogleClient {
     }
 
     pub async fn list_calendars(&mut self) -> Result<Vec<CalendarListItem>, ClientError> {
-        let listclient = CalendarListClient::new(self.client().clone());
-        do_client!(self, { listclient.list() })
+        let request = CalendarListRequest::default();
+        let page: CalendarListPage = do_client!(self, { self.fetch(Method::GET, &request, None) })?;
+        Ok(page.items)
     }
 
-    pub async fn record_to_event(&mut self, calendar_id: String, record: &mut Record) -> Event {
-        let start_chrono = record.datetime().with_timezone(&chrono::Local);
-        let utc = start_chrono.with_timezone(&chrono_tz::UTC);
+    // free_busy asks Google Calendar when any of the calendars are busy between start and end.
+    pub async fn free_busy(
+        &mut self,
+        calendars: Vec<String>,
+        start: chrono::DateTime<chrono::Local>,
+        end: chrono::DateTime<chrono::Local>,
+    ) -> Result<Vec<Period>> {
+        let request = FreeBusyRequest::new(calendars, start, end);
+
+        let response: FreeBusyResponse =
+            do_client!(self, { self.fetch(Method::POST, &request, None) })?;
+
+        let parse = |s: &str| {
+            s.parse::<chrono::DateTime<chrono::Local>>()
+                .map(|dt| dt.naive_local())
+                .map_err(|_| anyhow!("Couldn't parse time"))
+        };
+
+        let mut busy = Vec::new();
+
+        for (id, calendar) in response.calendars {
+            if let Some(error) = calendar.errors.first() {
+                return Err(anyhow!(
+                    "Could not look up calendar {}: {}",
+                    id,
+                    error.reason
+                ));
+            }
+
+            for period in calendar.busy {
+                busy.push((parse(&period.start)?, parse(&period.end)?));
+            }
+        }
+
+        Ok(busy)
+    }
+
+    // set_event_dates sets the start and end of the event from the record. All day events use
+    // Google's date form, where the end date is exclusive, so they stay on the same days in every
+    // time zone. Events without an end time still need one; it is marked as unspecified so they
+    // come back without one.
+    pub fn set_event_dates(&self, event: &mut Event, record: &Record) {
+        let (start, end) = self.event_dates(record);
+        event.start = Some(start);
+        event.end = Some(end);
+        event.end_time_unspecified = matches!(record.record_type(), RecordType::At).then_some(true);
+    }
+
+    fn event_dates(&self, record: &Record) -> (EventCalendarDate, EventCalendarDate) {
+        if record.all_day() {
+            let end = record.end_date().unwrap_or(record.date())
+                + chrono::TimeDelta::try_days(1).unwrap_or_default();
+
+            return (
+                EventCalendarDate {
+                    date: Some(record.date().to_string()),
+                    ..Default::default()
+                },
+                EventCalendarDate {
+                    date: Some(end.to_string()),
+                    ..Default::default()
+                },
+            );
+        }
+
+        let utc = record.datetime().with_timezone(&chrono_tz::UTC);
 
         let start = EventCalendarDate {
             date_time: Some(utc.to_rfc3339()),
@@ -72,33 +288,29 @@ impl GoogleClient {
             ..Default::default()
         };
 
-        let end = match record.record_type() {
-            RecordType::At => Some(EventCalendarDate {
-                date_time: Some((utc + self.config.default_duration().duration()).to_rfc3339()),
-                time_zone: Some(utc.timezone().to_string()),
-                ..Default::default()
-            }),
-            RecordType::Schedule => {
-                let dt = chrono::NaiveDateTime::new(record.date(), record.scheduled().unwrap().1)
-                    .and_local_timezone(chrono::Local)
-                    .unwrap();
+        let end = match record.scheduled() {
+            Some((_, end)) => chrono::NaiveDateTime::new(record.date(), end)
+                .and_local_timezone(chrono::Local)
+                .unwrap()
+                .with_timezone(&chrono_tz::UTC),
+            None => utc + self.config.default_duration().duration(),
+        };
 
-                Some(EventCalendarDate {
-                    date_time: Some(dt.with_timezone(&chrono_tz::UTC).to_rfc3339()),
-                    time_zone: Some(dt.with_timezone(&chrono_tz::UTC).to_string()),
-                    ..Default::default()
-                })
-            }
-            RecordType::AllDay => Some(EventCalendarDate {
-                date_time: Some(
-                    (utc + chrono::TimeDelta::try_days(1).unwrap_or_default()).to_rfc3339(),
-                ),
-                time_zone: Some(utc.timezone().to_string()),
+        (
+            start,
+            EventCalendarDate {
+                date_time: Some(end.to_rfc3339()),
+                time_zone: Some(end.timezone().to_string()),
                 ..Default::default()
-            }),
-        };
+            },
+        )
+    }
 
-        let event_client = EventClient::new(self.client());
+    pub async fn record_to_event(&mut self, calendar_id: String, record: &mut Record) -> Event {
+        let current = match record.internal_key() {
+            Some(key) => self.get_event(calendar_id.clone(), key).await.ok(),
+            None => None,
+        };
 
         let mut f = |record: Record| {
             let mut event = Event::default();
@@ -118,38 +330,28 @@ impl GoogleClient {
             event
         };
 
-        let mut event = if let Some(key) = record.internal_key() {
-            if let Ok(event) = event_client.get(calendar_id.clone(), key).await {
-                event
-            } else {
-                f(record.clone())
-            }
-        } else {
-            f(record.clone())
-        };
-
-        event.start = Some(start);
+        let mut event = current.unwrap_or_else(|| f(record.clone()));
 
-        if end.is_some() {
-            event.end = end;
-        }
+        self.set_event_dates(&mut event, record);
 
         if let Some(notifications) = record.notifications() {
             let mut reminders = EventReminder::default();
 
             for notification in notifications {
-                if notification.duration() == chrono::TimeDelta::try_minutes(10).unwrap_or_default()
-                {
+                let Some(minutes) = reminder_minutes(record, &notification) else {
+                    continue;
+                };
+
+                if minutes == 10 {
                     reminders.use_default = true;
                 } else {
-                    let mut overrides = Vec::new();
-                    if let Ok(minutes) = notification.duration().num_minutes().try_into() {
-                        overrides.push(DefaultReminder {
+                    reminders
+                        .overrides
+                        .get_or_insert_with(Vec::new)
+                        .push(DefaultReminder {
                             method: gcal::ReminderMethod::PopUp,
                             minutes,
                         });
-                    }
-                    reminders.overrides = Some(overrides);
                 }
             }
 
@@ -166,34 +368,115 @@ impl GoogleClient {
         event
     }
 
+    fn remember_uid(&mut self, event: &Event) {
+        if let Some(uid) = &event.ical_uid {
+            if let Ok(uid) = uid.strip_prefix("UID:").unwrap_or_default().parse::<u64>() {
+                if let Some(id) = event.id.clone() {
+                    self.ical_map.insert(id, uid);
+                }
+            }
+        }
+    }
+
+    // batch sends the parts in as few batch requests as possible. Parts which were rate limited or
+    // failed on the server side are sent again with backoff, the same as single requests are.
+    async fn batch(&mut self, parts: Vec<BatchPart>) -> Result<Vec<BatchResponse>> {
+        let mut responses = vec![None; parts.len()];
+        let mut pending = (0..parts.len()).collect::<Vec<usize>>();
+        let mut attempt = 0;
+
+        while !pending.is_empty() {
+            let mut retry = Vec::new();
+
+            for chunk in pending.chunks(BATCH_LIMIT) {
+                let batch = chunk
+                    .iter()
+                    .map(|i| parts[*i].clone())
+                    .collect::<Vec<BatchPart>>();
+
+                let results = do_client!(self, {
+                    google_batch::send(
+                        &self.transport,
+                        self.config.access_token().unwrap_or_default(),
+                        batch.clone(),
+                    )
+                })?;
+
+                for (index, response) in chunk.iter().zip(results) {
+                    if attempt < self.config.max_retries() && is_retryable_response(&response) {
+                        retry.push(*index);
+                    } else {
+                        responses[*index] = Some(response);
+                    }
+                }
+            }
+
+            if !retry.is_empty() {
+                tokio::time::sleep(backoff(&self.config, attempt)).await;
+                attempt += 1;
+            }
+
+            pending = retry;
+        }
+
+        Ok(responses.into_iter().flatten().collect())
+    }
+
+    // current_event fetches the version of the event a conditional write was refused for.
+    async fn current_event(&mut self, calendar_id: String, event: &Event) -> Result<Event> {
+        let key = event.id.clone().unwrap_or_default();
+        Ok(do_client!(self, {
+            self.get_event(calendar_id.clone(), key.clone())
+        })?)
+    }
+
     pub async fn refresh_access_token(&mut self) -> Result<()> {
         let res: Result<AccessToken, ClientError> =
             request_access_token(self.config.clone().into(), None, None, true)
                 .await
                 .map_err(|e| e.into());
-        let token = res?;
-        self.config.set_access_token(Some(token.access_token));
-        self.config.set_access_token_expires_at(Some(
-            now().naive_utc()
-                + chrono::TimeDelta::try_seconds(token.expires_in).unwrap_or_default(),
-        ));
-
-        if let Some(refresh_token) = token.refresh_token {
-            self.config.set_refresh_token(Some(refresh_token));
-            if let Some(expires_in) = token.refresh_token_expires_in {
-                self.config.set_refresh_token_expires_at(Some(
-                    now().naive_utc()
-                        + chrono::TimeDelta::try_seconds(expires_in).unwrap_or_default(),
-                ));
+        store_token(&mut self.config, res?);
+        self.config.save(None)?;
+        Ok(())
+    }
+
+    // list_events lists the events, returning them along with the sync token handed out at the end
+    // of the listing. Recurring events are expanded into their instances by the API, so no requests
+    // need to be made per recurring event.
+    async fn list_events(
+        &mut self,
+        calendar_id: String,
+        mut request: EventListRequest,
+    ) -> Result<(Vec<Event>, Option<String>)> {
+        request.set("singleEvents", "true".to_string());
+        self.list_pages(calendar_id, request).await
+    }
+
+    // list_pages follows the pages of a listing, returning every event along with the sync token
+    // handed out on the last page.
+    async fn list_pages(
+        &mut self,
+        calendar_id: String,
+        mut request: EventListRequest,
+    ) -> Result<(Vec<Event>, Option<String>)> {
+        request.set("maxResults", "2500".to_string());
+
+        let mut events = Vec::new();
+
+        loop {
+            let page: EventPage = do_client!(self, { self.fetch(Method::GET, &request, None) })?;
+
+            for mut event in page.items {
+                event.calendar_id = Some(calendar_id.clone());
+                events.push(event);
+            }
+
+            if let Some(page_token) = page.next_page_token {
+                request.set("pageToken", page_token);
             } else {
-                self.config.set_refresh_token_expires_at(Some(
-                    now().naive_utc() + chrono::TimeDelta::try_seconds(3600).unwrap_or_default(),
-                ));
+                return Ok((events, page.next_sync_token));
             }
         }
-
-        self.config.save(None)?;
-        Ok(())
     }
 
     async fn perform_list(
@@ -202,67 +485,80 @@ impl GoogleClient {
         start: chrono::DateTime<chrono::Local>,
         end: chrono::DateTime<chrono::Local>,
     ) -> Result<Vec<Record>> {
-        let list = EventClient::new(self.client());
+        let mut request = EventListRequest::new(calendar_id.clone());
+        request.set("timeMin", start.to_rfc3339());
+        request.set("timeMax", end.to_rfc3339());
 
-        let events = do_client!(self, { list.list(calendar_id.clone(), start, end) })?;
+        let (events, _) = self.list_events(calendar_id, request).await?;
 
         let mut records = Vec::new();
 
-        for mut event in events {
-            if event.recurrence.is_some() {
-                event.calendar_id = Some(calendar_id.clone());
+        for event in events {
+            if !matches!(event.status, Some(EventStatus::Cancelled)) {
+                records.push(self.event_to_record(event)?)
+            }
+        }
 
-                let instances = EventClient::new(self.client())
-                    .instances(event.clone())
-                    .await?;
-
-                for new_event in instances.items {
-                    if let Some(new_start) = &new_event.start {
-                        if let Some(new_start) = &new_start.date {
-                            if let Ok(new_start) = new_start.parse::<chrono::NaiveDate>() {
-                                if new_start > start.date_naive() && new_start < end.date_naive() {
-                                    if let Some(status) = new_event.status.clone() {
-                                        if !matches!(status, EventStatus::Cancelled) {
-                                            records.push(self.event_to_record(new_event)?);
-                                        }
-                                    }
-                                }
-                            }
-                        } else if let Some(new_start) = &new_start.date_time {
-                            if let Ok(new_start) =
-                                new_start.parse::<chrono::DateTime<chrono::Local>>()
-                            {
-                                if new_start > start && new_start < end {
-                                    if let Some(status) = new_event.status.clone() {
-                                        if !matches!(status, EventStatus::Cancelled) {
-                                            records.push(self.event_to_record(new_event)?);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+        Ok(records)
+    }
+
+    // perform_sync lists the changes since `token` was issued, or the whole query window when there
+    // is no token.
+    async fn perform_sync(
+        &mut self,
+        calendar_id: String,
+        token: Option<String>,
+    ) -> Result<SyncResult> {
+        let window = window(&self.config);
+        let mut request = EventListRequest::new(calendar_id.clone());
+
+        if let Some(token) = &token {
+            request.set("syncToken", token.clone());
+        } else {
+            request.set("timeMin", window.0.to_rfc3339());
+            request.set("timeMax", window.1.to_rfc3339());
+        }
+
+        let (events, next_token) = self.list_events(calendar_id, request).await?;
+
+        let mut result = SyncResult {
+            token: next_token,
+            full: token.is_none(),
+            ..Default::default()
+        };
+
+        for event in events {
+            let id = event.id.clone().unwrap_or_default();
+
+            if matches!(event.status, Some(EventStatus::Cancelled)) {
+                result.removed.push(id);
+                continue;
+            }
+
+            let record = self.event_to_record(event)?;
+            // changes are reported for the whole calendar, so events which have moved out of the
+            // window are treated as if they were removed.
+            if record.datetime() < window.0 || record.datetime() > window.1 {
+                result.removed.push(id);
             } else {
-                event.calendar_id = Some(calendar_id.clone());
-                if let Some(status) = event.status.clone() {
-                    if !matches!(status, EventStatus::Cancelled) {
-                        records.push(self.event_to_record(event)?)
-                    }
-                } else {
-                    records.push(self.event_to_record(event)?)
-                }
+                result.records.push(record);
             }
         }
 
-        Ok(records)
+        Ok(result)
     }
 
     pub fn event_to_record(&mut self, event: Event) -> Result<Record, ClientError> {
         let mut record = Record::default();
 
         record.set_internal_key(event.id.clone());
-        record.set_internal_recurrence_key(event.id.clone());
+        record.set_etag(event.etag.clone());
+        record.set_internal_recurrence_key(
+            event
+                .recurring_event_id
+                .clone()
+                .or(event.recurrence.as_ref().and(event.id.clone())),
+        );
 
         let original_start = event.original_start_time;
 
@@ -290,35 +586,26 @@ impl GoogleClient {
             .flatten()
             .flatten();
 
-        let has_start_time = start_time.is_some();
-
-        let has_end_time = match event.end.clone() {
-            Some(end) => end.date_time.is_some() || event.end_time_unspecified.unwrap_or_default(),
-            None => false,
+        let end_time = match event.end.clone().and_then(|x| x.date_time) {
+            Some(end) => match end.parse::<chrono::DateTime<chrono::Local>>() {
+                Ok(p) => Some(p.naive_local()),
+                Err(_) => return Err(anyhow!("Couldn't parse time").into()),
+            },
+            None => None,
         };
 
-        let schedule = if has_start_time && has_end_time {
-            let local = match event
-                .end
-                .clone()
-                .unwrap()
-                .date_time
-                .unwrap()
-                .parse::<chrono::DateTime<chrono::Local>>()
-            {
-                Ok(p) => p.naive_local(),
-                Err(_) => return Err(anyhow!("Couldn't parse time").into()),
-            };
+        let end_date = event
+            .end
+            .clone()
+            .and_then(|x| x.date)
+            .and_then(|y| y.parse::<chrono::NaiveDate>().ok());
 
-            if (start_time.unwrap() + chrono::TimeDelta::try_days(1).unwrap_or_default()) == local {
-                RecordType::AllDay
-            } else {
-                RecordType::Schedule
-            }
-        } else if has_start_time {
-            RecordType::At
-        } else {
+        let schedule = if start_time.is_none() {
             RecordType::AllDay
+        } else if end_time.is_some() && !event.end_time_unspecified.unwrap_or_default() {
+            RecordType::Schedule
+        } else {
+            RecordType::At
         };
 
         record.set_record_type(schedule.clone());
@@ -351,6 +638,10 @@ impl GoogleClient {
             RecordType::AllDay => {
                 record.set_all_day();
                 record.set_date(date);
+                // the end date of an all day event is the day after it ends
+                record.set_end_date(
+                    end_date.map(|d| d - chrono::TimeDelta::try_days(1).unwrap_or_default()),
+                );
             }
             RecordType::At => {
                 record.set_at(Some(start_time.time()));
@@ -360,19 +651,7 @@ impl GoogleClient {
                 record.set_date(start_time.date());
                 record.set_scheduled(Some((
                     start_time.time(),
-                    event.end.map_or_else(
-                        || now.time(),
-                        |x| {
-                            x.date_time.map_or_else(
-                                || now.time(),
-                                |y| {
-                                    y.parse::<chrono::DateTime<chrono::Local>>()
-                                        .unwrap_or(now)
-                                        .time()
-                                },
-                            )
-                        },
-                    ),
+                    end_time.map_or_else(|| now.time(), |x| x.time()),
                 )));
             }
         }
@@ -387,17 +666,51 @@ impl GoogleClient {
         }
         Ok(record)
     }
+
+    fn event_to_recurring(&mut self, event: Event) -> Result<RecurringRecord> {
+        let mut ret: Option<RecurringRecord> = None;
+
+        let record = self.event_to_record(event.clone())?;
+        for recur in &event
+            .recurrence
+            .ok_or(anyhow!("No recurrence data for this event"))?
+        {
+            if let Ok(rr) = RecurringRecord::from_rrule(record.clone(), recur.clone()) {
+                ret = Some(rr);
+                break;
+            }
+        }
+
+        let mut ret = ret.ok_or(anyhow!("No recurrence data found for event"))?;
+        ret.set_internal_key(event.id.clone());
+        Ok(ret)
+    }
 }
 
 #[async_trait]
 impl RemoteClient for GoogleClient {
+    fn is_offline(&self, error: &anyhow::Error) -> bool {
+        is_connection_error(error)
+    }
+
+    async fn sync(&mut self, calendar_id: String, token: Option<String>) -> Result<SyncResult> {
+        if token.is_some() {
+            match self.perform_sync(calendar_id.clone(), token).await {
+                // the token has expired, and the calendar must be listed from scratch.
+                Err(e) if status_code(&e) == Some(410) => {}
+                res => return res,
+            }
+        }
+
+        self.perform_sync(calendar_id, None).await
+    }
+
     async fn delete(&mut self, calendar_id: String, event_id: String) -> Result<()> {
-        let events = EventClient::new(self.client());
         let mut event = Event::default();
         event.id = Some(event_id);
         event.calendar_id = Some(calendar_id);
 
-        do_client!(self, { events.delete(event.clone()) })?;
+        do_client!(self, { self.send(Method::DELETE, &event, None) })?;
         Ok(())
     }
 
@@ -406,36 +719,93 @@ impl RemoteClient for GoogleClient {
         calendar_id: String,
         event_id: String,
     ) -> Result<Vec<String>> {
-        let events = EventClient::new(self.client());
+        let (instances, _) = self
+            .list_pages(
+                calendar_id.clone(),
+                EventListRequest::instances(calendar_id.clone(), event_id.clone()),
+            )
+            .await?;
+
         let mut event = Event::default();
         event.id = Some(event_id);
-        event.calendar_id = Some(calendar_id.clone());
-
-        let list = events.instances(event.clone()).await?;
+        event.calendar_id = Some(calendar_id);
 
-        do_client!(self, { events.delete(event.clone()) })?;
+        do_client!(self, { self.send(Method::DELETE, &event, None) })?;
 
-        Ok(list
-            .items
+        Ok(instances
             .iter()
             .filter_map(|x| x.id.clone())
             .collect::<Vec<String>>())
     }
 
-    async fn record(&mut self, calendar_id: String, mut record: Record) -> Result<String> {
-        let event = self.record_to_event(calendar_id, &mut record).await;
-        let client = EventClient::new(self.client());
+    async fn delete_many(&mut self, calendar_id: String, event_ids: Vec<String>) -> Result<()> {
+        let parts = event_ids
+            .iter()
+            .map(|id| {
+                let mut event = Event::default();
+                event.id = Some(id.clone());
+                event.calendar_id = Some(calendar_id.clone());
+                BatchPart::new("DELETE", &event, None)
+            })
+            .collect::<Result<Vec<BatchPart>, ClientError>>()?;
+
+        // events which are already gone, such as the instances of a deleted recurring event, are
+        // not an error.
+        let failed = self
+            .batch(parts)
+            .await?
+            .into_iter()
+            .zip(event_ids)
+            .filter(|(r, _)| !r.is_success() && r.status != 404 && r.status != 410)
+            .map(|(r, id)| format!("{} ({})", id, r.status))
+            .collect::<Vec<String>>();
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Events could not be deleted: {}",
+                failed.join(", ")
+            ))
+        }
+    }
 
-        let event = do_client!(self, { client.insert(event.clone()) })?;
+    async fn record_many(
+        &mut self,
+        calendar_id: String,
+        records: Vec<Record>,
+    ) -> Result<Vec<Result<String>>> {
+        let mut parts = Vec::new();
+        for mut record in records {
+            let event = self.record_to_event(calendar_id.clone(), &mut record).await;
+            parts.push(BatchPart::new("POST", &event, Some("import".to_string()))?);
+        }
 
-        if let Some(uid) = event.ical_uid {
-            if let Ok(uid) = uid.strip_prefix("UID:").unwrap_or_default().parse::<u64>() {
-                if let Some(id) = event.id.clone() {
-                    self.ical_map.insert(id, uid);
-                }
+        let mut ids = Vec::new();
+        for response in self.batch(parts).await? {
+            if !response.is_success() {
+                ids.push(Err(anyhow!(
+                    "Event could not be saved: status {}",
+                    response.status
+                )));
+                continue;
             }
+
+            let event: Event = serde_json::from_str(&response.body)?;
+            self.remember_uid(&event);
+            ids.push(event.id.ok_or(anyhow!("Event could not be saved")));
         }
 
+        Ok(ids)
+    }
+
+    async fn record(&mut self, calendar_id: String, mut record: Record) -> Result<String> {
+        let event = self.record_to_event(calendar_id, &mut record).await;
+        let event: Event = do_client!(self, {
+            self.fetch(Method::POST, &event, Some("import".to_string()))
+        })?;
+        self.remember_uid(&event);
+
         if let Some(id) = event.id {
             Ok(id)
         } else {
@@ -469,8 +839,9 @@ impl RemoteClient for GoogleClient {
 
         event.recurrence = Some(recurrence);
 
-        let client = EventClient::new(self.client());
-        let event = do_client!(self, { client.insert(event.clone()) })?;
+        let event: Event = do_client!(self, {
+            self.fetch(Method::POST, &event, Some("import".to_string()))
+        })?;
 
         if let Some(id) = event.clone().id {
             return Ok((id.clone(), id));
@@ -480,18 +851,17 @@ impl RemoteClient for GoogleClient {
     }
 
     async fn list_recurrence(&mut self, calendar_id: String) -> Result<Vec<RecurringRecord>> {
-        let list = EventClient::new(self.client());
+        let window = window(&self.config);
+        let mut request = EventListRequest::new(calendar_id.clone());
+        request.set("timeMin", window.0.to_rfc3339());
+        request.set("timeMax", window.1.to_rfc3339());
 
-        let mut events = do_client!(self, {
-            let window = window(&self.config);
-            list.list(calendar_id.clone(), window.0, window.1)
-        })?;
+        let (events, _) = self.list_pages(calendar_id, request).await?;
 
         let mut v = Vec::new();
 
-        for event in &mut events {
+        for event in &events {
             if let Some(recurrence) = &event.recurrence {
-                event.calendar_id = Some(calendar_id.clone());
                 let record = self.event_to_record(event.clone())?;
                 for recur in recurrence {
                     if let Ok(mut x) =
@@ -539,6 +909,24 @@ impl RemoteClient for GoogleClient {
         self.perform_list(calendar_id, window.0, window.1).await
     }
 
+    async fn list_range(
+        &mut self,
+        calendar_id: String,
+        start: chrono::NaiveDate,
+        end: chrono::NaiveDate,
+        _include_completed: bool,
+    ) -> Result<Vec<Record>> {
+        let start = start
+            .and_time(chrono::NaiveTime::MIN)
+            .and_local_timezone(chrono::Local)
+            .unwrap();
+        let end = (end + chrono::TimeDelta::try_days(1).unwrap_or_default())
+            .and_time(chrono::NaiveTime::MIN)
+            .and_local_timezone(chrono::Local)
+            .unwrap();
+        self.perform_list(calendar_id, start, end).await
+    }
+
     async fn events_now(
         &mut self,
         calendar_id: String,
@@ -547,32 +935,7 @@ impl RemoteClient for GoogleClient {
     ) -> Result<Vec<Record>> {
         let window = window(&self.config);
         let list = self.perform_list(calendar_id, window.0, window.1).await?;
-        let mut v = Vec::new();
-        for item in list {
-            let dt = item.datetime();
-            let n = now();
-            if dt > n && n > dt - last {
-                v.push(item);
-            } else if let Some(notifications) = item.notifications() {
-                for notification in notifications {
-                    let dt_window = dt - notification.duration();
-                    let dt_time = dt_window
-                        .time()
-                        .with_second(0)
-                        .unwrap()
-                        .with_nanosecond(0)
-                        .unwrap();
-                    let n_time = n.time().with_second(0).unwrap().with_nanosecond(0).unwrap();
-
-                    if dt > n && dt_window.date_naive() == n.date_naive() && dt_time == n_time {
-                        v.push(item);
-                        break;
-                    }
-                }
-            }
-        }
-
-        Ok(v)
+        Ok(events_now(list, last))
     }
 
     async fn complete_task(&mut self, _calendar_id: String, _primary_key: u64) -> Result<()> {
@@ -580,8 +943,20 @@ impl RemoteClient for GoogleClient {
     }
 
     async fn get(&mut self, calendar_id: String, event_id: String) -> Result<Record> {
-        let events = EventClient::new(self.client());
-        Ok(self.event_to_record(events.get(calendar_id, event_id).await?)?)
+        let event = self.get_event(calendar_id, event_id).await?;
+        Ok(self.event_to_record(event)?)
+    }
+
+    // deleted events are still returned for a while, marked as cancelled, before Google forgets
+    // about them.
+    async fn is_gone(&mut self, calendar_id: String, event_id: String) -> Result<bool> {
+        match do_client!(self, {
+            self.get_event(calendar_id.clone(), event_id.clone())
+        }) {
+            Ok(event) => Ok(matches!(event.status, Some(EventStatus::Cancelled))),
+            Err(e) if matches!(client_status(&e), Some(404 | 410)) => Ok(true),
+            Err(e) => Err(e.into()),
+        }
     }
 
     async fn get_recurring(
@@ -589,45 +964,303 @@ impl RemoteClient for GoogleClient {
         calendar_id: String,
         event_id: String,
     ) -> Result<RecurringRecord> {
-        let events = EventClient::new(self.client());
-        let event = events.get(calendar_id, event_id).await?;
-        let mut ret: Option<RecurringRecord> = None;
+        let event = self.get_event(calendar_id, event_id).await?;
+        self.event_to_recurring(event)
+    }
 
-        let record = self.event_to_record(event.clone())?;
-        for recur in &event
-            .recurrence
-            .ok_or(anyhow!("No recurrence data for this event"))?
-        {
-            if let Ok(rr) = RecurringRecord::from_rrule(record.clone(), recur.clone()) {
-                ret = Some(rr);
-                break;
+    async fn update(&mut self, calendar_id: String, mut record: Record) -> Result<Option<String>> {
+        let etag = record.etag();
+        let event = self.record_to_event(calendar_id.clone(), &mut record).await;
+
+        match do_client!(self, { self.update_if_match(&event, etag.clone()) })? {
+            Some(event) => Ok(event.etag),
+            None => {
+                let current = self.current_event(calendar_id, &event).await?;
+                Err(EditConflict(self.event_to_record(current)?).into())
             }
         }
-
-        let mut ret = ret.ok_or(anyhow!("No recurrence data found for event"))?;
-        ret.set_internal_key(event.id.clone());
-        Ok(ret)
-    }
-
-    async fn update(&mut self, calendar_id: String, mut record: Record) -> Result<()> {
-        let events = EventClient::new(self.client());
-        let event = self.record_to_event(calendar_id, &mut record).await;
-        events.update(event).await?;
-        Ok(())
     }
 
     async fn update_recurring(
         &mut self,
         calendar_id: String,
         mut record: RecurringRecord,
-    ) -> Result<()> {
-        let events = EventClient::new(self.client());
+    ) -> Result<Option<String>> {
         let key = record.internal_key();
         let r = record.record();
+        let etag = r.etag();
         r.set_internal_key(key);
-        let mut event = self.record_to_event(calendar_id, r).await;
+        let mut event = self.record_to_event(calendar_id.clone(), r).await;
         event.recurrence = Some(BTreeSet::from_iter(vec![record.to_rrule()]));
-        events.update(event).await?;
-        Ok(())
+
+        match do_client!(self, { self.update_if_match(&event, etag.clone()) })? {
+            Some(event) => Ok(event.etag),
+            None => {
+                let current = self.current_event(calendar_id, &event).await?;
+                Err(EditConflict(self.event_to_recurring(current)?).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_backoff() {
+        use super::{backoff, is_retryable, is_retryable_response, MAX_BACKOFF};
+        use crate::config::Config;
+        use crate::db::google_batch::BatchResponse;
+        use gcal::ClientError;
+
+        let mut config = Config::default();
+        config.set_retry_delay(chrono::TimeDelta::try_seconds(1).unwrap_or_default());
+
+        for attempt in 0..10 {
+            let delay = backoff(&config, attempt);
+            assert!(delay <= std::time::Duration::from_secs(2_u64.pow(attempt).min(MAX_BACKOFF)));
+        }
+
+        let error = |status: &str| {
+            ClientError::UnknownError(format!(
+                "HTTP status client error ({}) for url (https://www.googleapis.com/calendar/v3)",
+                status
+            ))
+        };
+        assert!(is_retryable(&error("429 Too Many Requests")));
+        assert!(!is_retryable(&error("403 Forbidden")));
+        assert!(is_retryable(&error("503 Service Unavailable")));
+        assert!(!is_retryable(&error("404 Not Found")));
+        assert!(!is_retryable(&ClientError::InvalidToken));
+
+        let response = |status, body: &str| BatchResponse {
+            status,
+            body: body.to_string(),
+        };
+        assert!(is_retryable_response(&response(
+            403,
+            r#"{"error": {"errors": [{"reason": "rateLimitExceeded"}]}}"#
+        )));
+        assert!(is_retryable_response(&response(
+            403,
+            r#"{"error": {"errors": [{"reason": "userRateLimitExceeded"}]}}"#
+        )));
+        assert!(!is_retryable_response(&response(
+            403,
+            r#"{"error": {"errors": [{"reason": "forbidden"}]}}"#
+        )));
+        assert!(is_retryable_response(&response(500, "")));
+    }
+
+    #[test]
+    fn test_status_code() {
+        use super::status_code;
+        use gcal::ClientError;
+
+        let err: anyhow::Error = ClientError::UnknownError(
+            "HTTP status client error (410 Gone) for url (https://www.googleapis.com/calendar/v3/calendars/primary/events)".to_string(),
+        )
+        .into();
+        assert_eq!(status_code(&err), Some(410));
+
+        let err: anyhow::Error =
+            ClientError::UnknownError("error sending request for url".to_string()).into();
+        assert_eq!(status_code(&err), None);
+        assert_eq!(status_code(&ClientError::InvalidToken.into()), None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        use super::GoogleClient;
+        use crate::record::Record;
+        use gcal::resources::Event;
+
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 9).unwrap();
+        let time = |h| chrono::NaiveTime::from_hms_opt(h, 30, 0).unwrap();
+
+        let mut at = Record::build();
+        at.set_date(date).set_at(Some(time(9)));
+
+        let mut scheduled = Record::build();
+        scheduled
+            .set_date(date)
+            .set_scheduled(Some((time(9), time(11))));
+
+        let mut all_day = Record::build();
+        all_day.set_date(date).set_all_day();
+
+        let mut span = all_day.clone();
+        span.set_end_date(date.succ_opt().and_then(|d| d.succ_opt()));
+
+        let mut client = GoogleClient::default();
+
+        for mut record in [at, scheduled, all_day, span.clone()] {
+            record.set_detail("round trip".to_string());
+
+            let mut event = Event::default();
+            event.summary = Some(record.detail());
+            client.set_event_dates(&mut event, &record);
+
+            if record.all_day() {
+                let start = event.start.clone().unwrap();
+                assert_eq!(start.date, Some("2024-03-09".to_string()));
+                assert!(start.date_time.is_none());
+            }
+
+            assert_eq!(client.event_to_record(event).unwrap(), record);
+        }
+
+        let mut event = Event::default();
+        client.set_event_dates(&mut event, &span);
+        assert_eq!(event.end.unwrap().date, Some("2024-03-12".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_replay() {
+        use super::GoogleClient;
+        use crate::{
+            config::{Config, DBType},
+            db::{recorder::Transport, EditConflict, RemoteClient},
+            record::{Record, RecordType},
+        };
+
+        let mut config = Config::default();
+        config.set_db_type(DBType::Google);
+        config.set_client_info("client".to_string(), "secret".to_string());
+        config.set_access_token(Some("token".to_string()));
+
+        let transport = Transport::load(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/db/fixtures/google.json"),
+        )
+        .unwrap();
+        let mut client = GoogleClient::new(config).unwrap();
+        client.set_transport(transport.clone());
+
+        let list = client.list_all("primary".to_string(), false).await.unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].detail(), "Dentist");
+        assert_eq!(list[0].record_type(), RecordType::Schedule);
+        assert_eq!(list[0].etag(), Some("\"3418127465034000\"".to_string()));
+        assert_eq!(list[1].detail(), "Conference");
+        assert!(list[1].all_day());
+        assert_eq!(
+            list[1].end_date(),
+            chrono::NaiveDate::from_ymd_opt(2024, 3, 10)
+        );
+
+        // the event was changed since it was listed, so the write is refused
+        let mut record = list[0].clone();
+        record.set_detail("Dentist, earlier".to_string());
+        let err = client
+            .update("primary".to_string(), record)
+            .await
+            .unwrap_err();
+        let EditConflict(current) = err.downcast_ref::<EditConflict<Record>>().unwrap();
+        assert_eq!(current.detail(), "Dentist (moved)");
+
+        client
+            .delete("primary".to_string(), list[1].internal_key().unwrap())
+            .await
+            .unwrap();
+
+        let mut record = list[0].clone();
+        record.set_internal_key(None);
+        record.set_detail("Lunch with Sam".to_string());
+        assert_eq!(
+            client.record("primary".to_string(), record).await.unwrap(),
+            "7q6r5s4t3u2v1w0x9y8z7a6b5c"
+        );
+        assert!(transport.is_finished());
+
+        // requests which were not recorded are refused
+        assert!(client
+            .delete("primary".to_string(), list[0].internal_key().unwrap())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pages() {
+        use super::GoogleClient;
+        use crate::{
+            config::{Config, DBType},
+            db::{
+                recorder::{Exchange, Transport},
+                RemoteClient,
+            },
+        };
+
+        let mut config = Config::default();
+        config.set_db_type(DBType::Google);
+        config.set_client_info("client".to_string(), "secret".to_string());
+        config.set_access_token(Some("token".to_string()));
+
+        let url = "https://www.googleapis.com/calendar/v3/calendars/primary/events";
+        let event = |id: &str, day: u32| {
+            serde_json::json!({
+                "id": id,
+                "status": "confirmed",
+                "summary": "Standup",
+                "start": { "dateTime": format!("2024-03-{:02}T09:00:00Z", day) },
+                "end": { "dateTime": format!("2024-03-{:02}T09:15:00Z", day) },
+                "recurrence": ["RRULE:FREQ=DAILY;INTERVAL=1"],
+            })
+        };
+        let exchange = |method: &str, url: String, body: serde_json::Value| {
+            serde_json::from_value::<Exchange>(serde_json::json!({
+                "request": { "method": method, "url": url },
+                "response": { "status": 200, "body": body.to_string() },
+            }))
+            .unwrap()
+        };
+
+        let transport = Transport::replay(vec![
+            exchange(
+                "GET",
+                url.to_string(),
+                serde_json::json!({ "items": [event("a", 11)], "nextPageToken": "2" }),
+            ),
+            exchange(
+                "GET",
+                url.to_string(),
+                serde_json::json!({ "items": [event("b", 12)] }),
+            ),
+            exchange(
+                "GET",
+                format!("{}/a/instances", url),
+                serde_json::json!({ "items": [event("a_1", 11)], "nextPageToken": "2" }),
+            ),
+            exchange(
+                "GET",
+                format!("{}/a/instances", url),
+                serde_json::json!({ "items": [event("a_2", 12)] }),
+            ),
+            serde_json::from_value::<Exchange>(serde_json::json!({
+                "request": { "method": "DELETE", "url": format!("{}/a", url) },
+                "response": { "status": 204 },
+            }))
+            .unwrap(),
+        ]);
+
+        let mut client = GoogleClient::new(config).unwrap();
+        client.set_transport(transport.clone());
+
+        // every page is read, of the listing and of the instances
+        let recurring = client.list_recurrence("primary".to_string()).await.unwrap();
+        assert_eq!(
+            recurring
+                .iter()
+                .map(|r| r.internal_key().unwrap())
+                .collect::<Vec<String>>(),
+            vec!["a", "b"]
+        );
+
+        assert_eq!(
+            client
+                .delete_recurrence("primary".to_string(), "a".to_string())
+                .await
+                .unwrap(),
+            vec!["a_1", "a_2"]
+        );
+        assert!(transport.is_finished());
     }
 }
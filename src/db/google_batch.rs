@@ -0,0 +1,241 @@
+use crate::db::recorder::Transport;
+use gcal::{sendable::Sendable, ClientError};
+
+const BATCH_URL: &str = "https://www.googleapis.com/batch/calendar/v3";
+const BOUNDARY: &str = "saturn_batch";
+// Google refuses batches of more than 50 requests.
+pub const BATCH_LIMIT: usize = 50;
+
+// BatchPart is a single request inside of a batch request.
+#[derive(Debug, Clone)]
+pub struct BatchPart {
+    method: &'static str,
+    path: String,
+    body: Option<Vec<u8>>,
+}
+
+impl BatchPart {
+    pub fn new(
+        method: &'static str,
+        target: &impl Sendable,
+        action: Option<String>,
+    ) -> Result<Self, ClientError> {
+        let url = target.url(action)?;
+        let path = match url.query().filter(|q| !q.is_empty()) {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+
+        let body = if method == "DELETE" {
+            None
+        } else {
+            Some(target.body_bytes()?)
+        };
+
+        Ok(Self { method, path, body })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+impl BatchResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+// encode writes the parts out as a multipart/mixed body. Each part is identified by its index, so
+// the responses can be put back in order.
+pub fn encode(parts: &[BatchPart]) -> Vec<u8> {
+    let mut out = Vec::new();
+
+    for (index, part) in parts.iter().enumerate() {
+        out.extend(
+            format!(
+                "--{}\r\nContent-Type: application/http\r\nContent-ID: <item-{}>\r\n\r\n{} {} HTTP/1.1\r\n",
+                BOUNDARY, index, part.method, part.path
+            )
+            .as_bytes(),
+        );
+
+        if let Some(body) = &part.body {
+            out.extend(
+                format!(
+                    "Content-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+                    body.len()
+                )
+                .as_bytes(),
+            );
+            out.extend(body);
+        }
+
+        out.extend(b"\r\n");
+    }
+
+    out.extend(format!("--{}--\r\n", BOUNDARY).as_bytes());
+    out
+}
+
+// decode splits a multipart/mixed batch response into the responses for each part, in the order
+// the parts were sent.
+pub fn decode(
+    content_type: &str,
+    body: &str,
+    count: usize,
+) -> Result<Vec<BatchResponse>, ClientError> {
+    let invalid = || ClientError::UnknownError("Invalid batch response".to_string());
+
+    let boundary = content_type
+        .split(';')
+        .find_map(|s| s.trim().strip_prefix("boundary="))
+        .map(|s| s.trim_matches('"'))
+        .ok_or_else(invalid)?;
+
+    let body = body.replace("\r\n", "\n");
+    let mut responses = vec![None; count];
+
+    for part in body.split(&format!("--{}", boundary)) {
+        let part = part.trim_start_matches('\n');
+        if part.is_empty() || part.starts_with("--") {
+            continue;
+        }
+
+        let mut sections = part.splitn(3, "\n\n");
+        let headers = sections.next().ok_or_else(invalid)?;
+        let response = sections.next().ok_or_else(invalid)?;
+        let content = sections.next().unwrap_or_default();
+
+        let index = headers
+            .lines()
+            .find_map(|l| {
+                l.split_once(':')
+                    .filter(|(k, _)| k.eq_ignore_ascii_case("content-id"))
+            })
+            .and_then(|(_, v)| {
+                v.trim()
+                    .trim_start_matches("<response-item-")
+                    .trim_end_matches('>')
+                    .parse::<usize>()
+                    .ok()
+            })
+            .filter(|i| *i < count)
+            .ok_or_else(invalid)?;
+
+        let status = response
+            .lines()
+            .next()
+            .and_then(|l| l.split_whitespace().nth(1))
+            .and_then(|s| s.parse::<u16>().ok())
+            .ok_or_else(invalid)?;
+
+        responses[index] = Some(BatchResponse {
+            status,
+            body: content.trim_end().to_string(),
+        });
+    }
+
+    responses
+        .into_iter()
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(invalid)
+}
+
+// send performs a batch request. Errors for the batch as a whole are reported the same way gcal
+// reports them, so they can be retried the same way; each part carries its own status otherwise.
+pub async fn send(
+    transport: &Transport,
+    access_token: String,
+    parts: Vec<BatchPart>,
+) -> Result<Vec<BatchResponse>, ClientError> {
+    let url = reqwest::Url::parse(BATCH_URL)?;
+    let resp = transport
+        .send(
+            transport
+                .request(reqwest::Method::POST, url.clone())
+                .bearer_auth(access_token)
+                .header(
+                    reqwest::header::CONTENT_TYPE,
+                    format!("multipart/mixed; boundary={}", BOUNDARY),
+                )
+                .body(encode(&parts)),
+        )
+        .await?
+        .error_for_status(&url)?;
+
+    decode(
+        resp.content_type.as_deref().unwrap_or_default(),
+        &resp.body,
+        parts.len(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_encode() {
+        use super::{encode, BatchPart};
+        use gcal::resources::Event;
+
+        let mut event = Event::default();
+        event.calendar_id = Some("primary".to_string());
+        event.id = Some("abc".to_string());
+        let delete = BatchPart::new("DELETE", &event, None).unwrap();
+
+        event.id = None;
+        event.summary = Some("lunch".to_string());
+        let import = BatchPart::new("POST", &event, Some("import".to_string())).unwrap();
+
+        let body = String::from_utf8(encode(&[delete, import])).unwrap();
+        assert!(body.contains(
+            "Content-ID: <item-0>\r\n\r\nDELETE /calendar/v3/calendars/primary/events/abc HTTP/1.1\r\n"
+        ));
+        assert!(body.contains(
+            "Content-ID: <item-1>\r\n\r\nPOST /calendar/v3/calendars/primary/events/import HTTP/1.1\r\nContent-Type: application/json\r\n"
+        ));
+        assert!(body.contains(r#""summary":"lunch""#));
+        assert!(body.ends_with("--saturn_batch--\r\n"));
+    }
+
+    #[test]
+    fn test_decode() {
+        use super::{decode, BatchResponse};
+
+        let body = "--batch_x\r\n\
+            Content-Type: application/http\r\n\
+            Content-ID: <response-item-1>\r\n\
+            \r\n\
+            HTTP/1.1 200 OK\r\n\
+            Content-Type: application/json\r\n\
+            \r\n\
+            {\"id\": \"abc\"}\r\n\
+            --batch_x\r\n\
+            Content-Type: application/http\r\n\
+            Content-ID: <response-item-0>\r\n\
+            \r\n\
+            HTTP/1.1 429 Too Many Requests\r\n\
+            \r\n\
+            --batch_x--\r\n";
+
+        let responses = decode("multipart/mixed; boundary=batch_x", body, 2).unwrap();
+        assert_eq!(
+            responses,
+            vec![
+                BatchResponse {
+                    status: 429,
+                    body: String::new()
+                },
+                BatchResponse {
+                    status: 200,
+                    body: "{\"id\": \"abc\"}".to_string()
+                }
+            ]
+        );
+
+        assert!(decode("multipart/mixed; boundary=batch_x", body, 3).is_err());
+        assert!(decode("application/json", body, 2).is_err());
+    }
+}
@@ -3,6 +3,7 @@ macro_rules! do_client {
     ($obj:ident, $block:block) => {{
         use $crate::time::now;
 
+        let mut attempt = 0;
         'end: loop {
             if let Some(expires) = $obj.config.access_token_expires_at() {
                 if expires - chrono::TimeDelta::try_hours(1).unwrap_or_default() < now().naive_utc() {
@@ -18,6 +19,13 @@ macro_rules! do_client {
                     ClientError::InvalidToken => {
                         $obj.refresh_access_token().await?;
                     }
+                    e if attempt < $obj.config.max_retries()
+                        && $crate::db::google::is_retryable(&e) =>
+                    {
+                        tokio::time::sleep($crate::db::google::backoff(&$obj.config, attempt))
+                            .await;
+                        attempt += 1;
+                    }
                     _ => break 'end Err(e),
                 },
             }
@@ -0,0 +1,153 @@
+use gcal::{
+    resources::{CalendarListItem, Event},
+    sendable::{QueryParams, Sendable},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// EventListRequest lists the events of a calendar, or the instances of a recurring event, with
+// query parameters that gcal's EventClient does not expose, such as sync and page tokens.
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct EventListRequest {
+    #[serde(skip)]
+    calendar_id: String,
+    #[serde(skip)]
+    event_id: Option<String>,
+    #[serde(skip)]
+    query: QueryParams,
+}
+
+impl EventListRequest {
+    pub fn new(calendar_id: String) -> Self {
+        Self {
+            calendar_id,
+            ..Default::default()
+        }
+    }
+
+    pub fn instances(calendar_id: String, event_id: String) -> Self {
+        Self {
+            calendar_id,
+            event_id: Some(event_id),
+            ..Default::default()
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: String) {
+        self.query.insert(key.to_string(), value);
+    }
+}
+
+impl Sendable for EventListRequest {
+    fn path(&self, _action: Option<String>) -> String {
+        match &self.event_id {
+            Some(event_id) => format!(
+                "calendars/{}/events/{}/instances",
+                self.calendar_id, event_id
+            ),
+            None => format!("calendars/{}/events", self.calendar_id),
+        }
+    }
+
+    fn query(&self) -> QueryParams {
+        self.query.clone()
+    }
+}
+
+// CalendarListRequest lists the calendars the user owns.
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct CalendarListRequest {}
+
+impl Sendable for CalendarListRequest {
+    fn path(&self, _action: Option<String>) -> String {
+        "users/me/calendarList".to_string()
+    }
+
+    fn query(&self) -> QueryParams {
+        QueryParams::from([("minAccessRole".to_string(), "owner".to_string())])
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct CalendarListPage {
+    #[serde(default)]
+    pub items: Vec<CalendarListItem>,
+}
+
+// EventPage is a single page of an event listing. Unlike gcal's Events, it carries the sync token
+// which is handed out on the last page.
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct EventPage {
+    #[serde(default)]
+    pub items: Vec<Event>,
+    pub next_page_token: Option<String>,
+    pub next_sync_token: Option<String>,
+}
+
+// FreeBusyRequest asks when the calendars are busy between two times.
+#[derive(Debug, Clone, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FreeBusyRequest {
+    time_min: String,
+    time_max: String,
+    items: Vec<FreeBusyItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct FreeBusyItem {
+    id: String,
+}
+
+impl FreeBusyRequest {
+    pub fn new(
+        calendars: Vec<String>,
+        start: chrono::DateTime<chrono::Local>,
+        end: chrono::DateTime<chrono::Local>,
+    ) -> Self {
+        Self {
+            time_min: start.to_rfc3339(),
+            time_max: end.to_rfc3339(),
+            items: calendars
+                .into_iter()
+                .map(|id| FreeBusyItem { id })
+                .collect(),
+        }
+    }
+}
+
+impl Sendable for FreeBusyRequest {
+    fn path(&self, _action: Option<String>) -> String {
+        "freeBusy".to_string()
+    }
+
+    fn query(&self) -> QueryParams {
+        QueryParams::default()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct FreeBusyResponse {
+    #[serde(default)]
+    pub calendars: BTreeMap<String, FreeBusyCalendar>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct FreeBusyCalendar {
+    #[serde(default)]
+    pub busy: Vec<TimePeriod>,
+    #[serde(default)]
+    pub errors: Vec<FreeBusyError>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct TimePeriod {
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct FreeBusyError {
+    pub domain: String,
+    pub reason: String,
+}
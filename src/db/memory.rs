@@ -1,6 +1,6 @@
 use super::unixfile::UnixFileLoader;
 use crate::{
-    db::DB,
+    db::{sync::SyncState, DB},
     filenames::saturn_db,
     record::{Record, RecurringRecord},
     time::now,
@@ -17,12 +17,42 @@ pub struct MemoryDB {
     records: BTreeMap<u64, Record>,
     recurrence_key: u64,
     recurring: BTreeMap<u64, RecurringRecord>,
+    #[serde(default)]
+    sync: SyncState,
 }
 
 impl MemoryDB {
     pub fn new() -> Self {
         Self::default()
     }
+
+    // materialize records each recurrence on its days up to and including `until`, skipping the
+    // ones which were recorded already.
+    async fn materialize(&mut self, until: chrono::NaiveDate) -> Result<()> {
+        let mut recurring = self.recurring.clone();
+        let records = self.records.clone();
+
+        for recur in recurring.values_mut() {
+            let mut begin = recur.record().datetime();
+
+            while begin.date_naive() <= until {
+                let seen = records.values().any(|record| {
+                    record.recurrence_key() == Some(recur.recurrence_key())
+                        && record.datetime() == begin
+                });
+
+                if !seen {
+                    let key = self.next_key();
+                    self.record(recur.record_from(key, begin.naive_local()))
+                        .await?;
+                }
+
+                begin += recur.recurrence().duration();
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -33,6 +63,7 @@ impl DB for MemoryDB {
         self.records = db.records;
         self.recurrence_key = db.recurrence_key;
         self.recurring = db.recurring;
+        self.sync = db.sync;
         Ok(())
     }
 
@@ -56,6 +87,14 @@ impl DB for MemoryDB {
         self.recurrence_key = primary_key;
     }
 
+    fn sync_state(&self) -> Option<SyncState> {
+        Some(self.sync.clone())
+    }
+
+    fn set_sync_state(&mut self, state: SyncState) {
+        self.sync = state;
+    }
+
     async fn delete(&mut self, primary_key: u64) -> Result<()> {
         self.records.remove(&primary_key);
         Ok(())
@@ -95,35 +134,8 @@ impl DB for MemoryDB {
     }
 
     async fn update_recurrence(&mut self) -> Result<()> {
-        let mut recurring = self.recurring.clone();
-        let records = self.records.clone();
-
-        for (_, recur) in &mut recurring {
-            let mut seen: Option<&Record> = None;
-
-            let mut begin = recur.record().datetime();
-            let tomorrow = (now() + chrono::TimeDelta::try_days(1).unwrap_or_default()).date_naive();
-
-            while begin.date_naive() <= tomorrow {
-                for (_, record) in &records {
-                    if let Some(key) = record.recurrence_key() {
-                        if key == recur.recurrence_key() && record.datetime() == begin {
-                            seen = Some(record);
-                        }
-                    }
-                }
-
-                if seen.is_none() {
-                    let key = self.next_key();
-                    self.record(recur.record_from(key, begin.naive_local()))
-                        .await?;
-                }
-
-                begin += recur.recurrence().duration();
-            }
-        }
-
-        Ok(())
+        self.materialize((now() + chrono::TimeDelta::try_days(1).unwrap_or_default()).date_naive())
+            .await
     }
 
     async fn list_today(&mut self, include_completed: bool) -> Result<Vec<Record>> {
@@ -133,7 +145,7 @@ impl DB for MemoryDB {
             .records
             .iter()
             .filter_map(|(_, v)| {
-                if v.date() != today || (v.completed() && !include_completed) {
+                if !v.on_date(today) || (v.completed() && !include_completed) {
                     None
                 } else {
                     Some(v.clone())
@@ -164,6 +176,44 @@ impl DB for MemoryDB {
         Ok(v)
     }
 
+    // list_range also lists the recurrences on the days of the range which have not been recorded
+    // yet. They are not recorded here, so nothing is kept of them when the database is saved; they
+    // have no ID of their own until `update_recurrence` records them.
+    async fn list_range(
+        &mut self,
+        start: chrono::NaiveDate,
+        end: chrono::NaiveDate,
+        include_completed: bool,
+    ) -> Result<Vec<Record>> {
+        let mut v = self
+            .list_all(include_completed)
+            .await?
+            .into_iter()
+            .filter(|r| r.in_range(start, end))
+            .collect::<Vec<Record>>();
+
+        for recur in self.recurring.values() {
+            let mut begin = recur.clone().record().datetime();
+            let step = recur.recurrence().duration();
+
+            while step > chrono::Duration::zero() && begin.date_naive() <= end {
+                let seen = self.records.values().any(|record| {
+                    record.recurrence_key() == Some(recur.recurrence_key())
+                        && record.datetime() == begin
+                });
+
+                let record = recur.record_from(0, begin.naive_local());
+                if !seen && record.in_range(start, end) {
+                    v.push(record);
+                }
+
+                begin += step;
+            }
+        }
+
+        Ok(v)
+    }
+
     async fn events_now(
         &mut self,
         last: chrono::Duration,
@@ -209,7 +259,7 @@ impl DB for MemoryDB {
                     ret.push(item.clone());
                 } else if let Some(notifications) = item.notifications() {
                     for notification in notifications {
-                        let dt_window = dt - notification.duration();
+                        let dt_window = crate::notifications::fires_at(item, &notification);
                         let dt_time = dt_window
                             .time()
                             .with_second(0)
@@ -218,7 +268,11 @@ impl DB for MemoryDB {
                             .unwrap();
                         let n_time = n.time().with_second(0).unwrap().with_nanosecond(0).unwrap();
 
-                        if dt > n && dt_window.date_naive() == n.date_naive() && dt_time == n_time {
+                        let anchor = crate::notifications::anchored(item, notification.anchor());
+                        if anchor > n.naive_local()
+                            && dt_window.date() == n.date_naive()
+                            && dt_time == n_time
+                        {
                             ret.push(item.clone());
                             break;
                         }
@@ -319,4 +373,93 @@ mod tests {
         assert_eq!(db.primary_key, db2.primary_key);
         assert_eq!(db.records, db2.records);
     }
+
+    #[tokio::test]
+    async fn test_list_range() {
+        use crate::db::{memory::MemoryDB, DB};
+        use crate::record::{Record, RecurringRecord};
+
+        let mut db = MemoryDB::new();
+        let today = crate::time::now().date_naive();
+        let day = |n| today + chrono::TimeDelta::try_days(n).unwrap_or_default();
+
+        let mut record = Record::build();
+        record
+            .set_date(today)
+            .set_at(Some(chrono::NaiveTime::from_hms_opt(9, 0, 0).unwrap()));
+        let mut recurrence = RecurringRecord::new(
+            record,
+            fancy_duration::FancyDuration::new(chrono::TimeDelta::try_days(1).unwrap()),
+        );
+        recurrence.set_recurrence_key(1);
+        db.record_recurrence(recurrence).await.unwrap();
+
+        let list = db.list_range(day(5), day(6), false).await.unwrap();
+        assert_eq!(
+            list.iter().map(|r| r.date()).collect::<Vec<_>>(),
+            vec![day(5), day(6)]
+        );
+        assert!(list.iter().all(|r| r.recurrence_key() == Some(1)));
+
+        // nothing is recorded for the range
+        assert!(db.list_all(false).await.unwrap().is_empty());
+
+        // recorded recurrences are listed once
+        db.materialize(day(5)).await.unwrap();
+        let list = db.list_range(day(0), day(6), false).await.unwrap();
+        assert_eq!(list.len(), 7);
+        assert_eq!(list.iter().filter(|r| r.primary_key() == 0).count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_record_entry() {
+        use crate::config::Config;
+        use crate::db::{memory::MemoryDB, DB};
+        use crate::parsers::entry::EntryParser;
+        use crate::record::Record;
+
+        let config = Config::default();
+        let mut db = MemoryDB::new();
+        let today = crate::time::now().date_naive();
+        let entry = |s: &str| {
+            EntryParser::new(
+                s.split(' ').map(|s| s.to_string()).collect::<Vec<String>>(),
+                false,
+            )
+        };
+
+        let mut meeting = Record::build();
+        meeting
+            .set_primary_key(db.next_key())
+            .set_date(today + chrono::TimeDelta::try_days(3).unwrap_or_default())
+            .set_at(Some(chrono::NaiveTime::from_hms_opt(9, 0, 0).unwrap()));
+        db.record(meeting).await.unwrap();
+
+        assert!(db
+            .record_entry(entry("tomorrow at 3pm Lunch"), false, &config)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // the third occurrence overlaps the meeting, and the entry is still made
+        let overlap = db
+            .record_entry(entry("recur 1d tomorrow at 9am Standup"), false, &config)
+            .await
+            .unwrap();
+        assert_eq!(
+            overlap
+                .iter()
+                .map(|r| r.primary_key())
+                .collect::<Vec<u64>>(),
+            vec![1]
+        );
+        assert_eq!(db.list_recurrence().await.unwrap().len(), 1);
+
+        assert!(db
+            .record_entry(entry("recur 1d tomorrow at 9am Standup"), true, &config)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(db.list_recurrence().await.unwrap().len(), 2);
+    }
 }
@@ -1,15 +1,28 @@
+pub mod cache;
+pub mod fake;
 pub mod google;
+pub mod google_batch;
 pub mod google_macros;
+pub mod google_requests;
 pub mod memory;
+pub mod queue;
+pub mod recorder;
 pub mod remote;
+pub mod sync;
 pub mod unixfile;
 
 use crate::{
+    config::Config,
+    conflicts::overlapping,
+    db::{queue::WriteQueue, sync::SyncState},
     parsers::entry::EntryParser,
     record::{Record, RecurringRecord},
+    time::window,
 };
 use anyhow::Result;
 use async_trait::async_trait;
+use chrono::Timelike;
+use serde::{Deserialize, Serialize};
 
 #[async_trait]
 pub trait DB: Send {
@@ -33,10 +46,69 @@ pub trait DB: Send {
     fn recurrence_key(&self) -> u64;
     fn set_recurrence_key(&mut self, primary_key: u64);
 
-    async fn record_entry(&mut self, entry: EntryParser) -> Result<()> {
+    // write_queue is only available for remote databases, which queue writes while offline.
+    fn write_queue(&self) -> Option<WriteQueue> {
+        None
+    }
+
+    fn clear_conflicts(&mut self) {}
+
+    // sync_state is only available for local databases, which can be mirrored to a remote
+    // calendar with `sync::sync`.
+    fn sync_state(&self) -> Option<SyncState> {
+        None
+    }
+
+    fn set_sync_state(&mut self, _state: SyncState) {}
+
+    // gc is only available for remote databases, which keep maps between their own IDs and the
+    // remote calendar's.
+    async fn gc(&mut self, _renumber: bool) -> Result<Option<GcReport>> {
+        Ok(None)
+    }
+
+    // record_entry records the entry and returns the existing records it takes place at the same
+    // time as, so they can be reported. Recurring entries are checked at each occurrence within
+    // the query window. At records are assumed to take up the default duration. Nothing is
+    // checked when forced.
+    async fn record_entry(
+        &mut self,
+        entry: EntryParser,
+        force: bool,
+        config: &Config,
+    ) -> Result<Vec<Record>> {
         let record = entry.to_record()?;
         let recurrence = record.recurrence();
         let mut record = record.record();
+
+        let mut overlap = std::collections::BTreeMap::new();
+
+        if !force {
+            let existing = self.list_all(false).await?;
+            let default_duration = config.default_duration().duration();
+
+            let mut occurrences = vec![record.clone()];
+            if let Some(mut recurrence) = recurrence.clone() {
+                recurrence.set_record(record.clone());
+                let step = recurrence.recurrence().duration();
+                let window = window(config);
+                let mut begin = record.datetime() + step;
+
+                while step > chrono::Duration::zero() && begin < window.1 {
+                    if begin >= window.0 {
+                        occurrences.push(recurrence.record_from(0, begin.naive_local()));
+                    }
+                    begin += step;
+                }
+            }
+
+            for occurrence in &occurrences {
+                for other in overlapping(occurrence, &existing, default_duration) {
+                    overlap.insert(other.primary_key(), other);
+                }
+            }
+        }
+
         record.set_primary_key(self.next_key());
 
         if let Some(mut recurrence) = recurrence {
@@ -53,7 +125,7 @@ pub trait DB: Send {
             self.record(record).await?;
         }
 
-        Ok(())
+        Ok(overlap.into_values().collect())
     }
 
     async fn update(&mut self, record: Record) -> Result<()>;
@@ -70,6 +142,22 @@ pub trait DB: Send {
     async fn update_recurrence(&mut self) -> Result<()>;
     async fn list_today(&mut self, include_completed: bool) -> Result<Vec<Record>>;
     async fn list_all(&mut self, include_completed: bool) -> Result<Vec<Record>>;
+
+    // list_range lists the records which take place on any day from `start` to `end`, inclusive.
+    async fn list_range(
+        &mut self,
+        start: chrono::NaiveDate,
+        end: chrono::NaiveDate,
+        include_completed: bool,
+    ) -> Result<Vec<Record>> {
+        Ok(self
+            .list_all(include_completed)
+            .await?
+            .into_iter()
+            .filter(|r| r.in_range(start, end))
+            .collect())
+    }
+
     async fn events_now(
         &mut self,
         last: chrono::Duration,
@@ -78,15 +166,106 @@ pub trait DB: Send {
     async fn complete_task(&mut self, primary_key: u64) -> Result<()>;
 }
 
+// events_now filters a listing down to the records which are happening within `last` of now, or
+// which have a notification due this minute.
+pub fn events_now(list: Vec<Record>, last: chrono::Duration) -> Vec<Record> {
+    let mut v = Vec::new();
+    for item in list {
+        let dt = item.datetime();
+        let n = crate::time::now();
+        if dt > n && n > dt - last {
+            v.push(item);
+        } else if let Some(notifications) = item.notifications() {
+            for notification in notifications {
+                let dt_window = crate::notifications::fires_at(&item, &notification);
+                let dt_time = dt_window
+                    .time()
+                    .with_second(0)
+                    .unwrap()
+                    .with_nanosecond(0)
+                    .unwrap();
+                let n_time = n.time().with_second(0).unwrap().with_nanosecond(0).unwrap();
+
+                let anchor = crate::notifications::anchored(&item, notification.anchor());
+                if anchor > n.naive_local()
+                    && dt_window.date() == n.date_naive()
+                    && dt_time == n_time
+                {
+                    v.push(item);
+                    break;
+                }
+            }
+        }
+    }
+
+    v
+}
+
+// EditConflict is returned when updating an item which was changed remotely since it was read. It
+// carries the remote version of the item, so the changes can be merged.
+#[derive(Debug, Clone)]
+pub struct EditConflict<T>(pub T);
+
+impl<T> std::fmt::Display for EditConflict<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("This item was changed remotely since it was last read")
+    }
+}
+
+impl<T: std::fmt::Debug> std::error::Error for EditConflict<T> {}
+
+// GcReport describes what was reclaimed from a database's ID maps. `renumbered` maps old primary
+// keys to new ones.
+#[derive(Debug, Clone, Default)]
+pub struct GcReport {
+    pub removed: Vec<u64>,
+    pub removed_recurring: Vec<u64>,
+    pub renumbered: std::collections::BTreeMap<u64, u64>,
+}
+
+// SyncResult is the outcome of a sync against a remote calendar. A full sync replaces everything
+// that is known about the calendar; otherwise `records` and `removed` only describe what changed
+// since the token passed to the sync was issued.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncResult {
+    pub records: Vec<Record>,
+    pub removed: Vec<String>,
+    pub token: Option<String>,
+    pub full: bool,
+}
+
 #[async_trait]
 pub trait RemoteClient {
-    async fn update(&mut self, calendar_id: String, record: Record) -> Result<()>;
+    // is_offline reports whether the error was caused by the remote side being unreachable, as
+    // opposed to it rejecting the request.
+    fn is_offline(&self, _error: &anyhow::Error) -> bool {
+        false
+    }
+
+    // sync fetches the changes to the calendar since `token` was issued. Clients which cannot
+    // synchronize incrementally always perform a full listing.
+    async fn sync(&mut self, calendar_id: String, _token: Option<String>) -> Result<SyncResult> {
+        Ok(SyncResult {
+            records: self.list_all(calendar_id, true).await?,
+            full: true,
+            ..Default::default()
+        })
+    }
+
+    // updates return the new version of the remote event, which is used to detect changes made
+    // elsewhere on the next update.
+    async fn update(&mut self, calendar_id: String, record: Record) -> Result<Option<String>>;
     async fn update_recurring(
         &mut self,
         calendar_id: String,
         record: RecurringRecord,
-    ) -> Result<()>;
+    ) -> Result<Option<String>>;
     async fn get(&mut self, calendar_id: String, event_id: String) -> Result<Record>;
+
+    // is_gone reports whether the event was deleted from the calendar, as opposed to only having
+    // left the query window. Only a definite answer from the remote side counts as deleted.
+    async fn is_gone(&mut self, calendar_id: String, event_id: String) -> Result<bool>;
+
     async fn get_recurring(
         &mut self,
         calendar_id: String,
@@ -99,6 +278,30 @@ pub trait RemoteClient {
         event_id: String,
     ) -> Result<Vec<String>>;
     async fn record(&mut self, calendar_id: String, record: Record) -> Result<String>;
+
+    // delete_many and record_many act on several events at once. Clients which cannot batch
+    // requests perform them one at a time. record_many reports the outcome of each record, so
+    // that the records which were saved are not lost when others fail.
+    async fn delete_many(&mut self, calendar_id: String, event_ids: Vec<String>) -> Result<()> {
+        for event_id in event_ids {
+            self.delete(calendar_id.clone(), event_id).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn record_many(
+        &mut self,
+        calendar_id: String,
+        records: Vec<Record>,
+    ) -> Result<Vec<Result<String>>> {
+        let mut ids = Vec::new();
+        for record in records {
+            ids.push(self.record(calendar_id.clone(), record).await);
+        }
+
+        Ok(ids)
+    }
     async fn record_recurrence(
         &mut self,
         calendar_id: String,
@@ -116,6 +319,24 @@ pub trait RemoteClient {
         calendar_id: String,
         include_completed: bool,
     ) -> Result<Vec<Record>>;
+
+    // list_range lists the events which take place on any day from `start` to `end`, inclusive,
+    // even outside the query window. Clients which cannot list a range filter the window instead.
+    async fn list_range(
+        &mut self,
+        calendar_id: String,
+        start: chrono::NaiveDate,
+        end: chrono::NaiveDate,
+        include_completed: bool,
+    ) -> Result<Vec<Record>> {
+        Ok(self
+            .list_all(calendar_id, include_completed)
+            .await?
+            .into_iter()
+            .filter(|r| r.in_range(start, end))
+            .collect())
+    }
+
     async fn events_now(
         &mut self,
         calendar_id: String,
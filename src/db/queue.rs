@@ -0,0 +1,336 @@
+use crate::{
+    record::{Record, RecurringRecord},
+    time::now,
+};
+use serde::{Deserialize, Serialize};
+
+// PendingWrite is a write which could not be delivered to the remote calendar, and must be
+// replayed once it can be reached again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PendingWrite {
+    Insert(Record),
+    Update(Record),
+    Delete {
+        primary_key: u64,
+        internal_key: String,
+    },
+    Complete(u64),
+    InsertRecurrence(RecurringRecord),
+    UpdateRecurring(RecurringRecord),
+    DeleteRecurrence {
+        recurrence_key: u64,
+        internal_key: String,
+    },
+}
+
+impl PendingWrite {
+    // primary_key is the ID the write is shown with; for recurring writes, that is the recurrence
+    // key, as with `saturn delete -r`.
+    pub fn primary_key(&self) -> u64 {
+        match self {
+            Self::Insert(record) | Self::Update(record) => record.primary_key(),
+            Self::Delete { primary_key, .. } | Self::Complete(primary_key) => *primary_key,
+            _ => self.recurrence_key().unwrap_or_default(),
+        }
+    }
+
+    pub fn recurrence_key(&self) -> Option<u64> {
+        match self {
+            Self::InsertRecurrence(record) | Self::UpdateRecurring(record) => {
+                Some(record.recurrence_key())
+            }
+            Self::DeleteRecurrence { recurrence_key, .. } => Some(*recurrence_key),
+            _ => None,
+        }
+    }
+
+    pub fn action(&self) -> &'static str {
+        match self {
+            Self::Insert(_) => "insert",
+            Self::Update(_) => "update",
+            Self::Delete { .. } => "delete",
+            Self::Complete(_) => "complete",
+            Self::InsertRecurrence(_) => "insert recurring",
+            Self::UpdateRecurring(_) => "update recurring",
+            Self::DeleteRecurrence { .. } => "delete recurring",
+        }
+    }
+
+    pub fn detail(&self) -> Option<String> {
+        match self {
+            Self::Insert(record) | Self::Update(record) => Some(record.detail()),
+            Self::InsertRecurrence(record) | Self::UpdateRecurring(record) => {
+                Some(record.clone().record().detail())
+            }
+            _ => None,
+        }
+    }
+}
+
+// Conflict is a queued write the remote calendar refused when it was replayed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conflict {
+    pub write: PendingWrite,
+    pub error: String,
+    pub at: chrono::DateTime<chrono::Local>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct WriteQueue {
+    pending: Vec<PendingWrite>,
+    conflicts: Vec<Conflict>,
+}
+
+impl WriteQueue {
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn pending(&self) -> Vec<PendingWrite> {
+        self.pending.clone()
+    }
+
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        self.conflicts.clone()
+    }
+
+    pub fn clear_conflicts(&mut self) {
+        self.conflicts.clear()
+    }
+
+    // is_provisional reports whether the record was created offline and has not been sent yet.
+    pub fn is_provisional(&self, primary_key: u64) -> bool {
+        self.pending
+            .iter()
+            .any(|w| matches!(w, PendingWrite::Insert(r) if r.primary_key() == primary_key))
+    }
+
+    // is_provisional_recurrence reports whether the recurring record was created offline and has
+    // not been sent yet.
+    pub fn is_provisional_recurrence(&self, recurrence_key: u64) -> bool {
+        self.pending.iter().any(|w| {
+            matches!(w, PendingWrite::InsertRecurrence(r) if r.recurrence_key() == recurrence_key)
+        })
+    }
+
+    // push adds a write to the queue. Writes against records that were created offline are folded
+    // into the queued insert, so they never reach the remote side separately.
+    pub fn push(&mut self, write: PendingWrite) {
+        if let Some(recurrence_key) = write.recurrence_key() {
+            return self.push_recurring(recurrence_key, write);
+        }
+
+        let primary_key = write.primary_key();
+
+        if self.is_provisional(primary_key) {
+            match write {
+                PendingWrite::Update(record) => {
+                    for pending in &mut self.pending {
+                        if let PendingWrite::Insert(r) = pending {
+                            if r.primary_key() == primary_key {
+                                *r = record.clone();
+                            }
+                        }
+                    }
+                }
+                PendingWrite::Complete(_) => {
+                    for pending in &mut self.pending {
+                        if let PendingWrite::Insert(r) = pending {
+                            if r.primary_key() == primary_key {
+                                r.set_completed(true);
+                            }
+                        }
+                    }
+                }
+                PendingWrite::Delete { .. } => self
+                    .pending
+                    .retain(|w| w.recurrence_key().is_some() || w.primary_key() != primary_key),
+                _ => self.pending.push(write),
+            }
+        } else {
+            if matches!(write, PendingWrite::Delete { .. }) {
+                self.pending
+                    .retain(|w| w.recurrence_key().is_some() || w.primary_key() != primary_key);
+            }
+            self.pending.push(write);
+        }
+    }
+
+    // push_recurring folds writes against recurring records the same way push does for records.
+    fn push_recurring(&mut self, recurrence_key: u64, write: PendingWrite) {
+        let provisional = self.is_provisional_recurrence(recurrence_key);
+
+        match write {
+            PendingWrite::UpdateRecurring(record) if provisional => {
+                for pending in &mut self.pending {
+                    if let PendingWrite::InsertRecurrence(r) = pending {
+                        if r.recurrence_key() == recurrence_key {
+                            *r = record.clone();
+                        }
+                    }
+                }
+            }
+            PendingWrite::DeleteRecurrence { .. } => {
+                self.pending
+                    .retain(|w| w.recurrence_key() != Some(recurrence_key));
+                if !provisional {
+                    self.pending.push(write);
+                }
+            }
+            _ => self.pending.push(write),
+        }
+    }
+
+    // take empties the queue so it can be replayed; anything that still cannot be delivered must
+    // be handed back with `requeue`.
+    pub fn take(&mut self) -> Vec<PendingWrite> {
+        std::mem::take(&mut self.pending)
+    }
+
+    pub fn requeue(&mut self, mut writes: Vec<PendingWrite>) {
+        writes.append(&mut self.pending);
+        self.pending = writes;
+    }
+
+    pub fn conflict(&mut self, write: PendingWrite, error: String) {
+        self.conflicts.push(Conflict {
+            write,
+            error,
+            at: now(),
+        })
+    }
+
+    // apply replays the queued writes on top of a listing, so that listings reflect what has been
+    // done offline.
+    pub fn apply(&self, cache: &mut crate::db::cache::RecordCache) {
+        for write in &self.pending {
+            match write {
+                PendingWrite::Insert(record) | PendingWrite::Update(record) => {
+                    cache.insert(record.clone())
+                }
+                PendingWrite::Delete { primary_key, .. } => cache.remove(*primary_key),
+                PendingWrite::Complete(primary_key) => cache.set_completed(*primary_key),
+                PendingWrite::InsertRecurrence(record) | PendingWrite::UpdateRecurring(record) => {
+                    let mut record = record.clone();
+                    cache.insert(record.record().clone());
+                    cache.insert_recurring(record);
+                }
+                PendingWrite::DeleteRecurrence { recurrence_key, .. } => {
+                    cache.remove_recurrence(*recurrence_key)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_folding() {
+        use super::{PendingWrite, WriteQueue};
+        use crate::record::Record;
+
+        let mut queue = WriteQueue::default();
+        let mut record = Record::default();
+        record.set_primary_key(1);
+        record.set_detail("offline".to_string());
+        queue.push(PendingWrite::Insert(record.clone()));
+        assert!(queue.is_provisional(1));
+
+        record.set_detail("edited offline".to_string());
+        queue.push(PendingWrite::Update(record.clone()));
+        queue.push(PendingWrite::Complete(1));
+        assert_eq!(queue.pending().len(), 1);
+
+        if let PendingWrite::Insert(r) = &queue.pending()[0] {
+            assert_eq!(r.detail(), "edited offline");
+            assert!(r.completed());
+        } else {
+            panic!("insert was not kept");
+        }
+
+        queue.push(PendingWrite::Delete {
+            primary_key: 1,
+            internal_key: String::new(),
+        });
+        assert!(queue.is_empty());
+
+        let mut record = Record::default();
+        record.set_primary_key(2);
+        queue.push(PendingWrite::Update(record));
+        queue.push(PendingWrite::Complete(2));
+        queue.push(PendingWrite::Delete {
+            primary_key: 2,
+            internal_key: "remote".to_string(),
+        });
+        assert_eq!(queue.pending().len(), 1);
+        assert_eq!(queue.pending()[0].action(), "delete");
+    }
+
+    #[test]
+    fn test_recurring_folding() {
+        use super::{PendingWrite, WriteQueue};
+        use crate::record::{Record, RecurringRecord};
+
+        let mut queue = WriteQueue::default();
+        let mut record = Record::default();
+        record.set_primary_key(1);
+        record.set_detail("standup".to_string());
+        let mut recurring = RecurringRecord::new(
+            record,
+            fancy_duration::FancyDuration::new(chrono::TimeDelta::try_days(1).unwrap()),
+        );
+        recurring.set_recurrence_key(1);
+
+        queue.push(PendingWrite::InsertRecurrence(recurring.clone()));
+        assert!(queue.is_provisional_recurrence(1));
+
+        recurring.record().set_detail("daily standup".to_string());
+        queue.push(PendingWrite::UpdateRecurring(recurring.clone()));
+        assert_eq!(queue.pending().len(), 1);
+        assert_eq!(queue.pending()[0].detail().unwrap(), "daily standup");
+
+        // a record sharing the number of the recurrence is left alone
+        queue.push(PendingWrite::Delete {
+            primary_key: 1,
+            internal_key: "remote".to_string(),
+        });
+        assert_eq!(queue.pending().len(), 2);
+
+        queue.push(PendingWrite::DeleteRecurrence {
+            recurrence_key: 1,
+            internal_key: String::new(),
+        });
+        assert_eq!(queue.pending().len(), 1);
+        assert_eq!(queue.pending()[0].action(), "delete");
+
+        queue.push(PendingWrite::DeleteRecurrence {
+            recurrence_key: 2,
+            internal_key: "remote".to_string(),
+        });
+        assert_eq!(queue.pending()[1].action(), "delete recurring");
+    }
+
+    #[test]
+    fn test_requeue() {
+        use super::{PendingWrite, WriteQueue};
+
+        let mut queue = WriteQueue::default();
+        queue.push(PendingWrite::Complete(1));
+        queue.push(PendingWrite::Complete(2));
+
+        let mut writes = queue.take();
+        assert!(queue.is_empty());
+        queue.push(PendingWrite::Complete(3));
+
+        let first = writes.remove(0);
+        queue.conflict(first, "rejected".to_string());
+        queue.requeue(writes);
+
+        let pending = queue.pending();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].primary_key(), 2);
+        assert_eq!(pending[1].primary_key(), 3);
+        assert_eq!(queue.conflicts().len(), 1);
+    }
+}
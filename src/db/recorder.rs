@@ -0,0 +1,249 @@
+use gcal::ClientError;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+// Request is an HTTP request made to Google Calendar. The access token is not kept.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub method: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+impl Request {
+    fn path(&self) -> String {
+        reqwest::Url::parse(&self.url)
+            .map(|url| url.path().to_string())
+            .unwrap_or_default()
+    }
+}
+
+// Response is what Google Calendar answered to a request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    #[serde(default)]
+    pub body: String,
+}
+
+impl Response {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    // error_for_status raises unsuccessful responses the same way gcal does, so that they are
+    // retried and reported the same way.
+    pub fn error_for_status(self, url: &reqwest::Url) -> Result<Self, ClientError> {
+        if self.is_success() {
+            return Ok(self);
+        }
+
+        if self.status == 401 {
+            return Err(ClientError::InvalidToken);
+        }
+
+        let reason = reqwest::StatusCode::from_u16(self.status)
+            .ok()
+            .and_then(|s| s.canonical_reason())
+            .unwrap_or("<unknown status code>");
+
+        Err(ClientError::UnknownError(format!(
+            "HTTP status {} error ({} {}) for url ({})",
+            if self.status < 500 {
+                "client"
+            } else {
+                "server"
+            },
+            self.status,
+            reason,
+            url
+        )))
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, ClientError> {
+        Ok(serde_json::from_str(&self.body)?)
+    }
+}
+
+// Exchange is a request and the response to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Exchange {
+    pub request: Request,
+    pub response: Response,
+}
+
+#[derive(Debug, Clone, Default)]
+enum Mode {
+    #[default]
+    Live,
+    Record(PathBuf, Arc<Mutex<Vec<Exchange>>>),
+    Replay(Arc<Mutex<VecDeque<Exchange>>>),
+}
+
+// Transport sends the HTTP requests GoogleClient makes. With a path, every exchange is also
+// written to a fixture file, which a replaying transport can serve later without a network
+// connection. Replayed requests must be made in the order they were recorded, and are matched by
+// method and path; queries are not compared, as listings ask for times relative to now.
+#[derive(Debug, Clone, Default)]
+pub struct Transport {
+    client: reqwest::Client,
+    mode: Mode,
+}
+
+impl Transport {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            mode: match path {
+                Some(path) => Mode::Record(path, Default::default()),
+                None => Mode::Live,
+            },
+        }
+    }
+
+    pub fn replay(exchanges: Vec<Exchange>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            mode: Mode::Replay(Arc::new(Mutex::new(exchanges.into()))),
+        }
+    }
+
+    pub fn load(path: &std::path::Path) -> anyhow::Result<Self> {
+        Ok(Self::replay(serde_json::from_slice(&std::fs::read(path)?)?))
+    }
+
+    // is_finished reports whether every recorded exchange has been replayed.
+    pub fn is_finished(&self) -> bool {
+        match &self.mode {
+            Mode::Replay(exchanges) => exchanges.lock().unwrap().is_empty(),
+            _ => true,
+        }
+    }
+
+    pub fn request(&self, method: reqwest::Method, url: reqwest::Url) -> reqwest::RequestBuilder {
+        self.client.request(method, url)
+    }
+
+    pub async fn send(&self, request: reqwest::RequestBuilder) -> Result<Response, ClientError> {
+        let request = request.build()?;
+        let recorded = Request {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            body: request
+                .body()
+                .and_then(|body| body.as_bytes())
+                .map(|body| String::from_utf8_lossy(body).to_string()),
+        };
+
+        if let Mode::Replay(exchanges) = &self.mode {
+            let exchange = exchanges.lock().unwrap().pop_front().ok_or_else(|| {
+                ClientError::UnknownError(format!(
+                    "No more exchanges were recorded, got {} {}",
+                    recorded.method, recorded.url
+                ))
+            })?;
+
+            if exchange.request.method != recorded.method
+                || exchange.request.path() != recorded.path()
+            {
+                return Err(ClientError::UnknownError(format!(
+                    "Expected {} {} to be replayed, got {} {}",
+                    exchange.request.method, exchange.request.url, recorded.method, recorded.url
+                )));
+            }
+
+            return Ok(exchange.response);
+        }
+
+        let resp = self.client.execute(request).await?;
+        let response = Response {
+            status: resp.status().as_u16(),
+            content_type: resp
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .map(|v| v.to_str().map(|s| s.to_string()))
+                .transpose()?,
+            body: resp.text().await?,
+        };
+
+        if let Mode::Record(path, exchanges) = &self.mode {
+            let mut exchanges = exchanges.lock().unwrap();
+            exchanges.push(Exchange {
+                request: recorded,
+                response: response.clone(),
+            });
+            std::fs::write(path, serde_json::to_vec_pretty(&*exchanges)?)
+                .map_err(|e| ClientError::UnknownError(e.to_string()))?;
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[tokio::test]
+    async fn test_record_replay() {
+        use super::{Exchange, Transport};
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = reqwest::Url::parse(&format!(
+            "http://{}/calendar/v3/calendars/primary/events/abc",
+            listener.local_addr().unwrap()
+        ))
+        .unwrap();
+
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 4096];
+            let _ = stream.read(&mut buf).unwrap();
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 12\r\n\r\n{\"id\":\"abc\"}",
+                )
+                .unwrap();
+        });
+
+        let f = tempfile::NamedTempFile::new().unwrap();
+        let transport = Transport::new(Some(f.path().to_path_buf()));
+        let response = transport
+            .send(
+                transport
+                    .request(reqwest::Method::GET, url.clone())
+                    .bearer_auth("secret"),
+            )
+            .await
+            .unwrap();
+        server.join().unwrap();
+        assert_eq!(response.body, r#"{"id":"abc"}"#);
+
+        let recording = std::fs::read_to_string(f.path()).unwrap();
+        assert!(!recording.contains("secret"));
+        let exchanges: Vec<Exchange> = serde_json::from_str(&recording).unwrap();
+        assert_eq!(exchanges.len(), 1);
+        assert_eq!(exchanges[0].request.method, "GET");
+        assert_eq!(exchanges[0].response.status, 200);
+
+        // the recording is served without the server
+        let replay = Transport::load(f.path()).unwrap();
+        let replayed = replay
+            .send(replay.request(reqwest::Method::GET, url.clone()))
+            .await
+            .unwrap();
+        assert_eq!(replayed, response);
+        assert!(replay.is_finished());
+
+        assert!(replay
+            .send(replay.request(reqwest::Method::DELETE, url))
+            .await
+            .is_err());
+    }
+}
@@ -1,17 +1,26 @@
 use crate::{
-    db::{unixfile::UnixFileLoader, RemoteClient, DB},
+    db::{
+        cache::RecordCache,
+        events_now,
+        google::reminder_minutes,
+        queue::{PendingWrite, WriteQueue},
+        unixfile::UnixFileLoader,
+        EditConflict, GcReport, RemoteClient, DB,
+    },
     filenames::saturn_db,
     record::{Record, RecurringRecord},
+    time::now,
 };
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 #[derive(Debug, Clone)]
 pub struct RemoteDBClient<T: RemoteClient + Send + Sync + Default + std::fmt::Debug> {
     client: T,
     db: RemoteDB,
+    cache_ttl: chrono::Duration,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -23,16 +32,247 @@ pub struct RemoteDB {
     recurring_id_map: BTreeMap<String, u64>,
     reverse_recurring_id_map: BTreeMap<u64, String>,
     fields: BTreeMap<u64, crate::record::Fields>,
+    // notifications which Google's reminders cannot hold, those anchored to a part of the record or
+    // at a time of day, are kept here like the fields are
+    #[serde(default)]
+    notifications: BTreeMap<u64, crate::record::Notifications>,
     calendar_id: String,
+    #[serde(default)]
+    cache: RecordCache,
+    #[serde(default)]
+    queue: WriteQueue,
+    #[serde(default)]
+    sync_token: Option<String>,
+    #[serde(default)]
+    synced_on: Option<chrono::NaiveDate>,
 }
 
 impl<T: RemoteClient + Send + Sync + Default + std::fmt::Debug> RemoteDBClient<T> {
-    pub fn new(calendar_id: String, client: T) -> Self {
+    pub fn new(calendar_id: String, cache_ttl: chrono::Duration, client: T) -> Self {
         let db = RemoteDB::new(calendar_id);
 
         // assuming this call convention is honored, client will always be "some" when actually
         // used, and will only be empty when deserialized.
-        Self { client, db }
+        Self {
+            client,
+            db,
+            cache_ttl,
+        }
+    }
+
+    // refresh syncs the listing window when the cache has gone stale. If the remote side cannot be
+    // reached, the stale cache is used instead.
+    async fn refresh(&mut self) -> Result<()> {
+        if self.db.cache.is_fresh(self.cache_ttl) {
+            return Ok(());
+        }
+
+        let calendar_id = self.db.calendar_id.clone();
+        let today = now().date_naive();
+
+        // the window moves along with the current date, so a full sync is made once a day to drop
+        // the records which have fallen out of it.
+        let token = if self.db.synced_on == Some(today) && self.db.cache.is_populated() {
+            self.db.sync_token.clone()
+        } else {
+            None
+        };
+
+        match self.client.sync(calendar_id, token).await {
+            Ok(result) => {
+                let records = self.db.record_updates(result.records).await?;
+
+                if result.full {
+                    self.db.cache.replace(records);
+                    self.db.synced_on = Some(today);
+                } else {
+                    let mut removed = Vec::new();
+                    for id in result.removed {
+                        if let Some(recurrence_key) = self.db.recurring_lookup_internal(id.clone())
+                        {
+                            self.db.cache.remove_recurrence(recurrence_key);
+                        }
+
+                        if let Some(primary_key) = self.db.lookup_internal(id) {
+                            removed.push(primary_key);
+                        }
+                    }
+
+                    self.db.cache.merge(records, removed);
+                }
+
+                self.db.sync_token = result.token;
+                self.db.queue.apply(&mut self.db.cache);
+                Ok(())
+            }
+            Err(e) if self.client.is_offline(&e) && self.db.cache.is_populated() => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn refresh_recurring(&mut self) -> Result<()> {
+        if self.db.cache.is_recurring_fresh(self.cache_ttl) {
+            return Ok(());
+        }
+
+        let calendar_id = self.db.calendar_id.clone();
+
+        match self.client.list_recurrence(calendar_id).await {
+            Ok(records) => {
+                let records = self.db.record_recurring_updates(records).await?;
+                self.db.cache.replace_recurring(records);
+                Ok(())
+            }
+            Err(e) if self.client.is_offline(&e) && self.db.cache.is_recurring_populated() => {
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    // write delivers a write to the remote calendar, or queues it if the calendar cannot be
+    // reached. Writes are also queued while others are still waiting, so they arrive in order.
+    // Returns true if the write was delivered.
+    async fn write(&mut self, write: PendingWrite) -> Result<bool> {
+        if !self.db.queue.is_empty() {
+            self.db.queue.push(write);
+            return Ok(false);
+        }
+
+        match self.deliver(write.clone()).await {
+            Ok(()) => Ok(true),
+            Err(e) if self.client.is_offline(&e) => {
+                self.db.queue.push(write);
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn deliver(&mut self, write: PendingWrite) -> Result<()> {
+        let calendar_id = self.db.calendar_id.clone();
+
+        match write {
+            PendingWrite::Insert(mut record) => {
+                let internal_key = self.client.record(calendar_id, record.clone()).await?;
+                self.db.add(internal_key.clone(), record.primary_key());
+                record.set_internal_key(Some(internal_key));
+                self.db.cache.insert(record);
+            }
+            PendingWrite::Update(mut record) => {
+                match self.client.update(calendar_id, record.clone()).await {
+                    Ok(etag) => {
+                        record.set_etag(etag);
+                        self.db.cache.insert(record);
+                    }
+                    Err(e) => match e.downcast::<EditConflict<Record>>() {
+                        Ok(EditConflict(mut remote)) => {
+                            remote.set_primary_key(record.primary_key());
+                            remote.set_recurrence_key(record.recurrence_key());
+                            remote.set_fields(record.fields());
+                            self.db.restore_notifications(&mut remote);
+                            self.db.cache.refresh(remote.clone());
+                            return Err(EditConflict(remote).into());
+                        }
+                        Err(e) => return Err(e),
+                    },
+                }
+            }
+            PendingWrite::Delete {
+                primary_key,
+                internal_key,
+            } => {
+                self.client.delete(calendar_id, internal_key).await?;
+                self.db.delete(primary_key).await?;
+            }
+            PendingWrite::Complete(primary_key) => {
+                self.client.complete_task(calendar_id, primary_key).await?
+            }
+            PendingWrite::InsertRecurrence(record) => {
+                let (_, recurrence_key) = self
+                    .client
+                    .record_recurrence(calendar_id, record.clone())
+                    .await?;
+
+                self.db
+                    .add_recurring(recurrence_key, record.recurrence_key());
+                // the instances are created on the remote side, so the listings must be fetched
+                // again.
+                self.db.cache.expire();
+            }
+            PendingWrite::UpdateRecurring(mut record) => {
+                let recurrence_key = record.recurrence_key();
+                let etag = match self
+                    .client
+                    .update_recurring(calendar_id, record.clone())
+                    .await
+                {
+                    Ok(etag) => etag,
+                    Err(e) => match e.downcast::<EditConflict<RecurringRecord>>() {
+                        Ok(EditConflict(mut remote)) => {
+                            remote.set_recurrence_key(recurrence_key);
+                            remote
+                                .record()
+                                .set_primary_key(record.record().primary_key());
+                            return Err(EditConflict(remote).into());
+                        }
+                        Err(e) => return Err(e),
+                    },
+                };
+                record.record().set_etag(etag);
+                self.db.cache.remove_recurrence(recurrence_key);
+                self.db.cache.insert(record.record().clone());
+                self.db.cache.expire();
+            }
+            PendingWrite::DeleteRecurrence {
+                recurrence_key,
+                internal_key,
+            } => {
+                // deleting the recurring event removes its instances along with it; only the
+                // local copies of them are left to clean up.
+                let list = self
+                    .client
+                    .delete_recurrence(calendar_id.clone(), internal_key)
+                    .await?;
+
+                for item in list {
+                    if let Some(id) = self.db.lookup_internal(item) {
+                        self.db.delete(id).await?;
+                        self.db.cache.remove(id);
+                    }
+                }
+
+                self.db.delete_recurrence(recurrence_key).await?;
+                self.db.cache.remove_recurrence(recurrence_key);
+            }
+        }
+
+        Ok(())
+    }
+
+    // replay sends any writes which were queued while offline. Writes the remote side refuses are
+    // kept as conflicts, to be reported by `saturn sync status`.
+    async fn replay(&mut self) -> Result<()> {
+        if self.db.queue.is_empty() {
+            return Ok(());
+        }
+
+        let mut writes = self.db.queue.take().into_iter();
+        while let Some(write) = writes.next() {
+            if let Err(e) = self.deliver(write.clone()).await {
+                if self.client.is_offline(&e) {
+                    let mut rest = vec![write];
+                    rest.extend(writes);
+                    self.db.queue.requeue(rest);
+                    return Ok(());
+                }
+
+                self.db.queue.conflict(write, e.to_string());
+            }
+        }
+
+        self.db.cache.expire();
+        Ok(())
     }
 }
 
@@ -46,7 +286,12 @@ impl RemoteDB {
             recurring_id_map: BTreeMap::default(),
             reverse_recurring_id_map: BTreeMap::default(),
             fields: BTreeMap::default(),
+            notifications: BTreeMap::default(),
             calendar_id,
+            cache: RecordCache::default(),
+            queue: WriteQueue::default(),
+            sync_token: None,
+            synced_on: None,
         }
     }
 
@@ -115,6 +360,113 @@ impl RemoteDB {
 }
 
 impl RemoteDB {
+    // gc drops the mappings of the events in `gone`, along with their fields and notifications,
+    // and optionally renumbers the primary keys that are left into a dense range, in their
+    // original order.
+    fn gc(
+        &mut self,
+        gone: &BTreeSet<String>,
+        gone_recurring: &BTreeSet<String>,
+        renumber: bool,
+    ) -> GcReport {
+        let mut report = GcReport::default();
+
+        for (id, primary_key) in self.id_map.clone() {
+            if gone.contains(&id) {
+                self.remove_by_public_id(id);
+                report.removed.push(primary_key);
+            }
+        }
+
+        for (id, recurrence_key) in self.recurring_id_map.clone() {
+            if gone_recurring.contains(&id) {
+                self.remove_recurring_by_public_id(id);
+                report.removed_recurring.push(recurrence_key);
+            }
+        }
+
+        report.removed.sort();
+        report.removed_recurring.sort();
+
+        let reverse_id_map = &self.reverse_id_map;
+        self.fields.retain(|pk, _| reverse_id_map.contains_key(pk));
+        self.notifications
+            .retain(|pk, _| reverse_id_map.contains_key(pk));
+
+        if renumber {
+            for (new, old) in self.reverse_id_map.keys().enumerate() {
+                let new = new as u64 + 1;
+                if new != *old {
+                    report.renumbered.insert(*old, new);
+                }
+            }
+
+            let renumbered = |pk: u64| report.renumbered.get(&pk).cloned().unwrap_or(pk);
+
+            self.id_map = self
+                .id_map
+                .iter()
+                .map(|(id, pk)| (id.clone(), renumbered(*pk)))
+                .collect();
+            self.reverse_id_map = self
+                .id_map
+                .iter()
+                .map(|(id, pk)| (*pk, id.clone()))
+                .collect();
+            self.fields = std::mem::take(&mut self.fields)
+                .into_iter()
+                .map(|(pk, fields)| (renumbered(pk), fields))
+                .collect();
+            self.notifications = std::mem::take(&mut self.notifications)
+                .into_iter()
+                .map(|(pk, notifications)| (renumbered(pk), notifications))
+                .collect();
+            self.primary_key = self.reverse_id_map.len() as u64;
+        }
+
+        report
+    }
+
+    // keep_notifications keeps the record's notifications which Google cannot hold.
+    fn keep_notifications(&mut self, record: &Record) {
+        let kept = record
+            .notifications()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|n| n.anchor().is_some() || n.time().is_some())
+            .collect::<crate::record::Notifications>();
+
+        if kept.is_empty() {
+            self.notifications.remove(&record.primary_key());
+        } else {
+            self.notifications.insert(record.primary_key(), kept);
+        }
+    }
+
+    // restore_notifications puts the kept notifications back in place of the plain reminders which
+    // were sent to Google for them.
+    fn restore_notifications(&self, record: &mut Record) {
+        let Some(kept) = self.notifications.get(&record.primary_key()) else {
+            return;
+        };
+
+        let mut notifications = record.notifications().unwrap_or_default();
+        for notification in kept {
+            let minutes = reminder_minutes(record, notification);
+            if let Some(pos) = notifications.iter().position(|n| {
+                n == notification
+                    || (n.anchor().is_none()
+                        && n.time().is_none()
+                        && n.duration().num_minutes().try_into().ok() == minutes)
+            }) {
+                notifications.remove(pos);
+            }
+            notifications.push(notification.clone());
+        }
+
+        record.set_notifications(Some(notifications));
+    }
+
     fn record_internal(&mut self, internal_key: String, pk: Option<u64>) -> Result<u64> {
         let pk = if let Some(pk) = pk {
             pk
@@ -130,9 +482,16 @@ impl RemoteDB {
         for record in &mut records {
             if let Some(internal_recurrence_key) = record.internal_recurrence_key() {
                 if record.recurrence_key().is_none() {
-                    let key = self.next_recurrence_key();
+                    let key = if let Some(key) =
+                        self.recurring_lookup_internal(internal_recurrence_key.clone())
+                    {
+                        key
+                    } else {
+                        let key = self.next_recurrence_key();
+                        self.add_recurring(internal_recurrence_key, key);
+                        key
+                    };
                     record.set_recurrence_key(Some(key));
-                    self.add_recurring(internal_recurrence_key, key);
                 }
             }
 
@@ -148,6 +507,7 @@ impl RemoteDB {
             if let Some(fields) = self.fields.get(&record.primary_key()) {
                 record.set_fields(fields.clone());
             }
+            self.restore_notifications(record);
         }
 
         Ok(records)
@@ -192,10 +552,9 @@ impl RemoteDB {
     }
 }
 
-#[async_trait]
-impl DB for RemoteDB {
-    async fn load(&mut self) -> Result<()> {
-        let db: Self = UnixFileLoader::new(&saturn_db()).load().await?;
+impl RemoteDB {
+    async fn load_from(&mut self, path: &std::path::PathBuf) -> Result<()> {
+        let db: Self = UnixFileLoader::new(path).load().await?;
         self.primary_key = db.primary_key;
         self.recurrence_key = db.recurrence_key;
         self.id_map = db.id_map;
@@ -203,11 +562,27 @@ impl DB for RemoteDB {
         self.recurring_id_map = db.recurring_id_map;
         self.reverse_recurring_id_map = db.reverse_recurring_id_map;
         self.fields = db.fields;
+        self.notifications = db.notifications;
+        self.cache = db.cache;
+        self.queue = db.queue;
+        self.sync_token = db.sync_token;
+        self.synced_on = db.synced_on;
         self.update_recurrence().await
     }
 
+    async fn dump_to(&self, path: &std::path::PathBuf) -> Result<()> {
+        UnixFileLoader::new(path).dump(self.clone()).await
+    }
+}
+
+#[async_trait]
+impl DB for RemoteDB {
+    async fn load(&mut self) -> Result<()> {
+        self.load_from(&saturn_db()).await
+    }
+
     async fn dump(&self) -> Result<()> {
-        UnixFileLoader::new(&saturn_db()).dump(self.clone()).await
+        self.dump_to(&saturn_db()).await
     }
 
     fn primary_key(&self) -> u64 {
@@ -228,13 +603,21 @@ impl DB for RemoteDB {
 
     async fn delete(&mut self, primary_key: u64) -> Result<()> {
         self.remove_by_internal_id(primary_key);
+        self.fields.remove(&primary_key);
+        self.notifications.remove(&primary_key);
         Ok(())
     }
 
     async fn delete_recurrence(&mut self, recurrence_key: u64) -> Result<Vec<String>> {
-        self.remove_by_internal_id(recurrence_key);
+        // the recurring event itself is mapped to a primary key as well.
+        if let Some(primary_key) = self
+            .recurring_lookup(recurrence_key)
+            .and_then(|id| self.lookup_internal(id))
+        {
+            self.delete(primary_key).await?;
+        }
+
         self.remove_recurring_by_internal_id(recurrence_key);
-        // FIXME leaves a garbage record in the PK table
         Ok(Vec::new())
     }
 
@@ -302,7 +685,8 @@ impl DB for RemoteDB {
 #[async_trait]
 impl<T: RemoteClient + Send + Sync + Default + std::fmt::Debug> DB for RemoteDBClient<T> {
     async fn load(&mut self) -> Result<()> {
-        self.db.load().await
+        self.db.load().await?;
+        self.replay().await
     }
 
     async fn dump(&self) -> Result<()> {
@@ -325,45 +709,109 @@ impl<T: RemoteClient + Send + Sync + Default + std::fmt::Debug> DB for RemoteDBC
         self.db.set_recurrence_key(recurrence_key);
     }
 
-    async fn delete(&mut self, primary_key: u64) -> Result<()> {
-        let id = self
-            .db
-            .lookup(primary_key)
-            .map_or_else(|| Err(anyhow!("Invalid ID")), |k| Ok(k))?;
-
-        let calendar_id = self.db.calendar_id.clone();
+    fn write_queue(&self) -> Option<WriteQueue> {
+        Some(self.db.queue.clone())
+    }
 
-        self.client.delete(calendar_id, id).await?;
-        self.db.delete(primary_key).await?;
-        Ok(())
+    fn clear_conflicts(&mut self) {
+        self.db.queue.clear_conflicts()
     }
 
-    async fn delete_recurrence(&mut self, recurrence_key: u64) -> Result<Vec<String>> {
-        let id = self
-            .db
-            .recurring_lookup(recurrence_key)
-            .map_or_else(|| Err(anyhow!("Invalid ID")), |k| Ok(k))?;
-        let calendar_id = self.db.calendar_id.clone();
+    // gc lists the calendar from scratch, and drops the mappings of the events which are no longer
+    // listed and which the remote side confirms were deleted. Events which have only left the
+    // query window are kept, along with their fields.
+    async fn gc(&mut self, renumber: bool) -> Result<Option<GcReport>> {
+        if !self.db.queue.is_empty() {
+            return Err(anyhow!(
+                "Writes are waiting to be sent, see `saturn sync status`. Try again once they have been."
+            ));
+        }
 
-        let list = self
-            .client
-            .delete_recurrence(calendar_id.clone(), id.clone())
-            .await?;
-        for item in list.iter() {
-            if let Some(id) = self.db.lookup_internal(item.clone()) {
-                let res = self.delete(id).await;
-                if matches!(res, Result::Err(_)) {
-                    break;
-                }
+        let calendar_id = self.db.calendar_id.clone();
+        let result = self.client.sync(calendar_id.clone(), None).await?;
+        let recurring = self.client.list_recurrence(calendar_id.clone()).await?;
+
+        let live = result
+            .records
+            .iter()
+            .filter_map(|r| r.internal_key())
+            .collect::<BTreeSet<String>>();
+        let live_recurring = recurring
+            .iter()
+            .filter_map(|r| r.internal_key())
+            .chain(
+                result
+                    .records
+                    .iter()
+                    .filter_map(|r| r.internal_recurrence_key()),
+            )
+            .collect::<BTreeSet<String>>();
+
+        let mut gone = BTreeSet::new();
+        for id in self.db.id_map.keys() {
+            if !live.contains(id)
+                && !live_recurring.contains(id)
+                && self.client.is_gone(calendar_id.clone(), id.clone()).await?
+            {
+                gone.insert(id.clone());
             }
         }
 
-        self.db.delete_recurrence(recurrence_key).await?;
-        if let Some(id) = self.db.lookup_internal(id) {
-            self.db.delete(id).await?;
+        let mut gone_recurring = BTreeSet::new();
+        for id in self.db.recurring_id_map.keys() {
+            if !live_recurring.contains(id)
+                && self.client.is_gone(calendar_id.clone(), id.clone()).await?
+            {
+                gone_recurring.insert(id.clone());
+            }
         }
-        // FIXME leaves a garbage record in the PK table
-        Ok(list)
+
+        let report = self.db.gc(&gone, &gone_recurring, renumber);
+
+        let records = self.db.record_updates(result.records).await?;
+        self.db.cache.replace(records);
+        let recurring = self.db.record_recurring_updates(recurring).await?;
+        self.db.cache.replace_recurring(recurring);
+        self.db.sync_token = result.token;
+        self.db.synced_on = Some(now().date_naive());
+
+        Ok(Some(report))
+    }
+
+    async fn delete(&mut self, primary_key: u64) -> Result<()> {
+        let internal_key = if self.db.queue.is_provisional(primary_key) {
+            String::new()
+        } else {
+            self.db.lookup(primary_key).ok_or(anyhow!("Invalid ID"))?
+        };
+
+        self.write(PendingWrite::Delete {
+            primary_key,
+            internal_key,
+        })
+        .await?;
+        self.db.cache.remove(primary_key);
+        Ok(())
+    }
+
+    // delete_recurrence removes the instances along with the recurring record once the delete is
+    // delivered, as only the remote side knows which instances there are.
+    async fn delete_recurrence(&mut self, recurrence_key: u64) -> Result<Vec<String>> {
+        let internal_key = if self.db.queue.is_provisional_recurrence(recurrence_key) {
+            String::new()
+        } else {
+            self.db
+                .recurring_lookup(recurrence_key)
+                .ok_or(anyhow!("Invalid ID"))?
+        };
+
+        self.write(PendingWrite::DeleteRecurrence {
+            recurrence_key,
+            internal_key,
+        })
+        .await?;
+        self.db.cache.remove_recurrence(recurrence_key);
+        Ok(Vec::new())
     }
 
     async fn record(&mut self, record: Record) -> Result<()> {
@@ -383,49 +831,38 @@ impl<T: RemoteClient + Send + Sync + Default + std::fmt::Debug> DB for RemoteDBC
     }
 
     async fn insert_record(&mut self, record: Record) -> Result<()> {
-        let key = record.primary_key();
-        let calendar_id = self.db.calendar_id.clone();
+        self.db.fields.insert(record.primary_key(), record.fields());
+        self.db.keep_notifications(&record);
 
-        let internal_key = self.client.record(calendar_id, record.clone()).await?;
+        if !self.write(PendingWrite::Insert(record.clone())).await? {
+            self.db.cache.insert(record);
+        }
 
-        self.db.add(internal_key, key);
-        self.db.fields.insert(key, record.fields());
         Ok(())
     }
 
     async fn insert_recurrence(&mut self, mut record: RecurringRecord) -> Result<()> {
-        let calendar_id = self.db.calendar_id.clone();
-
-        let (key, recurrence_key) = self
-            .client
-            .record_recurrence(calendar_id, record.clone())
-            .await?;
-
-        record.set_internal_key(Some(key.clone()));
-        record
-            .record()
-            .set_internal_recurrence_key(Some(key.clone()));
-        record.record().set_internal_key(Some(key.clone()));
         record.record().set_primary_key(self.next_key());
 
         if record.recurrence_key() == 0 {
-            record.set_recurrence_key(self.next_recurrence_key());
-            record
-                .record()
-                .set_recurrence_key(Some(self.recurrence_key()));
+            let recurrence_key = self.next_recurrence_key();
+            record.set_recurrence_key(recurrence_key);
+        }
+
+        if !self
+            .write(PendingWrite::InsertRecurrence(record.clone()))
+            .await?
+        {
+            self.db.cache.insert(record.record().clone());
+            self.db.cache.insert_recurring(record);
         }
 
-        self.db
-            .add_recurring(recurrence_key, record.recurrence_key());
         Ok(())
     }
 
     async fn list_recurrence(&mut self) -> Result<Vec<RecurringRecord>> {
-        let calendar_id = self.db.calendar_id.clone();
-
-        self.db
-            .record_recurring_updates(self.client.list_recurrence(calendar_id).await?)
-            .await
+        self.refresh_recurring().await?;
+        Ok(self.db.cache.recurring())
     }
 
     async fn update_recurrence(&mut self) -> Result<()> {
@@ -435,23 +872,48 @@ impl<T: RemoteClient + Send + Sync + Default + std::fmt::Debug> DB for RemoteDBC
     }
 
     async fn list_today(&mut self, include_completed: bool) -> Result<Vec<Record>> {
-        let calendar_id = self.db.calendar_id.clone();
+        self.refresh().await?;
+        let today = now().date_naive();
 
-        self.db
-            .record_updates(
-                self.client
-                    .list_today(calendar_id, include_completed)
-                    .await?,
-            )
-            .await
+        Ok(self
+            .db
+            .cache
+            .records(include_completed)
+            .into_iter()
+            .filter(|r| r.date() == today)
+            .collect())
     }
 
     async fn list_all(&mut self, include_completed: bool) -> Result<Vec<Record>> {
+        self.refresh().await?;
+        Ok(self.db.cache.records(include_completed))
+    }
+
+    // list_range asks the remote side for the range, as it may lie outside the cached window. When
+    // the remote side cannot be reached, the cache is used instead.
+    async fn list_range(
+        &mut self,
+        start: chrono::NaiveDate,
+        end: chrono::NaiveDate,
+        include_completed: bool,
+    ) -> Result<Vec<Record>> {
         let calendar_id = self.db.calendar_id.clone();
 
-        self.db
-            .record_updates(self.client.list_all(calendar_id, include_completed).await?)
+        match self
+            .client
+            .list_range(calendar_id, start, end, include_completed)
             .await
+        {
+            Ok(records) => self.db.record_updates(records).await,
+            Err(e) if self.client.is_offline(&e) && self.db.cache.is_populated() => Ok(self
+                .db
+                .cache
+                .records(include_completed)
+                .into_iter()
+                .filter(|r| r.in_range(start, end))
+                .collect()),
+            Err(e) => Err(e),
+        }
     }
 
     async fn events_now(
@@ -459,47 +921,69 @@ impl<T: RemoteClient + Send + Sync + Default + std::fmt::Debug> DB for RemoteDBC
         last: chrono::Duration,
         include_completed: bool,
     ) -> Result<Vec<Record>> {
-        let calendar_id = self.db.calendar_id.clone();
-
-        self.db
-            .record_updates(
-                self.client
-                    .events_now(calendar_id, last, include_completed)
-                    .await?,
-            )
-            .await
+        self.refresh().await?;
+        Ok(events_now(self.db.cache.records(include_completed), last))
     }
 
     async fn complete_task(&mut self, primary_key: u64) -> Result<()> {
-        let calendar_id = self.db.calendar_id.clone();
-
-        self.client.complete_task(calendar_id, primary_key).await
+        self.write(PendingWrite::Complete(primary_key)).await?;
+        self.db.cache.set_completed(primary_key);
+        Ok(())
     }
 
     async fn get(&mut self, primary_key: u64) -> Result<Record> {
+        if self.db.cache.is_fresh(self.cache_ttl) || self.db.queue.is_provisional(primary_key) {
+            if let Some(rec) = self.db.cache.get(primary_key) {
+                return Ok(rec);
+            }
+        }
+
         let calendar_id = self.db.calendar_id.clone();
         let event_id = self
             .db
             .lookup(primary_key)
             .ok_or(anyhow!("No Record Found"))?;
-        let mut rec = self.client.get(calendar_id, event_id).await?;
+        let mut rec = match self.client.get(calendar_id, event_id).await {
+            Ok(rec) => rec,
+            Err(e) if self.client.is_offline(&e) => {
+                return self.db.cache.get(primary_key).ok_or(e);
+            }
+            Err(e) => return Err(e),
+        };
         rec.set_primary_key(primary_key);
         if let Some(fields) = self.db.fields.get(&primary_key) {
             rec.set_fields(fields.clone());
         }
+        self.db.restore_notifications(&mut rec);
+        self.db.cache.refresh(rec.clone());
         Ok(rec)
     }
 
     async fn get_recurring(&mut self, recurrence_key: u64) -> Result<RecurringRecord> {
+        if self.db.cache.is_recurring_fresh(self.cache_ttl)
+            || self.db.queue.is_provisional_recurrence(recurrence_key)
+        {
+            if let Some(rec) = self.db.cache.get_recurring(recurrence_key) {
+                return Ok(rec);
+            }
+        }
+
         let calendar_id = self.db.calendar_id.clone();
         let event_id = self
             .db
             .recurring_lookup(recurrence_key)
             .ok_or(anyhow!("No Record Found"))?;
-        let mut rec = self
+        let mut rec = match self
             .client
             .get_recurring(calendar_id, event_id.clone())
-            .await?;
+            .await
+        {
+            Ok(rec) => rec,
+            Err(e) if self.client.is_offline(&e) => {
+                return self.db.cache.get_recurring(recurrence_key).ok_or(e);
+            }
+            Err(e) => return Err(e),
+        };
         let primary_key = self.db.lookup_internal(event_id).unwrap_or(0);
         rec.record().set_primary_key(primary_key);
         rec.record().set_recurrence_key(Some(recurrence_key));
@@ -508,13 +992,397 @@ impl<T: RemoteClient + Send + Sync + Default + std::fmt::Debug> DB for RemoteDBC
     }
 
     async fn update(&mut self, record: Record) -> Result<()> {
-        let calendar_id = self.db.calendar_id.clone();
         self.db.fields.insert(record.primary_key(), record.fields());
-        self.client.update(calendar_id, record).await
+        self.db.keep_notifications(&record);
+
+        if !self.write(PendingWrite::Update(record.clone())).await? {
+            self.db.cache.insert(record);
+        }
+
+        Ok(())
     }
 
-    async fn update_recurring(&mut self, record: RecurringRecord) -> Result<()> {
-        let calendar_id = self.db.calendar_id.clone();
-        self.client.update_recurring(calendar_id, record).await
+    async fn update_recurring(&mut self, mut record: RecurringRecord) -> Result<()> {
+        if !self
+            .write(PendingWrite::UpdateRecurring(record.clone()))
+            .await?
+        {
+            self.db.cache.insert(record.record().clone());
+            self.db.cache.insert_recurring(record);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        db::{fake::FakeClient, remote::RemoteDBClient, RemoteClient, DB},
+        record::Record,
+        time::now,
+    };
+
+    fn record(detail: &str, days: i64) -> Record {
+        let mut record = Record::default();
+        record.set_date(now().date_naive() + chrono::TimeDelta::try_days(days).unwrap_or_default());
+        record.set_at(Some(chrono::NaiveTime::from_hms_opt(8, 0, 0).unwrap()));
+        record.set_detail(detail.to_string());
+        record
+    }
+
+    #[tokio::test]
+    async fn test_id_mapping() {
+        let mut fake = FakeClient::new();
+        let mut db = RemoteDBClient::new(
+            "primary".to_string(),
+            chrono::Duration::zero(),
+            fake.clone(),
+        );
+
+        let mut local = record("local", 1);
+        local.set_primary_key(db.next_key());
+        db.record(local).await.unwrap();
+
+        let remote_id = fake
+            .record("primary".to_string(), record("remote", 2))
+            .await
+            .unwrap();
+
+        let list = db.list_all(false).await.unwrap();
+        assert_eq!(list.len(), 2);
+        let remote = list.iter().find(|r| r.detail() == "remote").unwrap();
+        assert_eq!(
+            list.iter()
+                .find(|r| r.detail() == "local")
+                .unwrap()
+                .primary_key(),
+            1
+        );
+        assert_eq!(remote.primary_key(), 2);
+        assert_eq!(db.next_key(), 3);
+
+        // changes made elsewhere arrive through an incremental sync and keep their IDs
+        fake.edit("primary", &remote_id, |r| {
+            r.set_detail("changed remotely".to_string());
+        });
+        let list = db.list_all(false).await.unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(db.get(2).await.unwrap().detail(), "changed remotely");
+
+        fake.delete("primary".to_string(), remote_id).await.unwrap();
+        let list = db.list_all(false).await.unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].primary_key(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_list_range() {
+        let mut fake = FakeClient::new();
+        let mut db = RemoteDBClient::new(
+            "primary".to_string(),
+            chrono::Duration::zero(),
+            fake.clone(),
+        );
+        let day = |n| now().date_naive() + chrono::TimeDelta::try_days(n).unwrap_or_default();
+
+        fake.record("primary".to_string(), record("soon", 2))
+            .await
+            .unwrap();
+        fake.record("primary".to_string(), record("later", 90))
+            .await
+            .unwrap();
+
+        // the range is listed even though it lies outside the query window
+        assert_eq!(db.list_all(false).await.unwrap().len(), 1);
+        let list = db.list_range(day(80), day(100), false).await.unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].detail(), "later");
+        assert_eq!(list[0].primary_key(), 2);
+
+        // offline, the cache is used instead
+        fake.set_offline(true);
+        let list = db.list_range(day(0), day(100), false).await.unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].detail(), "soon");
+    }
+
+    #[tokio::test]
+    async fn test_recurrence_keys() {
+        use crate::record::RecurringRecord;
+        use fancy_duration::FancyDuration;
+
+        let fake = FakeClient::new();
+        let mut db = RemoteDBClient::new(
+            "primary".to_string(),
+            chrono::Duration::zero(),
+            fake.clone(),
+        );
+
+        for detail in ["daily", "weekly"] {
+            let recurring = RecurringRecord::new(
+                record(detail, -2),
+                FancyDuration::new(
+                    chrono::TimeDelta::try_days(if detail == "daily" { 1 } else { 7 })
+                        .unwrap_or_default(),
+                ),
+            );
+            db.record_recurrence(recurring).await.unwrap();
+        }
+
+        let list = db.list_all(false).await.unwrap();
+        let daily = list
+            .iter()
+            .filter(|r| r.detail() == "daily")
+            .collect::<Vec<&Record>>();
+        let weekly = list
+            .iter()
+            .filter(|r| r.detail() == "weekly")
+            .collect::<Vec<&Record>>();
+        assert!(daily.len() > weekly.len());
+        assert!(daily.iter().all(|r| r.recurrence_key() == Some(1)));
+        assert!(weekly.iter().all(|r| r.recurrence_key() == Some(2)));
+
+        let mut keys = list.iter().map(|r| r.primary_key()).collect::<Vec<u64>>();
+        keys.dedup();
+        assert_eq!(keys.len(), list.len());
+
+        let recurring = db.list_recurrence().await.unwrap();
+        assert_eq!(recurring.len(), 2);
+        assert_eq!(
+            db.get_recurring(1).await.unwrap().record().detail(),
+            "daily"
+        );
+
+        // deleting the series takes its instances, and all of their IDs, along
+        let master = db.db.recurring_lookup(1).unwrap();
+        assert!(db.db.lookup_internal(master.clone()).is_some());
+        db.delete_recurrence(1).await.unwrap();
+        assert!(!db.db.id_map.keys().any(|id| id.starts_with(&master)));
+        assert!(db.db.recurring_lookup(1).is_none());
+        let list = db.list_all(false).await.unwrap();
+        assert!(list.iter().all(|r| r.detail() == "weekly"));
+        assert_eq!(fake.recurring("primary").len(), 1);
+        assert!(db.get_recurring(1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_offline_writes() {
+        let fake = FakeClient::new();
+        let mut db = RemoteDBClient::new(
+            "primary".to_string(),
+            chrono::Duration::zero(),
+            fake.clone(),
+        );
+        assert!(db.list_all(false).await.unwrap().is_empty());
+
+        fake.set_offline(true);
+        let mut offline = record("offline", 0);
+        offline.set_primary_key(db.next_key());
+        db.record(offline).await.unwrap();
+
+        assert_eq!(db.list_all(false).await.unwrap().len(), 1);
+        assert_eq!(db.write_queue().unwrap().pending().len(), 1);
+        assert!(fake.events("primary").is_empty());
+
+        fake.set_offline(false);
+        db.replay().await.unwrap();
+        assert!(db.write_queue().unwrap().is_empty());
+        assert_eq!(fake.events("primary").len(), 1);
+        assert_eq!(db.get(1).await.unwrap().detail(), "offline");
+
+        // refused writes become conflicts
+        fake.set_offline(true);
+        db.complete_task(1).await.unwrap();
+        fake.set_offline(false);
+        fake.fail_next(400);
+        db.replay().await.unwrap();
+        assert_eq!(db.write_queue().unwrap().conflicts().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_offline_recurrence() {
+        use crate::record::RecurringRecord;
+        use fancy_duration::FancyDuration;
+
+        let fake = FakeClient::new();
+        let mut db = RemoteDBClient::new(
+            "primary".to_string(),
+            chrono::Duration::zero(),
+            fake.clone(),
+        );
+        assert!(db.list_all(false).await.unwrap().is_empty());
+
+        fake.set_offline(true);
+        for detail in ["daily", "kept"] {
+            db.record_recurrence(RecurringRecord::new(
+                record(detail, 0),
+                FancyDuration::new(chrono::TimeDelta::try_days(1).unwrap_or_default()),
+            ))
+            .await
+            .unwrap();
+        }
+        assert_eq!(db.list_recurrence().await.unwrap().len(), 2);
+
+        let mut recurring = db.get_recurring(1).await.unwrap();
+        recurring.record().set_detail("standup".to_string());
+        db.update_recurring(recurring).await.unwrap();
+        db.delete_recurrence(2).await.unwrap();
+        assert_eq!(db.write_queue().unwrap().pending().len(), 1);
+        assert!(fake.recurring("primary").is_empty());
+
+        fake.set_offline(false);
+        db.replay().await.unwrap();
+        assert!(db.write_queue().unwrap().is_empty());
+        let recurring = fake.recurring("primary");
+        assert_eq!(recurring.len(), 1);
+        assert_eq!(recurring[0].clone().record().detail(), "standup");
+        assert!(db.db.recurring_lookup(1).is_some());
+
+        fake.set_offline(true);
+        db.delete_recurrence(1).await.unwrap();
+        fake.set_offline(false);
+        db.replay().await.unwrap();
+        assert!(fake.recurring("primary").is_empty());
+        assert!(db.db.recurring_lookup(1).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_edit_conflict() {
+        use crate::db::EditConflict;
+
+        let fake = FakeClient::new();
+        let mut db = RemoteDBClient::new(
+            "primary".to_string(),
+            chrono::Duration::zero(),
+            fake.clone(),
+        );
+
+        let mut r = record("original", 0);
+        r.set_primary_key(db.next_key());
+        db.record(r).await.unwrap();
+
+        let mut mine = db.get(1).await.unwrap();
+        fake.edit("primary", &mine.internal_key().unwrap(), |r| {
+            r.set_detail("theirs".to_string());
+        });
+
+        mine.set_detail("mine".to_string());
+        let err = db.update(mine).await.unwrap_err();
+        let conflict = err.downcast_ref::<EditConflict<Record>>().unwrap();
+        assert_eq!(conflict.0.detail(), "theirs");
+        assert_eq!(conflict.0.primary_key(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_kept_notifications() {
+        use crate::record::Notification;
+
+        let fake = FakeClient::new();
+        let mut db = RemoteDBClient::new(
+            "primary".to_string(),
+            chrono::Duration::zero(),
+            fake.clone(),
+        );
+
+        let notifications = ["5m", "1d before end at 9:00", "at 10:00"]
+            .iter()
+            .map(|n| n.parse::<Notification>().unwrap())
+            .collect::<Vec<_>>();
+
+        let mut r = record("deadline", 3);
+        r.set_primary_key(db.next_key());
+        r.set_notifications(Some(notifications.clone()));
+        db.record(r).await.unwrap();
+        assert_eq!(
+            db.get(1).await.unwrap().notifications(),
+            Some(notifications.clone())
+        );
+
+        // Google only gives back the reminders before the start, as plain durations
+        let key = db.get(1).await.unwrap().internal_key().unwrap();
+        fake.edit("primary", &key, |r| {
+            r.set_notifications(Some(vec!["5m".parse().unwrap(), "23h".parse().unwrap()]));
+        });
+        assert_eq!(
+            db.get(1).await.unwrap().notifications(),
+            Some(notifications)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_kept_notifications_dump() {
+        use crate::{db::remote::RemoteDB, record::Notification};
+
+        let fake = FakeClient::new();
+        let mut db = RemoteDBClient::new(
+            "primary".to_string(),
+            chrono::Duration::zero(),
+            fake.clone(),
+        );
+
+        let notifications = vec!["1d before end at 9:00".parse::<Notification>().unwrap()];
+        let mut r = record("deadline", 3);
+        r.set_primary_key(db.next_key());
+        r.set_notifications(Some(notifications.clone()));
+        db.record(r).await.unwrap();
+
+        let f = tempfile::NamedTempFile::new().unwrap();
+        db.db.dump_to(&f.path().to_path_buf()).await.unwrap();
+
+        let mut loaded = RemoteDB::new("primary".to_string());
+        loaded.load_from(&f.path().to_path_buf()).await.unwrap();
+        assert_eq!(loaded.notifications.get(&1), Some(&notifications));
+    }
+
+    #[tokio::test]
+    async fn test_gc() {
+        let mut fake = FakeClient::new();
+        let mut db = RemoteDBClient::new(
+            "primary".to_string(),
+            chrono::Duration::zero(),
+            fake.clone(),
+        );
+
+        // the last one is outside of the query window
+        for (detail, days) in [("one", 0), ("two", 0), ("three", 0), ("later", 90)] {
+            let mut r = record(detail, days);
+            r.set_primary_key(db.next_key());
+            r.add_field("name".to_string(), detail.to_string());
+            db.record(r).await.unwrap();
+        }
+
+        assert_eq!(
+            db.gc(false).await.unwrap().unwrap().removed,
+            Vec::<u64>::new()
+        );
+        assert_eq!(db.db.fields.len(), 4);
+
+        // the first two are deleted elsewhere
+        for primary_key in [1, 2] {
+            let id = db.db.lookup(primary_key).unwrap();
+            fake.delete("primary".to_string(), id).await.unwrap();
+        }
+
+        let report = db.gc(true).await.unwrap().unwrap();
+        assert_eq!(report.removed, vec![1, 2]);
+        assert_eq!(report.renumbered.get(&3), Some(&1));
+        assert_eq!(report.renumbered.get(&4), Some(&2));
+        assert_eq!(db.db.fields.len(), 2);
+        assert_eq!(db.next_key(), 3);
+        assert!(db.db.lookup(2).is_some());
+        assert_eq!(
+            db.db.fields.get(&2).and_then(|f| f.get("name").cloned()),
+            Some(vec!["later".to_string()])
+        );
+
+        let list = db.list_all(false).await.unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].primary_key(), 1);
+        assert_eq!(list[0].detail(), "three");
+        assert_eq!(
+            db.get(1).await.unwrap().get_field("name".to_string()),
+            Some(vec!["three".to_string()])
+        );
     }
 }
@@ -0,0 +1,783 @@
+use crate::{
+    db::{RemoteClient, DB},
+    record::{Record, RecordType, Schedule},
+    time::now,
+};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// ConflictPolicy decides which side wins when a record was changed on both sides since the last
+// sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    #[default]
+    PreferLocal,
+    PreferRemote,
+}
+
+impl std::str::FromStr for ConflictPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(Self::PreferLocal),
+            "remote" => Ok(Self::PreferRemote),
+            _ => Err(anyhow!(
+                "Invalid conflict policy {}, must be local or remote",
+                s
+            )),
+        }
+    }
+}
+
+// View is the part of a record which is kept in sync. Fields, completion and notifications are
+// local only; the remote calendar cannot represent them faithfully.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct View {
+    date: chrono::NaiveDate,
+    typ: RecordType,
+    at: Option<chrono::NaiveTime>,
+    scheduled: Option<Schedule>,
+    #[serde(default)]
+    end_date: Option<chrono::NaiveDate>,
+    detail: String,
+}
+
+impl From<&Record> for View {
+    fn from(record: &Record) -> Self {
+        Self {
+            date: record.date(),
+            typ: record.record_type(),
+            at: record.at(),
+            scheduled: record.scheduled(),
+            end_date: record.end_date(),
+            detail: record.detail(),
+        }
+    }
+}
+
+impl View {
+    fn apply(&self, record: &mut Record) {
+        record.set_date(self.date);
+        record.set_detail(self.detail.clone());
+
+        match self.typ {
+            RecordType::At => record.set_at(self.at),
+            RecordType::Schedule => record.set_scheduled(self.scheduled),
+            RecordType::AllDay => record.set_all_day().set_end_date(self.end_date),
+        };
+    }
+}
+
+// Link ties a local record to its remote copy, along with how both sides looked after the last
+// sync. The remote side is unknown until it has been listed once after a push, as the remote
+// calendar may represent the record slightly differently.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Link {
+    pub remote_id: String,
+    pub local: View,
+    pub remote: Option<View>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SyncState {
+    links: BTreeMap<u64, Link>,
+    last_sync: Option<chrono::DateTime<chrono::Local>>,
+}
+
+impl SyncState {
+    pub fn links(&self) -> &BTreeMap<u64, Link> {
+        &self.links
+    }
+
+    pub fn last_sync(&self) -> Option<chrono::DateTime<chrono::Local>> {
+        self.last_sync
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncAction {
+    PushInsert(Record),
+    PushUpdate(Record, String),
+    PushDelete(u64, String),
+    PullInsert(Record),
+    PullUpdate(u64, Record),
+    PullDelete(u64),
+    // Rebase records that both sides now agree on.
+    Rebase(u64, Record, Record),
+    Forget(u64),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SyncReport {
+    pub pushed: usize,
+    pub pulled: usize,
+    pub deleted_remote: usize,
+    pub deleted_local: usize,
+    pub conflicts: usize,
+    // errors are the changes which could not be made on either side. They are tried again on the
+    // next sync, as their links were left as they were.
+    pub errors: Vec<String>,
+}
+
+fn changed(current: Option<&Record>, base: Option<&View>) -> bool {
+    match (current, base) {
+        (Some(current), Some(base)) => &View::from(current) != base,
+        (None, Some(_)) => true,
+        (_, None) => false,
+    }
+}
+
+// reconcile compares the local and remote records against the state of the last sync, and
+// decides what must be done to bring both sides together. It does not perform any of it. Remote
+// records are keyed by their remote ID; links which are not in `links` are not considered at all.
+pub fn reconcile(
+    local: &BTreeMap<u64, Record>,
+    remote: &BTreeMap<String, Record>,
+    links: &BTreeMap<u64, Link>,
+    linked: &BTreeSet<String>,
+    policy: ConflictPolicy,
+) -> (Vec<SyncAction>, usize) {
+    let mut actions = Vec::new();
+    let mut conflicts = 0;
+
+    for (primary_key, link) in links {
+        let l = local.get(primary_key);
+        let r = remote.get(&link.remote_id);
+        let local_changed = changed(l, Some(&link.local));
+        let remote_changed = changed(r, link.remote.as_ref());
+
+        match (l, r) {
+            (Some(l), Some(r)) => {
+                if View::from(l) == View::from(r) {
+                    if local_changed || remote_changed || link.remote.is_none() {
+                        actions.push(SyncAction::Rebase(*primary_key, l.clone(), r.clone()));
+                    }
+                } else if local_changed && remote_changed {
+                    conflicts += 1;
+                    match policy {
+                        ConflictPolicy::PreferLocal => {
+                            actions.push(SyncAction::PushUpdate(l.clone(), link.remote_id.clone()))
+                        }
+                        ConflictPolicy::PreferRemote => {
+                            actions.push(SyncAction::PullUpdate(*primary_key, r.clone()))
+                        }
+                    }
+                } else if local_changed {
+                    actions.push(SyncAction::PushUpdate(l.clone(), link.remote_id.clone()));
+                } else if remote_changed {
+                    actions.push(SyncAction::PullUpdate(*primary_key, r.clone()));
+                } else if link.remote.is_none() {
+                    actions.push(SyncAction::Rebase(*primary_key, l.clone(), r.clone()));
+                }
+            }
+            (None, Some(r)) => {
+                if remote_changed && policy == ConflictPolicy::PreferRemote {
+                    conflicts += 1;
+                    actions.push(SyncAction::Forget(*primary_key));
+                    actions.push(SyncAction::PullInsert(r.clone()));
+                } else {
+                    actions.push(SyncAction::PushDelete(*primary_key, link.remote_id.clone()));
+                }
+            }
+            (Some(l), None) => {
+                if local_changed && policy == ConflictPolicy::PreferLocal {
+                    conflicts += 1;
+                    actions.push(SyncAction::Forget(*primary_key));
+                    actions.push(SyncAction::PushInsert(l.clone()));
+                } else {
+                    actions.push(SyncAction::PullDelete(*primary_key));
+                }
+            }
+            (None, None) => actions.push(SyncAction::Forget(*primary_key)),
+        }
+    }
+
+    for (primary_key, record) in local {
+        if !links.contains_key(primary_key) {
+            actions.push(SyncAction::PushInsert(record.clone()));
+        }
+    }
+
+    for (remote_id, record) in remote {
+        if !linked.contains(remote_id) {
+            actions.push(SyncAction::PullInsert(record.clone()));
+        }
+    }
+
+    (actions, conflicts)
+}
+
+// sync mirrors the local database to a remote calendar and back. Only the records within the
+// window are considered, since that is all the remote side lists. Changes which fail are reported
+// rather than stopping the sync, so the links of the ones which were made are kept. Recurring records are mirrored
+// as the occurrences materialized in the local database, each as an event of its own; the
+// recurrences themselves never leave it, and remote recurring events come back as single records.
+pub async fn sync<C: RemoteClient + Send>(
+    db: &mut (impl DB + ?Sized),
+    client: &mut C,
+    calendar_id: String,
+    window: (
+        chrono::DateTime<chrono::Local>,
+        chrono::DateTime<chrono::Local>,
+    ),
+    policy: ConflictPolicy,
+) -> Result<SyncReport> {
+    let mut state = db
+        .sync_state()
+        .ok_or(anyhow!("This database cannot be mirrored"))?;
+
+    let in_window =
+        |date: chrono::NaiveDate| date >= window.0.date_naive() && date <= window.1.date_naive();
+
+    let local = db
+        .list_all(true)
+        .await?
+        .into_iter()
+        .filter(|r| in_window(r.date()))
+        .map(|r| (r.primary_key(), r))
+        .collect::<BTreeMap<u64, Record>>();
+
+    let remote = client
+        .list_all(calendar_id.clone(), true)
+        .await?
+        .into_iter()
+        .filter_map(|r| r.internal_key().map(|k| (k, r)))
+        .collect::<BTreeMap<String, Record>>();
+
+    let links = state
+        .links
+        .iter()
+        .filter(|(pk, link)| local.contains_key(pk) || in_window(link.local.date))
+        .map(|(pk, link)| (*pk, link.clone()))
+        .collect::<BTreeMap<u64, Link>>();
+
+    let linked = state
+        .links
+        .values()
+        .map(|link| link.remote_id.clone())
+        .collect::<BTreeSet<String>>();
+
+    let (actions, conflicts) = reconcile(&local, &remote, &links, &linked, policy);
+    let mut report = SyncReport {
+        conflicts,
+        ..Default::default()
+    };
+
+    // new and deleted records are sent together, so they can be batched.
+    let mut inserts = Vec::new();
+    let mut deletes = Vec::new();
+
+    for action in actions {
+        let res: Result<()> = async {
+            match action {
+                SyncAction::PushInsert(record) => inserts.push(record),
+                SyncAction::PushUpdate(record, remote_id) => {
+                    let mut pushed = record.clone();
+                    pushed.set_internal_key(Some(remote_id.clone()));
+                    client
+                        .update(calendar_id.clone(), pushed)
+                        .await
+                        .map_err(|e| anyhow!("Could not update {}: {}", record.detail(), e))?;
+                    state.links.insert(
+                        record.primary_key(),
+                        Link {
+                            remote_id,
+                            local: View::from(&record),
+                            remote: None,
+                        },
+                    );
+                    report.pushed += 1;
+                }
+                SyncAction::PushDelete(primary_key, remote_id) => {
+                    deletes.push((primary_key, remote_id))
+                }
+                SyncAction::PullInsert(remote) => {
+                    let mut record = Record::default();
+                    View::from(&remote).apply(&mut record);
+                    record.set_notifications(remote.notifications());
+                    record.set_primary_key(db.next_key());
+                    db.record(record.clone()).await?;
+                    state.links.insert(
+                        record.primary_key(),
+                        Link {
+                            remote_id: remote.internal_key().unwrap_or_default(),
+                            local: View::from(&record),
+                            remote: Some(View::from(&remote)),
+                        },
+                    );
+                    report.pulled += 1;
+                }
+                SyncAction::PullUpdate(primary_key, remote) => {
+                    let mut record = db.get(primary_key).await?;
+                    View::from(&remote).apply(&mut record);
+                    db.update(record.clone()).await?;
+                    state.links.insert(
+                        primary_key,
+                        Link {
+                            remote_id: remote.internal_key().unwrap_or_default(),
+                            local: View::from(&record),
+                            remote: Some(View::from(&remote)),
+                        },
+                    );
+                    report.pulled += 1;
+                }
+                SyncAction::PullDelete(primary_key) => {
+                    db.delete(primary_key).await?;
+                    state.links.remove(&primary_key);
+                    report.deleted_local += 1;
+                }
+                SyncAction::Rebase(primary_key, local, remote) => {
+                    if let Some(link) = state.links.get_mut(&primary_key) {
+                        link.local = View::from(&local);
+                        link.remote = Some(View::from(&remote));
+                    }
+                }
+                SyncAction::Forget(primary_key) => {
+                    state.links.remove(&primary_key);
+                }
+            }
+
+            Ok(())
+        }
+        .await;
+
+        if let Err(e) = res {
+            report.errors.push(e.to_string());
+        }
+    }
+
+    if !deletes.is_empty() {
+        match client
+            .delete_many(
+                calendar_id.clone(),
+                deletes.iter().map(|(_, id)| id.clone()).collect(),
+            )
+            .await
+        {
+            Ok(()) => {
+                for (primary_key, _) in deletes {
+                    state.links.remove(&primary_key);
+                    report.deleted_remote += 1;
+                }
+            }
+            Err(e) => report.errors.push(e.to_string()),
+        }
+    }
+
+    if !inserts.is_empty() {
+        let pushed = inserts
+            .iter()
+            .cloned()
+            .map(|mut r| {
+                r.set_internal_key(None);
+                r
+            })
+            .collect();
+
+        match client.record_many(calendar_id.clone(), pushed).await {
+            Ok(ids) => {
+                for (record, remote_id) in inserts.iter().zip(ids) {
+                    match remote_id {
+                        Ok(remote_id) => {
+                            state.links.insert(
+                                record.primary_key(),
+                                Link {
+                                    remote_id,
+                                    local: View::from(record),
+                                    remote: None,
+                                },
+                            );
+                            report.pushed += 1;
+                        }
+                        Err(e) => {
+                            report
+                                .errors
+                                .push(format!("Could not send {}: {}", record.detail(), e))
+                        }
+                    }
+                }
+            }
+            Err(e) => report.errors.push(e.to_string()),
+        }
+    }
+
+    state.last_sync = Some(now());
+    db.set_sync_state(state);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reconcile, ConflictPolicy, Link, SyncAction, View};
+    use crate::record::Record;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    fn record(primary_key: u64, remote_id: Option<&str>, detail: &str) -> Record {
+        let mut record = Record::default();
+        record.set_primary_key(primary_key);
+        record.set_internal_key(remote_id.map(|s| s.to_string()));
+        record.set_detail(detail.to_string());
+        record
+    }
+
+    fn link(remote_id: &str, detail: &str) -> Link {
+        let view = View::from(&record(0, None, detail));
+        Link {
+            remote_id: remote_id.to_string(),
+            local: view.clone(),
+            remote: Some(view),
+        }
+    }
+
+    #[test]
+    fn test_reconcile_new_records() {
+        let local = BTreeMap::from([(1, record(1, None, "local"))]);
+        let remote = BTreeMap::from([("a".to_string(), record(0, Some("a"), "remote"))]);
+
+        let (actions, conflicts) = reconcile(
+            &local,
+            &remote,
+            &BTreeMap::new(),
+            &BTreeSet::new(),
+            ConflictPolicy::default(),
+        );
+
+        assert_eq!(conflicts, 0);
+        assert_eq!(
+            actions,
+            vec![
+                SyncAction::PushInsert(local[&1].clone()),
+                SyncAction::PullInsert(remote["a"].clone())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reconcile_changes() {
+        let local = BTreeMap::from([
+            (1, record(1, None, "unchanged")),
+            (2, record(2, None, "changed locally")),
+            (3, record(3, None, "base")),
+            (5, record(5, None, "base")),
+        ]);
+        let remote = BTreeMap::from([
+            ("a".to_string(), record(0, Some("a"), "unchanged")),
+            ("b".to_string(), record(0, Some("b"), "base")),
+            ("c".to_string(), record(0, Some("c"), "changed remotely")),
+            ("d".to_string(), record(0, Some("d"), "base")),
+        ]);
+        let links = BTreeMap::from([
+            (1, link("a", "unchanged")),
+            (2, link("b", "base")),
+            (3, link("c", "base")),
+            (4, link("d", "base")),
+            (5, link("e", "base")),
+        ]);
+        let linked = links
+            .values()
+            .map(|l| l.remote_id.clone())
+            .collect::<BTreeSet<String>>();
+
+        let (actions, conflicts) =
+            reconcile(&local, &remote, &links, &linked, ConflictPolicy::default());
+
+        assert_eq!(conflicts, 0);
+        assert_eq!(
+            actions,
+            vec![
+                SyncAction::PushUpdate(local[&2].clone(), "b".to_string()),
+                SyncAction::PullUpdate(3, remote["c"].clone()),
+                SyncAction::PushDelete(4, "d".to_string()),
+                SyncAction::PullDelete(5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reconcile_conflicts() {
+        let local = BTreeMap::from([(1, record(1, None, "local"))]);
+        let remote = BTreeMap::from([("a".to_string(), record(0, Some("a"), "remote"))]);
+        let links = BTreeMap::from([(1, link("a", "base"))]);
+        let linked = BTreeSet::from(["a".to_string()]);
+
+        let (actions, conflicts) = reconcile(
+            &local,
+            &remote,
+            &links,
+            &linked,
+            ConflictPolicy::PreferLocal,
+        );
+        assert_eq!(conflicts, 1);
+        assert_eq!(
+            actions,
+            vec![SyncAction::PushUpdate(local[&1].clone(), "a".to_string())]
+        );
+
+        let (actions, conflicts) = reconcile(
+            &local,
+            &remote,
+            &links,
+            &linked,
+            ConflictPolicy::PreferRemote,
+        );
+        assert_eq!(conflicts, 1);
+        assert_eq!(
+            actions,
+            vec![SyncAction::PullUpdate(1, remote["a"].clone())]
+        );
+
+        // a record deleted on one side and changed on the other
+        let (actions, conflicts) = reconcile(
+            &BTreeMap::new(),
+            &remote,
+            &links,
+            &linked,
+            ConflictPolicy::PreferRemote,
+        );
+        assert_eq!(conflicts, 1);
+        assert_eq!(
+            actions,
+            vec![
+                SyncAction::Forget(1),
+                SyncAction::PullInsert(remote["a"].clone())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sync() {
+        use super::sync;
+        use crate::db::{fake::FakeClient, memory::MemoryDB, RemoteClient, DB};
+
+        let today = crate::time::now().date_naive();
+        let at = |hour| {
+            let mut record = Record::build();
+            record
+                .set_date(today)
+                .set_at(Some(chrono::NaiveTime::from_hms_opt(hour, 0, 0).unwrap()));
+            record
+        };
+        let window = (
+            crate::time::now() - chrono::TimeDelta::try_days(1).unwrap(),
+            crate::time::now() + chrono::TimeDelta::try_days(1).unwrap(),
+        );
+        let details = |records: Vec<Record>| {
+            let mut details = records.iter().map(|r| r.detail()).collect::<Vec<String>>();
+            details.sort();
+            details
+        };
+
+        let mut db = MemoryDB::new();
+        let mut fake = FakeClient::new();
+        let mut client = fake.clone();
+
+        let mut local = at(9);
+        local
+            .set_primary_key(db.next_key())
+            .set_detail("local".to_string());
+        db.record(local.clone()).await.unwrap();
+        fake.record(
+            "primary".to_string(),
+            at(10).set_detail("remote".to_string()).clone(),
+        )
+        .await
+        .unwrap();
+
+        // new records are pushed and pulled
+        let report = sync(
+            &mut db,
+            &mut client,
+            "primary".into(),
+            window,
+            ConflictPolicy::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!((report.pushed, report.pulled), (1, 1));
+        assert_eq!(
+            details(db.list_all(false).await.unwrap()),
+            vec!["local", "remote"]
+        );
+        assert_eq!(details(fake.events("primary")), vec!["local", "remote"]);
+
+        // a second sync finds nothing to do
+        let report = sync(
+            &mut db,
+            &mut client,
+            "primary".into(),
+            window,
+            ConflictPolicy::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!((report.pushed, report.pulled, report.conflicts), (0, 0, 0));
+
+        // changes made on both sides are resolved by the policy
+        local.set_detail("changed locally".to_string());
+        db.update(local.clone()).await.unwrap();
+        let remote_id = db.sync_state().unwrap().links()[&local.primary_key()]
+            .remote_id
+            .clone();
+        fake.edit("primary", &remote_id, |r| {
+            r.set_detail("changed remotely".to_string());
+        });
+
+        let report = sync(
+            &mut db,
+            &mut client,
+            "primary".into(),
+            window,
+            ConflictPolicy::PreferRemote,
+        )
+        .await
+        .unwrap();
+        assert_eq!((report.conflicts, report.pulled), (1, 1));
+        assert_eq!(
+            db.get(local.primary_key()).await.unwrap().detail(),
+            "changed remotely"
+        );
+        assert_eq!(
+            details(fake.events("primary")),
+            vec!["changed remotely", "remote"]
+        );
+
+        // deletions are carried over in both directions
+        db.delete(local.primary_key()).await.unwrap();
+        let pulled = db
+            .list_all(false)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|r| r.detail() == "remote")
+            .unwrap();
+        let pulled_id = db.sync_state().unwrap().links()[&pulled.primary_key()]
+            .remote_id
+            .clone();
+        fake.delete("primary".to_string(), pulled_id).await.unwrap();
+
+        let report = sync(
+            &mut db,
+            &mut client,
+            "primary".into(),
+            window,
+            ConflictPolicy::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!((report.deleted_remote, report.deleted_local), (1, 1));
+        assert!(db.list_all(false).await.unwrap().is_empty());
+        assert!(fake.events("primary").is_empty());
+        assert!(db.sync_state().unwrap().links().is_empty());
+    }
+
+    #[test]
+    fn test_reconcile_after_push() {
+        // the remote representation is adopted the first time it is seen after a push.
+        let local = BTreeMap::from([(1, record(1, None, "base"))]);
+        let mut remote_record = record(0, Some("a"), "base");
+        remote_record.set_at(Some(chrono::NaiveTime::from_hms_opt(8, 0, 0).unwrap()));
+        let remote = BTreeMap::from([("a".to_string(), remote_record.clone())]);
+        let mut l = link("a", "base");
+        l.remote = None;
+        let links = BTreeMap::from([(1, l)]);
+        let linked = BTreeSet::from(["a".to_string()]);
+
+        let (actions, conflicts) =
+            reconcile(&local, &remote, &links, &linked, ConflictPolicy::default());
+        assert_eq!(conflicts, 0);
+        assert_eq!(
+            actions,
+            vec![SyncAction::Rebase(1, local[&1].clone(), remote_record)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sync_failure() {
+        use super::sync;
+        use crate::db::{fake::FakeClient, memory::MemoryDB, DB};
+
+        let window = (
+            crate::time::now() - chrono::TimeDelta::try_days(1).unwrap(),
+            crate::time::now() + chrono::TimeDelta::try_days(1).unwrap(),
+        );
+        let today = crate::time::now().date_naive();
+        let details = |records: Vec<Record>| {
+            let mut details = records.iter().map(|r| r.detail()).collect::<Vec<String>>();
+            details.sort();
+            details
+        };
+
+        let mut db = MemoryDB::new();
+        let fake = FakeClient::new();
+        let mut client = fake.clone();
+
+        let mut records = Vec::new();
+        for (hour, detail) in [(9, "first"), (10, "second"), (11, "third")] {
+            let mut record = Record::build();
+            record
+                .set_primary_key(db.next_key())
+                .set_date(today)
+                .set_at(Some(chrono::NaiveTime::from_hms_opt(hour, 0, 0).unwrap()))
+                .set_detail(detail.to_string());
+            records.push(record.clone());
+            if hour < 11 {
+                db.record(record).await.unwrap();
+            }
+        }
+
+        sync(
+            &mut db,
+            &mut client,
+            "primary".into(),
+            window,
+            ConflictPolicy::default(),
+        )
+        .await
+        .unwrap();
+
+        // both records are changed and a third is made; the listing goes through, the first
+        // update fails, and the rest is still sent
+        for record in &mut records[..2] {
+            record.set_detail(format!("{} changed", record.detail()));
+            db.update(record.clone()).await.unwrap();
+        }
+        db.record(records[2].clone()).await.unwrap();
+
+        fake.fail_after(1, 500);
+        let report = sync(
+            &mut db,
+            &mut client,
+            "primary".into(),
+            window,
+            ConflictPolicy::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.pushed, 2);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(db.sync_state().unwrap().links().len(), 3);
+        assert_eq!(
+            details(fake.events("primary")),
+            vec!["first", "second changed", "third"]
+        );
+
+        // only the failed update is sent again, and nothing is sent twice
+        let report = sync(
+            &mut db,
+            &mut client,
+            "primary".into(),
+            window,
+            ConflictPolicy::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.pushed, 1);
+        assert!(report.errors.is_empty());
+        assert_eq!(
+            details(fake.events("primary")),
+            vec!["first changed", "second changed", "third"]
+        );
+    }
+}
@@ -42,6 +42,11 @@ impl<'a> UnixFileLoader<'a> {
     where
         T: DB + Serialize + for<'de> Deserialize<'de>,
     {
+        // databases in the XDG data directory may not have one yet
+        if let Some(parent) = self.0.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
         unsafe {
             let fd = nix::libc::open(
                 std::ffi::CString::from_vec_unchecked(self.0.to_str().unwrap().as_bytes().to_vec())
@@ -1,21 +1,264 @@
-use std::{env::var, path::PathBuf};
+use anyhow::{anyhow, Result};
+use std::{env::var, path::PathBuf, sync::OnceLock};
 
 pub const CONFIG_FILENAME: &str = ".saturn.conf";
 pub const DB_FILENAME: &str = ".saturn.db";
+pub const SECRETS_FILENAME: &str = ".saturn.secrets";
+pub const XDG_CONFIG_FILENAME: &str = "config.yaml";
+pub const XDG_SECRETS_FILENAME: &str = "secrets.yaml";
+pub const XDG_DB_FILENAME: &str = "saturn.db";
+pub const NOTIFIED_FILENAME: &str = "notified.yaml";
+pub const DEFAULT_PROFILE: &str = "default";
+
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+pub fn valid_profile(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(anyhow!(
+            "Invalid profile name '{}': use letters, numbers, '-' and '_'",
+            name
+        ));
+    }
+
+    Ok(())
+}
+
+// set_profile selects the profile used for the rest of the process. SATURN_PROFILE is used when no
+// profile is given.
+pub fn set_profile(name: Option<String>) -> Result<()> {
+    if let Some(name) = name.or(var("SATURN_PROFILE").ok()) {
+        valid_profile(&name)?;
+        let _ = PROFILE.set(name);
+    }
+
+    Ok(())
+}
+
+// profile is the selected profile, if it is not the default one.
+pub fn profile() -> Option<String> {
+    PROFILE
+        .get()
+        .cloned()
+        .or(var("SATURN_PROFILE").ok())
+        .filter(|p| !p.is_empty() && p != DEFAULT_PROFILE)
+}
+
+// profile_filename keeps the files of a profile next to the default ones, e.g. `.saturn.work.conf`
+// for `.saturn.conf`.
+fn profile_filename(filename: &str, profile: Option<&str>) -> String {
+    match (profile, filename.rsplit_once('.')) {
+        (Some(profile), Some((stem, ext))) => format!("{}.{}.{}", stem, profile, ext),
+        (Some(profile), None) => format!("{}.{}", filename, profile),
+        (None, _) => filename.to_string(),
+    }
+}
+
+// profile_path is profile_filename for a file anywhere.
+fn profile_path(path: PathBuf, profile: Option<&str>) -> PathBuf {
+    match path.file_name() {
+        Some(name) => path.with_file_name(profile_filename(&name.to_string_lossy(), profile)),
+        None => path,
+    }
+}
+
+fn home_file(filename: &str, profile: Option<&str>) -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or("/".into())
+        .join(profile_filename(filename, profile))
+}
+
+// xdg_dir is the saturn directory under an XDG base directory, which falls back to its default
+// under the home directory when the variable is unset or not an absolute path.
+fn xdg_dir(name: &str, default: &str) -> PathBuf {
+    var(name)
+        .ok()
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .unwrap_or(dirs::home_dir().unwrap_or("/".into()).join(default))
+        .join("saturn")
+}
+
+fn xdg_config_file(filename: &str, profile: Option<&str>) -> PathBuf {
+    xdg_dir("XDG_CONFIG_HOME", ".config").join(profile_filename(filename, profile))
+}
+
+// xdg_or_legacy prefers the XDG file. The legacy dotfile is only used when it exists and the XDG
+// file does not, so existing setups keep working and new ones use the XDG directories.
+fn xdg_or_legacy(xdg: PathBuf, legacy: PathBuf) -> PathBuf {
+    if !xdg.exists() && legacy.exists() {
+        legacy
+    } else {
+        xdg
+    }
+}
+
+pub fn saturn_config_for(profile: Option<&str>) -> PathBuf {
+    let profile = profile.filter(|p| *p != DEFAULT_PROFILE);
+    xdg_or_legacy(
+        xdg_config_file(XDG_CONFIG_FILENAME, profile),
+        home_file(CONFIG_FILENAME, profile),
+    )
+}
 
 pub fn saturn_config() -> PathBuf {
-    dirs::home_dir().unwrap_or("/".into()).join(CONFIG_FILENAME)
+    saturn_config_for(profile().as_deref())
+}
+
+// saturn_secrets stays next to a legacy configuration file unless the XDG one already exists.
+pub fn saturn_secrets() -> PathBuf {
+    let profile = profile();
+    let xdg = xdg_config_file(XDG_SECRETS_FILENAME, profile.as_deref());
+    let legacy = home_file(SECRETS_FILENAME, profile.as_deref());
+
+    if !xdg.exists() && saturn_config() == home_file(CONFIG_FILENAME, profile.as_deref()) {
+        legacy
+    } else {
+        xdg_or_legacy(xdg, legacy)
+    }
+}
+
+// saturn_db_for is the database of a profile. SATURN_DB moves the database of the default profile;
+// the other profiles keep theirs next to it, e.g. `cal.work.db` for `cal.db`, so no two profiles
+// share a database.
+pub fn saturn_db_for(profile: Option<&str>) -> PathBuf {
+    let profile = profile.filter(|p| *p != DEFAULT_PROFILE);
+
+    match var("SATURN_DB") {
+        Ok(path) => profile_path(PathBuf::from(path), profile),
+        Err(_) => xdg_or_legacy(
+            xdg_dir("XDG_DATA_HOME", ".local/share")
+                .join(profile_filename(XDG_DB_FILENAME, profile)),
+            home_file(DB_FILENAME, profile),
+        ),
+    }
 }
 
 pub fn saturn_db() -> PathBuf {
-    var("SATURN_DB")
-        .unwrap_or(
-            dirs::home_dir()
-                .unwrap_or("/".into())
-                .join(DB_FILENAME)
-                .to_str()
-                .unwrap()
-                .to_string(),
-        )
-        .into()
+    saturn_db_for(profile().as_deref())
+}
+
+// saturn_notified is the state file which records the notifications that were sent.
+pub fn saturn_notified() -> PathBuf {
+    xdg_dir("XDG_STATE_HOME", ".local/state")
+        .join(profile_filename(NOTIFIED_FILENAME, profile().as_deref()))
+}
+
+// profiles_in finds the profiles with a configuration file in the directory, named after the
+// filename the way profile_filename does.
+fn profiles_in(dir: PathBuf, filename: &str) -> Result<Vec<String>> {
+    let (prefix, suffix) = filename.rsplit_once('.').unwrap();
+    let prefix = format!("{}.", prefix);
+    let suffix = format!(".{}", suffix);
+
+    let dir = match std::fs::read_dir(dir) {
+        Ok(dir) => dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut profiles = Vec::new();
+
+    for entry in dir {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        if let Some(profile) = name
+            .strip_prefix(&prefix)
+            .and_then(|n| n.strip_suffix(&suffix))
+        {
+            if valid_profile(profile).is_ok() && profile != DEFAULT_PROFILE {
+                profiles.push(profile.to_string());
+            }
+        }
+    }
+
+    Ok(profiles)
+}
+
+// saturn_profiles lists the profiles which have a configuration file in either location, always
+// including the default one.
+pub fn saturn_profiles() -> Result<Vec<String>> {
+    let mut profiles = profiles_in(xdg_dir("XDG_CONFIG_HOME", ".config"), XDG_CONFIG_FILENAME)?;
+    profiles.append(&mut profiles_in(
+        dirs::home_dir().unwrap_or("/".into()),
+        CONFIG_FILENAME,
+    )?);
+    profiles.sort();
+    profiles.dedup();
+
+    profiles.insert(0, DEFAULT_PROFILE.to_string());
+    Ok(profiles)
+}
+
+// saturn_recording is where exchanges with Google Calendar are written for replay in tests, if
+// anywhere.
+pub fn saturn_recording() -> Option<PathBuf> {
+    var("SATURN_RECORD").ok().map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_profile_filename() {
+        use super::{profile_filename, valid_profile, CONFIG_FILENAME, DB_FILENAME};
+
+        assert_eq!(profile_filename(CONFIG_FILENAME, None), ".saturn.conf");
+        assert_eq!(
+            profile_filename(CONFIG_FILENAME, Some("work")),
+            ".saturn.work.conf"
+        );
+        assert_eq!(
+            profile_filename(DB_FILENAME, Some("work")),
+            ".saturn.work.db"
+        );
+
+        assert!(valid_profile("work-2_b").is_ok());
+        assert!(valid_profile("").is_err());
+        assert!(valid_profile("../etc").is_err());
+    }
+
+    #[test]
+    fn test_profile_db() {
+        use super::{profile_path, saturn_db_for};
+        use std::path::PathBuf;
+
+        assert_ne!(saturn_db_for(None), saturn_db_for(Some("work")));
+        assert_ne!(saturn_db_for(Some("home")), saturn_db_for(Some("work")));
+        assert_eq!(saturn_db_for(Some("default")), saturn_db_for(None));
+
+        // SATURN_DB is moved along the same way
+        assert_eq!(
+            profile_path(PathBuf::from("/tmp/cal.db"), Some("work")),
+            PathBuf::from("/tmp/cal.work.db")
+        );
+        assert_eq!(
+            profile_path(PathBuf::from("/tmp/cal"), Some("work")),
+            PathBuf::from("/tmp/cal.work")
+        );
+        assert_eq!(
+            profile_path(PathBuf::from("/tmp/cal.db"), None),
+            PathBuf::from("/tmp/cal.db")
+        );
+    }
+
+    #[test]
+    fn test_xdg_or_legacy() {
+        use super::xdg_or_legacy;
+
+        let dir = tempfile::tempdir().unwrap();
+        let xdg = dir.path().join("saturn").join("config.yaml");
+        let legacy = dir.path().join(".saturn.conf");
+
+        // new setups use the XDG file
+        assert_eq!(xdg_or_legacy(xdg.clone(), legacy.clone()), xdg);
+
+        std::fs::write(&legacy, "").unwrap();
+        assert_eq!(xdg_or_legacy(xdg.clone(), legacy.clone()), legacy);
+
+        std::fs::create_dir_all(xdg.parent().unwrap()).unwrap();
+        std::fs::write(&xdg, "").unwrap();
+        assert_eq!(xdg_or_legacy(xdg.clone(), legacy), xdg);
+    }
 }
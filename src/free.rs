@@ -0,0 +1,179 @@
+use crate::record::{Record, RecordType};
+use serde::Serialize;
+
+// Period is a span of time, in local time.
+pub type Period = (chrono::NaiveDateTime, chrono::NaiveDateTime);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Slot {
+    pub start: chrono::NaiveDateTime,
+    pub end: chrono::NaiveDateTime,
+}
+
+impl Slot {
+    pub fn duration(&self) -> chrono::Duration {
+        self.end - self.start
+    }
+}
+
+// busy_period is the time taken up by the record. At records take up the default duration. All
+// day events do not count; they are mostly holidays, birthdays and reminders, which Google Calendar
+// does not count as busy either.
+pub fn busy_period(record: &Record, default_duration: chrono::Duration) -> Option<Period> {
+    match record.record_type() {
+        RecordType::AllDay => None,
+        RecordType::At => {
+            let start = record.date().and_time(record.at()?);
+            Some((start, start + default_duration))
+        }
+        RecordType::Schedule => {
+            let (start, end) = record.scheduled()?;
+            let start = record.date().and_time(start);
+            let mut end = record.date().and_time(end);
+            // schedules ending at or before their start run past midnight
+            if end <= start {
+                end += chrono::TimeDelta::try_days(1).unwrap_or_default();
+            }
+            Some((start, end))
+        }
+    }
+}
+
+pub fn busy_periods(records: &[Record], default_duration: chrono::Duration) -> Vec<Period> {
+    records
+        .iter()
+        .filter_map(|record| busy_period(record, default_duration))
+        .collect()
+}
+
+// free_slots finds the open time within working hours on each day of the range which is at least
+// as long as the duration. Nothing before `after` is considered free. Busy periods may come from
+// several calendars and overlap; time is only free if it is free in all of them.
+pub fn free_slots(
+    busy: &[Period],
+    range: (chrono::NaiveDate, chrono::NaiveDate),
+    hours: (chrono::NaiveTime, chrono::NaiveTime),
+    duration: chrono::Duration,
+    after: chrono::NaiveDateTime,
+) -> Vec<Slot> {
+    let mut busy = busy.to_vec();
+    busy.sort();
+
+    let mut slots = Vec::new();
+
+    for date in range.0.iter_days().take_while(|d| *d <= range.1) {
+        let day_end = date.and_time(hours.1);
+        let mut cursor = date.and_time(hours.0).max(after);
+
+        for (start, end) in &busy {
+            if *end <= cursor {
+                continue;
+            }
+
+            if *start >= day_end {
+                break;
+            }
+
+            if *start - cursor >= duration {
+                slots.push(Slot {
+                    start: cursor,
+                    end: *start,
+                });
+            }
+
+            cursor = cursor.max(*end);
+        }
+
+        if day_end - cursor >= duration {
+            slots.push(Slot {
+                start: cursor,
+                end: day_end,
+            });
+        }
+    }
+
+    slots
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_free_slots() {
+        use super::{busy_periods, free_slots, Slot};
+        use crate::record::Record;
+
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 11).unwrap();
+        let next = date.succ_opt().unwrap();
+        let time = |h, m| chrono::NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let minutes = |m| chrono::TimeDelta::try_minutes(m).unwrap_or_default();
+
+        let mut standup = Record::build();
+        standup.set_date(date).set_at(Some(time(9, 30)));
+
+        let mut review = Record::build();
+        review
+            .set_date(date)
+            .set_scheduled(Some((time(13, 0), time(14, 30))));
+
+        let mut holiday = Record::build();
+        holiday.set_date(date).set_all_day();
+
+        let mut busy = busy_periods(&[standup, review, holiday], minutes(15));
+        assert_eq!(busy.len(), 2);
+
+        // a second calendar with a meeting overlapping the review
+        busy.push((date.and_time(time(14, 0)), date.and_time(time(15, 0))));
+
+        let hours = (time(9, 0), time(17, 0));
+        let slots = free_slots(
+            &busy,
+            (date, next),
+            hours,
+            minutes(60),
+            date.and_time(time(0, 0)),
+        );
+
+        assert_eq!(
+            slots,
+            vec![
+                Slot {
+                    start: date.and_time(time(9, 45)),
+                    end: date.and_time(time(13, 0)),
+                },
+                Slot {
+                    start: date.and_time(time(15, 0)),
+                    end: date.and_time(time(17, 0)),
+                },
+                Slot {
+                    start: next.and_time(time(9, 0)),
+                    end: next.and_time(time(17, 0)),
+                },
+            ]
+        );
+
+        // the time before `after` has already passed
+        let slots = free_slots(
+            &busy,
+            (date, date),
+            hours,
+            minutes(60),
+            date.and_time(time(15, 30)),
+        );
+        assert_eq!(
+            slots,
+            vec![Slot {
+                start: date.and_time(time(15, 30)),
+                end: date.and_time(time(17, 0)),
+            }]
+        );
+
+        assert!(free_slots(
+            &busy,
+            (date, date),
+            hours,
+            minutes(240),
+            date.and_time(time(0, 0))
+        )
+        .is_empty());
+    }
+}
@@ -0,0 +1,192 @@
+use crate::{
+    config::Config,
+    free::busy_period,
+    record::{Record, RecordType},
+    time::now,
+};
+use crossterm::style::Color;
+use std::io::IsTerminal;
+
+// Highlight is where a record stands in relation to the current time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Highlight {
+    InProgress,
+    Upcoming,
+    Overdue,
+    Completed,
+}
+
+// ColorScheme is the color for each highlight; None leaves those records uncolored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorScheme {
+    pub in_progress: Option<Color>,
+    pub upcoming: Option<Color>,
+    pub overdue: Option<Color>,
+    pub completed: Option<Color>,
+}
+
+impl ColorScheme {
+    pub fn color(&self, highlight: Highlight) -> Option<Color> {
+        match highlight {
+            Highlight::InProgress => self.in_progress,
+            Highlight::Upcoming => self.upcoming,
+            Highlight::Overdue => self.overdue,
+            Highlight::Completed => self.completed,
+        }
+    }
+}
+
+pub const COLORS: &[&str] = &[
+    "black",
+    "dark_grey",
+    "grey",
+    "white",
+    "red",
+    "dark_red",
+    "green",
+    "dark_green",
+    "yellow",
+    "dark_yellow",
+    "blue",
+    "dark_blue",
+    "magenta",
+    "dark_magenta",
+    "cyan",
+    "dark_cyan",
+    "none",
+];
+
+// parse_color takes one of COLORS; `none` turns the color off.
+pub fn parse_color(name: &str) -> anyhow::Result<Option<Color>> {
+    if name == "none" {
+        return Ok(None);
+    }
+
+    Color::try_from(name).map(Some).map_err(|_| {
+        anyhow::anyhow!(
+            "Invalid color `{}`; valid colors are {}",
+            name,
+            COLORS.join(", ")
+        )
+    })
+}
+
+// colors_enabled is false when colors were turned off, when NO_COLOR is set, or when the output is
+// not a terminal, such as when piping to another program.
+pub fn colors_enabled(enabled: bool) -> bool {
+    enabled
+        && std::env::var("NO_COLOR").map_or(true, |v| v.is_empty())
+        && std::io::stdout().is_terminal()
+}
+
+// highlight finds where the record stands at `now`. Records starting within the window are
+// upcoming; records which have ended without being completed are overdue. At records take up the
+// default duration, and all day events take up their whole days.
+pub fn highlight(
+    record: &Record,
+    now: chrono::NaiveDateTime,
+    window: chrono::Duration,
+    default_duration: chrono::Duration,
+) -> Option<Highlight> {
+    if record.completed() {
+        return Some(Highlight::Completed);
+    }
+
+    let (start, end) = match record.record_type() {
+        RecordType::AllDay => (
+            record.date().and_hms_opt(0, 0, 0)?,
+            record
+                .end_date()
+                .unwrap_or(record.date())
+                .succ_opt()?
+                .and_hms_opt(0, 0, 0)?,
+        ),
+        _ => busy_period(record, default_duration)?,
+    };
+
+    if start <= now && now < end {
+        Some(Highlight::InProgress)
+    } else if now < start && start <= now + window {
+        Some(Highlight::Upcoming)
+    } else if end <= now {
+        Some(Highlight::Overdue)
+    } else {
+        None
+    }
+}
+
+// Highlighter colors records by their highlight at the time it was made.
+#[derive(Debug, Clone)]
+pub struct Highlighter {
+    scheme: ColorScheme,
+    now: chrono::NaiveDateTime,
+    window: chrono::Duration,
+    default_duration: chrono::Duration,
+}
+
+impl Highlighter {
+    // new returns None when colors are not used. The window defaults to the configured one.
+    pub fn new(config: &Config, window: Option<chrono::Duration>) -> Option<Self> {
+        if !colors_enabled(config.use_color()) {
+            return None;
+        }
+
+        Some(Self {
+            scheme: config.color_scheme(),
+            now: now().naive_local(),
+            window: window.unwrap_or(config.upcoming_window()),
+            default_duration: config.default_duration().duration(),
+        })
+    }
+
+    pub fn color(&self, record: &Record) -> Option<Color> {
+        highlight(record, self.now, self.window, self.default_duration)
+            .and_then(|h| self.scheme.color(h))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_highlight() {
+        use super::{highlight, Highlight};
+        use crate::record::Record;
+
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 11).unwrap();
+        let time = |h, m| chrono::NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let minutes = |m| chrono::TimeDelta::try_minutes(m).unwrap_or_default();
+        let now = date.and_time(time(10, 0));
+        let check = |record: &Record| highlight(record, now, minutes(30), minutes(15));
+
+        let mut record = Record::build();
+        record
+            .set_date(date)
+            .set_scheduled(Some((time(9, 30), time(10, 30))));
+        assert_eq!(check(&record), Some(Highlight::InProgress));
+
+        record.set_at(Some(time(9, 50)));
+        assert_eq!(check(&record), Some(Highlight::InProgress));
+
+        record.set_at(Some(time(10, 20)));
+        assert_eq!(check(&record), Some(Highlight::Upcoming));
+
+        record.set_at(Some(time(11, 0)));
+        assert_eq!(check(&record), None);
+
+        record.set_at(Some(time(9, 0)));
+        assert_eq!(check(&record), Some(Highlight::Overdue));
+
+        record.set_completed(true);
+        assert_eq!(check(&record), Some(Highlight::Completed));
+
+        let mut holiday = Record::build();
+        holiday.set_date(date).set_all_day();
+        assert_eq!(check(&holiday), Some(Highlight::InProgress));
+
+        holiday.set_date(date.pred_opt().unwrap());
+        assert_eq!(check(&holiday), Some(Highlight::Overdue));
+
+        holiday.set_end_date(Some(date));
+        assert_eq!(check(&holiday), Some(Highlight::InProgress));
+    }
+}
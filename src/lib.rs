@@ -1,9 +1,16 @@
 pub mod cli_processor;
 pub mod config;
+pub mod conflicts;
 pub mod db;
 pub mod filenames;
+pub mod free;
+pub mod highlight;
+pub mod merge;
+pub mod notifications;
 pub mod oauth;
+pub mod output;
 pub mod parsers;
 pub mod record;
+pub mod template;
 pub mod time;
 pub mod ui;
@@ -0,0 +1,220 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+
+const YOURS: &str = "<<<<<<< yours";
+const ORIGINAL: &str = "||||||| original";
+const SEPARATOR: &str = "=======";
+const REMOTE: &str = ">>>>>>> remote";
+
+const HEADER: &str =
+    "# This item was changed remotely while you were editing it. Changes which did not
+# overlap have been merged. Fields which were changed on both sides are marked below, showing
+# your version, the original, and the remote version. Keep the lines you want, remove the
+# markers, and save to try again.
+";
+
+// Merge is the outcome of merging local edits with the changes made to an item remotely.
+#[derive(Debug, Clone)]
+pub struct Merge {
+    pub text: String,
+    pub conflicts: usize,
+}
+
+struct Conflict {
+    key: Value,
+    base: Option<Value>,
+    ours: Option<Value>,
+    theirs: Option<Value>,
+}
+
+fn placeholder(index: usize) -> String {
+    format!("__saturn_conflict_{}__", index)
+}
+
+// three_way merges the fields of two edited versions of an item against the version they were
+// both made from. Fields changed on only one side are taken from that side; fields changed on
+// both sides are written out with conflict markers for the user to resolve.
+pub fn three_way<T: Serialize>(base: &T, ours: &T, theirs: &T) -> Result<Merge> {
+    let base = serde_yaml::to_value(base)?;
+    let ours = serde_yaml::to_value(ours)?;
+    let theirs = serde_yaml::to_value(theirs)?;
+
+    let mut conflicts = Vec::new();
+    let merged = merge_value(
+        Value::Null,
+        Some(&base),
+        Some(&ours),
+        Some(&theirs),
+        &mut conflicts,
+    )
+    .unwrap_or(Value::Null);
+
+    let text = serde_yaml::to_string(&merged)?;
+
+    if conflicts.is_empty() {
+        return Ok(Merge { text, conflicts: 0 });
+    }
+
+    let mut out = HEADER.to_string();
+
+    for line in text.lines() {
+        let index =
+            (0..conflicts.len()).find(|i| line.ends_with(&format!(": {}", placeholder(*i))));
+
+        if let Some(index) = index {
+            let conflict = &conflicts[index];
+            let indent = &line[..line.len() - line.trim_start().len()];
+
+            out += &format!("{}\n", YOURS);
+            out += &render(&conflict.key, conflict.ours.as_ref(), indent)?;
+            out += &format!("{}\n", ORIGINAL);
+            out += &render(&conflict.key, conflict.base.as_ref(), indent)?;
+            out += &format!("{}\n", SEPARATOR);
+            out += &render(&conflict.key, conflict.theirs.as_ref(), indent)?;
+            out += &format!("{}\n", REMOTE);
+        } else {
+            out += line;
+            out += "\n";
+        }
+    }
+
+    Ok(Merge {
+        text: out,
+        conflicts: conflicts.len(),
+    })
+}
+
+// has_conflict_markers reports whether a merge was saved without resolving all of its conflicts.
+pub fn has_conflict_markers(text: &str) -> bool {
+    text.lines()
+        .any(|line| [YOURS, ORIGINAL, SEPARATOR, REMOTE].contains(&line.trim_end()))
+}
+
+fn merge_value(
+    key: Value,
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    conflicts: &mut Vec<Conflict>,
+) -> Option<Value> {
+    if ours == theirs || theirs == base {
+        return ours.cloned();
+    }
+
+    if ours == base {
+        return theirs.cloned();
+    }
+
+    if let (Some(Value::Mapping(base)), Some(Value::Mapping(ours)), Some(Value::Mapping(theirs))) =
+        (base, ours, theirs)
+    {
+        let mut merged = Mapping::new();
+        let keys = ours
+            .keys()
+            .chain(theirs.keys().filter(|k| !ours.contains_key(*k)))
+            .chain(
+                base.keys()
+                    .filter(|k| !ours.contains_key(*k) && !theirs.contains_key(*k)),
+            );
+
+        for k in keys {
+            if let Some(v) = merge_value(
+                k.clone(),
+                base.get(k),
+                ours.get(k),
+                theirs.get(k),
+                conflicts,
+            ) {
+                merged.insert(k.clone(), v);
+            }
+        }
+
+        return Some(Value::Mapping(merged));
+    }
+
+    conflicts.push(Conflict {
+        key,
+        base: base.cloned(),
+        ours: ours.cloned(),
+        theirs: theirs.cloned(),
+    });
+
+    Some(Value::String(placeholder(conflicts.len() - 1)))
+}
+
+fn render(key: &Value, value: Option<&Value>, indent: &str) -> Result<String> {
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(String::new()),
+    };
+
+    let mut mapping = Mapping::new();
+    mapping.insert(key.clone(), value.clone());
+
+    Ok(serde_yaml::to_string(&mapping)?
+        .lines()
+        .map(|line| format!("{}{}\n", indent, line))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_three_way() {
+        use super::{has_conflict_markers, three_way};
+        use crate::record::{PresentedRecord, Record};
+
+        let mut record = Record::default();
+        record.set_detail("original".to_string());
+        record.set_at(Some(chrono::NaiveTime::from_hms_opt(8, 0, 0).unwrap()));
+        let base: PresentedRecord = record.clone().into();
+
+        // non-overlapping changes are merged
+        let mut ours = base.clone();
+        ours.detail = "mine".to_string();
+        let mut theirs = base.clone();
+        theirs.at = Some(chrono::NaiveTime::from_hms_opt(9, 0, 0).unwrap());
+
+        let merge = three_way(&base, &ours, &theirs).unwrap();
+        assert_eq!(merge.conflicts, 0);
+        assert!(!has_conflict_markers(&merge.text));
+        let merged: PresentedRecord = serde_yaml::from_str(&merge.text).unwrap();
+        assert_eq!(merged.detail, "mine");
+        assert_eq!(merged.at, theirs.at);
+
+        // overlapping changes are marked
+        theirs.detail = "remote".to_string();
+        let merge = three_way(&base, &ours, &theirs).unwrap();
+        assert_eq!(merge.conflicts, 1);
+        assert!(has_conflict_markers(&merge.text));
+        assert!(merge.text.contains("detail: mine\n"));
+        assert!(merge.text.contains("detail: original\n"));
+        assert!(merge.text.contains("detail: remote\n"));
+        assert!(serde_yaml::from_str::<PresentedRecord>(&merge.text).is_err());
+    }
+
+    #[test]
+    fn test_three_way_nested() {
+        use super::three_way;
+        use crate::record::{PresentedRecurringRecord, RecurringRecord};
+
+        let mut record = crate::record::Record::default();
+        record.set_detail("original".to_string());
+        let base: PresentedRecurringRecord = RecurringRecord::new(
+            record,
+            fancy_duration::FancyDuration::new(chrono::TimeDelta::try_days(1).unwrap_or_default()),
+        )
+        .into();
+
+        let mut ours = base.clone();
+        ours.record.detail = "mine".to_string();
+        let mut theirs = base.clone();
+        theirs.record.detail = "remote".to_string();
+
+        let merge = three_way(&base, &ours, &theirs).unwrap();
+        assert_eq!(merge.conflicts, 1);
+        assert!(merge.text.contains("  detail: mine\n"));
+        assert!(merge.text.contains("  detail: remote\n"));
+    }
+}
@@ -0,0 +1,972 @@
+use crate::{
+    db::{GcReport, DB},
+    filenames::{saturn_db, saturn_notified},
+    free::busy_period,
+    highlight::{highlight, Highlight},
+    output::EntryOutput,
+    record::{Anchor, Notification, Record, RecordType},
+    time::now,
+};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    io::Write,
+    ops::{Bound, RangeBounds},
+    os::unix::io::AsRawFd,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+// SINK_FIELD is the field of a record which chooses where its notifications are sent, instead of
+// the configured `notify_sink`.
+pub const SINK_FIELD: &str = "notify_sink";
+
+// SNOOZE_FIELD holds the times a record was snoozed until, each of which notifies once more.
+pub const SNOOZE_FIELD: &str = "notify_snooze";
+
+// ACK_FIELD holds the time up to which the notifications of a record were acknowledged.
+pub const ACK_FIELD: &str = "notify_ack";
+
+// SNOOZE is how long the snooze action of a desktop notification snoozes for.
+pub const SNOOZE: &str = "10m";
+
+const SNOOZE_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+// POLL is how often the daemon looks for changes to the database file while it waits.
+const POLL: std::time::Duration = std::time::Duration::from_secs(5);
+
+// WAIT_POLL is how often `Desktop::wait` looks for notifications which were closed.
+const WAIT_POLL: std::time::Duration = std::time::Duration::from_millis(100);
+
+// Due is a notification of a record, along with the time it fires. Snoozed notifications were
+// added by snoozing the record, and fire at the time it was snoozed until.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Due {
+    pub record: Record,
+    pub notification: Notification,
+    pub at: chrono::NaiveDateTime,
+    pub snoozed: bool,
+}
+
+// anchored is the time of the record a notification is relative to. All day events without an
+// anchor are notified before the end of their first day, as they always were.
+pub fn anchored(record: &Record, anchor: Option<Anchor>) -> chrono::NaiveDateTime {
+    let midnight = |date: chrono::NaiveDate| {
+        (date + chrono::TimeDelta::try_days(1).unwrap_or_default()).and_time(chrono::NaiveTime::MIN)
+    };
+
+    match (record.record_type(), anchor) {
+        (RecordType::AllDay, None) => midnight(record.date()),
+        (RecordType::AllDay, Some(Anchor::Start)) => record.date().and_time(chrono::NaiveTime::MIN),
+        (RecordType::AllDay, Some(Anchor::End)) => {
+            midnight(record.end_date().unwrap_or(record.date()))
+        }
+        (_, Some(Anchor::End)) => busy_period(record, chrono::TimeDelta::zero())
+            .map_or(record.datetime().naive_local(), |(_, end)| end),
+        (_, _) => record.datetime().naive_local(),
+    }
+}
+
+// fires_at is when a notification of the record fires: its duration before its anchor, at its
+// time of day if it has one.
+pub fn fires_at(record: &Record, notification: &Notification) -> chrono::NaiveDateTime {
+    let at = anchored(record, notification.anchor()) - notification.duration();
+
+    match notification.time() {
+        Some(time) => at.date().and_time(time),
+        None => at,
+    }
+}
+
+// snooze has the record notify once more at the time, and takes back its acknowledgement.
+pub fn snooze(record: &mut Record, until: chrono::NaiveDateTime) {
+    let mut fields = record.fields();
+    fields.remove(ACK_FIELD);
+    fields
+        .entry(SNOOZE_FIELD.to_string())
+        .or_default()
+        .push(until.format(SNOOZE_FORMAT).to_string());
+    record.set_fields(fields);
+}
+
+// acknowledge keeps the notifications of the record which fire up to `at` from notifying again,
+// including any times it was snoozed until. Later ones still notify, such as when the record is
+// moved later, or for the next occurrence of a recurring record.
+pub fn acknowledge(record: &mut Record, at: chrono::NaiveDateTime) {
+    let mut fields = record.fields();
+    fields.remove(SNOOZE_FIELD);
+    fields.insert(
+        ACK_FIELD.to_string(),
+        vec![at.format(SNOOZE_FORMAT).to_string()],
+    );
+    record.set_fields(fields);
+}
+
+// acknowledged is the time up to which the notifications of the record were acknowledged.
+fn acknowledged(record: &Record) -> Option<chrono::NaiveDateTime> {
+    record
+        .fields()
+        .get(ACK_FIELD)
+        .into_iter()
+        .flatten()
+        .filter_map(|s| chrono::NaiveDateTime::parse_from_str(s, SNOOZE_FORMAT).ok())
+        .max()
+}
+
+fn snoozed(record: &Record) -> Vec<chrono::NaiveDateTime> {
+    record
+        .fields()
+        .get(SNOOZE_FIELD)
+        .into_iter()
+        .flatten()
+        .filter_map(|s| chrono::NaiveDateTime::parse_from_str(s, SNOOZE_FORMAT).ok())
+        .collect()
+}
+
+// due lists the notifications of the records which fire within the range, in the order they fire,
+// leaving out the ones which were acknowledged.
+pub fn due(records: &[Record], range: impl RangeBounds<chrono::NaiveDateTime>) -> Vec<Due> {
+    let mut v = Vec::new();
+
+    for record in records {
+        let acknowledged = acknowledged(record);
+        let contains = |at: &chrono::NaiveDateTime| {
+            range.contains(at) && acknowledged.is_none_or(|ack| *at > ack)
+        };
+
+        for notification in record.notifications().unwrap_or_default() {
+            let at = fires_at(record, &notification);
+
+            if contains(&at) {
+                v.push(Due {
+                    record: record.clone(),
+                    notification,
+                    at,
+                    snoozed: false,
+                });
+            }
+        }
+
+        for at in snoozed(record) {
+            if contains(&at) {
+                v.push(Due {
+                    record: record.clone(),
+                    notification: chrono::TimeDelta::zero().into(),
+                    at,
+                    snoozed: true,
+                });
+            }
+        }
+    }
+
+    v.sort_by_key(|d| d.at);
+    v
+}
+
+// next is when the first notification after `after` fires, if any are left.
+pub fn next(records: &[Record], after: chrono::NaiveDateTime) -> Option<chrono::NaiveDateTime> {
+    due(records, (Bound::Excluded(after), Bound::Unbounded))
+        .first()
+        .map(|d| d.at)
+}
+
+// Sink is somewhere notifications are sent. `body` is the text of the notification, from the
+// notification template if there is one.
+pub trait Sink {
+    fn send(&self, due: &Due, body: &str) -> Result<()>;
+}
+
+// Desktop shows the notification on the desktop. Where the notification server supports actions,
+// the notification can be snoozed or acknowledged from it.
+#[derive(Debug, Clone, Default)]
+pub struct Desktop {
+    pub timeout: std::time::Duration,
+    pub icon: Option<String>,
+    waiting: Arc<Mutex<Vec<std::thread::JoinHandle<()>>>>,
+}
+
+impl Desktop {
+    pub fn new(timeout: std::time::Duration, icon: Option<String>) -> Self {
+        Self {
+            timeout,
+            icon,
+            ..Default::default()
+        }
+    }
+
+    // wait waits for the notifications with actions to be closed, so an action chosen before then
+    // is not lost when saturn exits. Notification servers may keep them around past their timeout,
+    // so it gives up once the timeout is over; a later action is lost. The actions change the
+    // database from another process, so it should be loaded again afterwards.
+    pub fn wait(&self) {
+        let waiting = std::mem::take(&mut *self.waiting.lock().unwrap());
+        let deadline = std::time::Instant::now() + self.timeout;
+
+        while waiting.iter().any(|h| !h.is_finished()) && std::time::Instant::now() < deadline {
+            std::thread::sleep(
+                WAIT_POLL.min(deadline.saturating_duration_since(std::time::Instant::now())),
+            );
+        }
+    }
+}
+
+// run_action runs `saturn snooze` or `saturn ack` for a notification action, in the same profile.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn run_action(args: &[String]) -> Result<()> {
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command.args(args);
+    if let Some(profile) = crate::filenames::profile() {
+        command.env("SATURN_PROFILE", profile);
+    }
+
+    let status = command.status()?;
+    if !status.success() {
+        return Err(anyhow!("`saturn {}` {}", args.join(" "), status));
+    }
+
+    Ok(())
+}
+
+impl Sink for Desktop {
+    fn send(&self, _due: &Due, body: &str) -> Result<()> {
+        let mut notification = notify_rust::Notification::new();
+        notification
+            .summary("Calendar Event")
+            .timeout(self.timeout)
+            .body(body);
+        if let Some(icon) = &self.icon {
+            notification.icon(icon);
+        }
+
+        #[cfg(all(unix, not(target_os = "macos")))]
+        if notify_rust::get_capabilities().is_ok_and(|c| c.iter().any(|c| c == "actions")) {
+            notification
+                .action("snooze", &format!("Snooze {}", SNOOZE))
+                .action("ack", "Done");
+
+            let handle = notification.show()?;
+            let id = _due.record.primary_key().to_string();
+            let mut waiting = self.waiting.lock().unwrap();
+            waiting.retain(|h| !h.is_finished());
+            waiting.push(std::thread::spawn(move || {
+                handle.wait_for_action(|action| {
+                    let args = match action {
+                        "snooze" => ["snooze".to_string(), id, SNOOZE.to_string()].to_vec(),
+                        "ack" => ["ack".to_string(), id].to_vec(),
+                        _ => return,
+                    };
+
+                    if let Err(e) = run_action(&args) {
+                        eprintln!("Could not {} notification: {}", action, e);
+                    }
+                })
+            }));
+
+            return Ok(());
+        }
+
+        notification.show()?;
+        Ok(())
+    }
+}
+
+// Event is what the command sink is given: the entry as `saturn show --format json` writes it,
+// along with the notification and its body.
+#[derive(Debug, Clone, Serialize)]
+struct Event<'a> {
+    #[serde(flatten)]
+    entry: EntryOutput,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notification: Option<String>,
+    snoozed: bool,
+    fires_at: chrono::NaiveDateTime,
+    body: &'a str,
+}
+
+// Command runs a shell command with the event as JSON on its standard input, e.g. to ring the
+// terminal bell or post to a webhook with curl.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Command(pub String);
+
+impl Sink for Command {
+    fn send(&self, due: &Due, body: &str) -> Result<()> {
+        let event = Event {
+            entry: due.record.clone().into(),
+            notification: Some(due.notification.to_string()).filter(|_| !due.snoozed),
+            snoozed: due.snoozed,
+            fires_at: due.at,
+            body,
+        };
+
+        let mut child = std::process::Command::new("/bin/sh")
+            .args(["-c", &self.0])
+            .stdin(std::process::Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(serde_json::to_string(&event)?.as_bytes())?;
+        }
+
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(anyhow!("Notification command `{}` {}", self.0, status));
+        }
+
+        Ok(())
+    }
+}
+
+// File appends the body as a line to a file, which a status bar can show.
+#[derive(Debug, Clone, PartialEq)]
+pub struct File(pub PathBuf);
+
+impl Sink for File {
+    fn send(&self, _due: &Due, body: &str) -> Result<()> {
+        let mut f = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.0)?;
+        writeln!(f, "{}", body.replace('\n', " "))?;
+        Ok(())
+    }
+}
+
+// SinkKind names one of the sinks: `desktop`, `command:<shell command>` or `file:<path>`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SinkKind {
+    Desktop,
+    Command(String),
+    File(PathBuf),
+}
+
+impl std::str::FromStr for SinkKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            _ if s.trim() == "desktop" => Ok(Self::Desktop),
+            Some(("command", command)) if !command.trim().is_empty() => {
+                Ok(Self::Command(command.trim().to_string()))
+            }
+            Some(("file", path)) if !path.trim().is_empty() => {
+                Ok(Self::File(PathBuf::from(path.trim())))
+            }
+            _ => Err(anyhow!(
+                "Invalid notification sink `{}`: use `desktop`, `command:<command>` or `file:<path>`",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for SinkKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Desktop => f.write_str("desktop"),
+            Self::Command(command) => write!(f, "command:{}", command),
+            Self::File(path) => write!(f, "file:{}", path.display()),
+        }
+    }
+}
+
+impl SinkKind {
+    pub fn build(&self, desktop: &Desktop) -> Box<dyn Sink> {
+        match self {
+            Self::Desktop => Box::new(desktop.clone()),
+            Self::Command(command) => Box::new(Command(command.clone())),
+            Self::File(path) => Box::new(File(path.clone())),
+        }
+    }
+}
+
+// sinks are where the record's notifications go: the sinks in its `notify_sink` field, or else the
+// configured one.
+pub fn sinks(record: &Record, configured: &SinkKind) -> Result<Vec<SinkKind>> {
+    match record.fields().get(SINK_FIELD) {
+        Some(values) if !values.is_empty() => values.iter().map(|v| v.parse()).collect(),
+        _ => Ok(vec![configured.clone()]),
+    }
+}
+
+// send sends the notification to each of its sinks. All of them are tried, even when one fails.
+pub fn send(due: &Due, body: &str, configured: &SinkKind, desktop: &Desktop) -> Result<()> {
+    let mut res = Ok(());
+
+    for kind in sinks(&due.record, configured)? {
+        if let Err(e) = kind.build(desktop).send(due, body) {
+            if res.is_ok() {
+                res = Err(e);
+            }
+        }
+    }
+
+    res
+}
+
+// Sent is a notification which was sent. The time it fired is part of it, so moving a record, or
+// the next occurrence of a recurring one, notifies again.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+struct Sent {
+    id: u64,
+    // seconds before the anchor of the notification
+    notification: i64,
+    at: chrono::NaiveDateTime,
+}
+
+impl From<&Due> for Sent {
+    fn from(value: &Due) -> Self {
+        Self {
+            id: value.record.primary_key(),
+            notification: value.notification.duration().num_seconds(),
+            at: value.at,
+        }
+    }
+}
+
+// Notified is kept in a state file between runs. It holds the notifications which were sent, so
+// none are sent twice, and when notifications were last looked for, which is where `--catch-up`
+// starts from.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Notified {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checked: Option<chrono::NaiveDateTime>,
+    #[serde(default)]
+    sent: BTreeSet<Sent>,
+}
+
+// NotifiedLock is an exclusive lock on the state file, taken the way the database file is locked.
+// `saturn notify`, the daemon and `saturn db gc` each hold it from loading the state to saving it,
+// so they don't undo each other's changes. It is released when dropped.
+pub struct NotifiedLock {
+    _file: std::fs::File,
+}
+
+impl Notified {
+    pub fn lock() -> Result<NotifiedLock> {
+        let path = saturn_notified();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+
+        if unsafe { nix::libc::flock(file.as_raw_fd(), nix::libc::LOCK_EX) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(NotifiedLock { _file: file })
+    }
+
+    pub fn load(_lock: &NotifiedLock) -> Result<Self> {
+        let path = saturn_notified();
+
+        match std::fs::read_to_string(&path) {
+            // locking creates the file
+            Ok(s) if s.trim().is_empty() => Ok(Self::default()),
+            Ok(s) => serde_yaml::from_str(&s)
+                .with_context(|| format!("Invalid notification state in {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, _lock: &NotifiedLock) -> Result<()> {
+        Ok(std::fs::write(
+            saturn_notified(),
+            serde_yaml::to_string(self)?,
+        )?)
+    }
+
+    pub fn checked(&self) -> Option<chrono::NaiveDateTime> {
+        self.checked
+    }
+
+    // renumber follows the primary keys changed by a gc, so notifications which were sent are not
+    // sent again, and the ones of removed records don't stand in for the records given their keys.
+    pub fn renumber(&mut self, report: &GcReport) {
+        self.sent = std::mem::take(&mut self.sent)
+            .into_iter()
+            .filter(|s| !report.removed.contains(&s.id))
+            .map(|mut s| {
+                s.id = report.renumbered.get(&s.id).cloned().unwrap_or(s.id);
+                s
+            })
+            .collect();
+    }
+
+    // deliver sends the notifications after `start` and within `end` which were not sent yet,
+    // except for records which are over by `now`. Each is recorded as sent before it is, so a
+    // notification which fails is not tried again. Sent notifications from before `start` are
+    // forgotten; later ranges start after it.
+    pub fn deliver(
+        &mut self,
+        records: &[Record],
+        start: chrono::NaiveDateTime,
+        end: Bound<chrono::NaiveDateTime>,
+        now: chrono::NaiveDateTime,
+        default_duration: chrono::Duration,
+        mut fire: impl FnMut(&Due) -> Result<()>,
+    ) -> Result<()> {
+        let mut res = Ok(());
+
+        for due in due(records, (Bound::Excluded(start), end)) {
+            // snoozes usually come once the record has started, so it may be over already
+            let over = !due.snoozed
+                && highlight(
+                    &due.record,
+                    now,
+                    chrono::TimeDelta::zero(),
+                    default_duration,
+                ) == Some(Highlight::Overdue);
+
+            if over || !self.sent.insert(Sent::from(&due)) {
+                continue;
+            }
+
+            if let Err(e) = fire(&due) {
+                if res.is_ok() {
+                    res = Err(e);
+                }
+            }
+        }
+
+        self.sent.retain(|s| s.at > start);
+        self.checked = Some(now);
+        res
+    }
+}
+
+fn modified(path: &std::path::Path) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// daemon sends each notification when it is due, until the process is stopped. The records are
+// read again whenever the database file changes, and every `refresh` to pick up changes made to a
+// remote calendar. Notifications which were missed while saturn wasn't running, or the computer
+// was suspended, are only sent when catching up.
+pub async fn daemon<D: DB>(
+    db: &mut D,
+    include_completed: bool,
+    refresh: chrono::Duration,
+    catch_up: bool,
+    default_duration: chrono::Duration,
+    mut fire: impl FnMut(&Due) -> Result<()>,
+) -> Result<()> {
+    let path = saturn_db();
+    let refresh = refresh.to_std().unwrap_or(POLL).max(POLL);
+    let mut checked = now().naive_local();
+    if catch_up {
+        checked = Notified::load(&Notified::lock()?)?
+            .checked()
+            .unwrap_or(checked)
+            .min(checked);
+    }
+    let mut records = Vec::new();
+
+    loop {
+        let mut loaded = modified(&path);
+        let reloaded = std::time::Instant::now();
+
+        // a failed read, such as when the network is down, keeps the records from the last one
+        match reload(db, include_completed).await {
+            Ok((list, saved)) => {
+                records = list;
+                // saving it ourselves is not a change to read again
+                if saved {
+                    loaded = modified(&path);
+                }
+            }
+            Err(e) => eprintln!("Could not read the calendar: {}", e),
+        }
+
+        while modified(&path) == loaded && reloaded.elapsed() < refresh {
+            let current = now().naive_local();
+            // notifications more than a minute late were missed, and are only sent when catching up
+            let start = if catch_up {
+                checked
+            } else {
+                checked.max(current - chrono::TimeDelta::try_minutes(1).unwrap_or_default())
+            };
+
+            // the state file is only touched when something is due, and read again first in case
+            // `saturn notify` sent some of it meanwhile
+            if !due(&records, (Bound::Excluded(start), Bound::Included(current))).is_empty() {
+                let lock = Notified::lock()?;
+                let mut notified = Notified::load(&lock)?;
+                let res = notified.deliver(
+                    &records,
+                    start,
+                    Bound::Included(current),
+                    current,
+                    default_duration,
+                    &mut fire,
+                );
+                notified.save(&lock)?;
+                drop(lock);
+
+                if let Err(e) = res {
+                    eprintln!("Could not send notification: {}", e);
+                }
+            }
+
+            checked = current;
+
+            let wait = next(&records, checked)
+                .and_then(|n| (n - checked).to_std().ok())
+                .map_or(POLL, |n| n.min(POLL));
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+// reload reads the records again, and saves the database when it handed out IDs while doing so:
+// the occurrences of recurring records it recorded, and new events on a remote calendar, need to
+// keep their IDs for `saturn snooze` and `saturn ack` to find them. Reports whether it was saved.
+async fn reload<D: DB>(db: &mut D, include_completed: bool) -> Result<(Vec<Record>, bool)> {
+    db.load().await?;
+    let primary_key = db.primary_key();
+    db.update_recurrence().await?;
+    let records = db.list_all(include_completed).await?;
+
+    let save = db.primary_key() != primary_key;
+    if save {
+        db.dump().await?;
+    }
+
+    Ok((records, save))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_due() {
+        use super::{due, fires_at, next};
+        use crate::record::{Anchor, Notification, Record};
+        use std::ops::Bound;
+
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 11).unwrap();
+        let time = |h, m| chrono::NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let minutes = |m| chrono::TimeDelta::try_minutes(m).unwrap_or_default();
+
+        let mut meeting = Record::build();
+        meeting
+            .set_primary_key(1)
+            .set_date(date)
+            .set_scheduled(Some((time(10, 0), time(11, 0))));
+        meeting.set_notifications(Some(vec![minutes(5).into(), minutes(30).into()]));
+        assert_eq!(
+            fires_at(&meeting, &minutes(5).into()),
+            date.and_time(time(9, 55))
+        );
+
+        let mut call = Record::build();
+        call.set_primary_key(2)
+            .set_date(date)
+            .set_at(Some(time(9, 45)));
+        call.set_notifications(Some(vec![minutes(5).into()]));
+        assert_eq!(
+            fires_at(&call, &minutes(5).into()),
+            date.and_time(time(9, 40))
+        );
+
+        let mut holiday = Record::build();
+        holiday.set_primary_key(3).set_date(date).set_all_day();
+        assert_eq!(
+            fires_at(&holiday, &minutes(60).into()),
+            date.and_time(time(23, 0))
+        );
+
+        let end = |m, at| Notification::new(minutes(m), Some(Anchor::End), at);
+        assert_eq!(
+            fires_at(&meeting, &end(5, None)),
+            date.and_time(time(10, 55))
+        );
+        assert_eq!(
+            fires_at(&call, &end(24 * 60, Some(time(9, 0)))),
+            date.pred_opt().unwrap().and_time(time(9, 0))
+        );
+        holiday.set_end_date(Some(date.succ_opt().unwrap()));
+        assert_eq!(
+            fires_at(&holiday, &end(60, None)),
+            date.succ_opt().unwrap().and_time(time(23, 0))
+        );
+        assert_eq!(
+            fires_at(
+                &holiday,
+                &Notification::new(minutes(0), None, Some(time(8, 0)))
+            ),
+            date.succ_opt().unwrap().and_time(time(8, 0))
+        );
+        holiday.set_end_date(None);
+
+        let records = vec![meeting, call, holiday];
+        let fired = due(
+            &records,
+            (
+                Bound::Excluded(date.and_time(time(9, 30))),
+                Bound::Included(date.and_time(time(9, 55))),
+            ),
+        )
+        .iter()
+        .map(|d| (d.record.primary_key(), d.at.time()))
+        .collect::<Vec<_>>();
+        assert_eq!(fired, vec![(2, time(9, 40)), (1, time(9, 55))]);
+
+        assert_eq!(
+            next(&records, date.and_time(time(9, 40))),
+            Some(date.and_time(time(9, 55)))
+        );
+        assert_eq!(next(&records, date.and_time(time(9, 55))), None);
+    }
+
+    #[test]
+    fn test_deliver() {
+        use super::Notified;
+        use crate::record::Record;
+        use std::ops::Bound;
+
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 11).unwrap();
+        let at = |h, m| date.and_hms_opt(h, m, 0).unwrap();
+        let minutes = |m| chrono::TimeDelta::try_minutes(m).unwrap_or_default();
+
+        let mut records = Vec::new();
+        for (key, start) in [(1, at(10, 0)), (2, at(9, 0))] {
+            let mut record = Record::build();
+            record
+                .set_primary_key(key)
+                .set_date(date)
+                .set_at(Some(start.time()));
+            record.set_notifications(Some(vec![minutes(30).into()]));
+            records.push(record);
+        }
+
+        let mut notified = Notified::default();
+        let mut deliver = |start, end, now| {
+            let mut sent = Vec::new();
+            notified
+                .deliver(
+                    &records,
+                    start,
+                    Bound::Included(end),
+                    now,
+                    minutes(15),
+                    |due| {
+                        sent.push(due.record.primary_key());
+                        Ok(())
+                    },
+                )
+                .unwrap();
+            sent
+        };
+
+        assert_eq!(deliver(at(9, 28), at(9, 30), at(9, 30)), vec![1]);
+        // overlapping windows don't send it again
+        assert_eq!(deliver(at(9, 29), at(9, 31), at(9, 31)), Vec::<u64>::new());
+        // catching up skips records which are already over
+        assert_eq!(deliver(at(8, 0), at(9, 31), at(9, 31)), Vec::<u64>::new());
+        assert_eq!(deliver(at(8, 0), at(9, 31), at(9, 5)), vec![2]);
+    }
+
+    #[test]
+    fn test_renumber() {
+        use super::Notified;
+        use crate::{db::GcReport, record::Record};
+        use std::ops::Bound;
+
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 11).unwrap();
+        let at = |h, m| date.and_hms_opt(h, m, 0).unwrap();
+        let minutes = |m| chrono::TimeDelta::try_minutes(m).unwrap_or_default();
+
+        let record = |key, hour| {
+            let mut record = Record::build();
+            record
+                .set_primary_key(key)
+                .set_date(date)
+                .set_at(Some(at(hour, 0).time()));
+            record.set_notifications(Some(vec![minutes(30).into()]));
+            record
+        };
+
+        let mut notified = Notified::default();
+        let deliver = |notified: &mut Notified, records: &[Record]| {
+            let mut sent = Vec::new();
+            notified
+                .deliver(
+                    records,
+                    at(8, 0),
+                    Bound::Included(at(10, 0)),
+                    at(8, 0),
+                    minutes(15),
+                    |due| {
+                        sent.push(due.record.primary_key());
+                        Ok(())
+                    },
+                )
+                .unwrap();
+            sent
+        };
+
+        assert_eq!(
+            deliver(&mut notified, &[record(1, 9), record(3, 10)]),
+            vec![1, 3]
+        );
+
+        // 1 was deleted, and 3 took its key
+        notified.renumber(&GcReport {
+            removed: vec![1],
+            renumbered: [(3, 1)].into(),
+            ..Default::default()
+        });
+        assert_eq!(
+            deliver(&mut notified, &[record(1, 10), record(2, 9)]),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn test_snooze() {
+        use super::{acknowledge, due, snooze, Notified};
+        use crate::record::Record;
+        use std::ops::Bound;
+
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 11).unwrap();
+        let at = |h, m| date.and_hms_opt(h, m, 0).unwrap();
+        let minutes = |m| chrono::TimeDelta::try_minutes(m).unwrap_or_default();
+
+        let mut record = Record::build();
+        record
+            .set_primary_key(1)
+            .set_date(date)
+            .set_at(Some(at(9, 0).time()));
+        record.set_notifications(Some(vec![minutes(5).into()]));
+        snooze(&mut record, at(9, 20));
+
+        let fired = due(&[record.clone()], ..)
+            .iter()
+            .map(|d| (d.at, d.snoozed))
+            .collect::<Vec<_>>();
+        assert_eq!(fired, vec![(at(8, 55), false), (at(9, 20), true)]);
+
+        // the snooze fires even though the record is over by then
+        let mut sent = 0;
+        Notified::default()
+            .deliver(
+                &[record.clone()],
+                at(9, 19),
+                Bound::Included(at(9, 20)),
+                at(9, 20),
+                minutes(15),
+                |_| {
+                    sent += 1;
+                    Ok(())
+                },
+            )
+            .unwrap();
+        assert_eq!(sent, 1);
+
+        acknowledge(&mut record, at(9, 21));
+        assert!(due(&[record.clone()], ..).is_empty());
+
+        // later notifications still fire, once the record is moved or on the next day
+        let mut moved = record.clone();
+        moved.set_at(Some(at(10, 0).time()));
+        let mut tomorrow = record.clone();
+        tomorrow.set_date(date.succ_opt().unwrap());
+        assert_eq!(
+            due(&[moved, tomorrow], ..)
+                .iter()
+                .map(|d| d.at)
+                .collect::<Vec<_>>(),
+            vec![
+                at(9, 55),
+                at(8, 55) + chrono::TimeDelta::try_days(1).unwrap()
+            ]
+        );
+
+        snooze(&mut record, at(9, 30));
+        assert_eq!(due(&[record], ..).len(), 2);
+    }
+
+    #[test]
+    fn test_wait() {
+        use super::Desktop;
+
+        let desktop = Desktop::new(std::time::Duration::from_millis(200), None);
+        desktop.waiting.lock().unwrap().push(std::thread::spawn(|| {
+            std::thread::sleep(std::time::Duration::from_secs(30))
+        }));
+
+        // a notification which is never closed does not keep saturn from exiting
+        let start = std::time::Instant::now();
+        desktop.wait();
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        assert!(desktop.waiting.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sinks() {
+        use super::{send, sinks, Desktop, Due, SinkKind};
+        use crate::record::{Fields, Record};
+
+        assert_eq!("desktop".parse::<SinkKind>().unwrap(), SinkKind::Desktop);
+        assert_eq!(
+            "command: notify.sh".parse::<SinkKind>().unwrap(),
+            SinkKind::Command("notify.sh".to_string())
+        );
+        assert!("file:".parse::<SinkKind>().is_err());
+        assert!("pager".parse::<SinkKind>().is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("log");
+        let json = dir.path().join("event.json");
+
+        let mut record = Record::build();
+        record.set_primary_key(7).set_detail("Dentist".to_string());
+        let due = Due {
+            record: record.clone(),
+            notification: chrono::TimeDelta::try_minutes(10).unwrap().into(),
+            at: chrono::NaiveDate::from_ymd_opt(2024, 3, 11)
+                .unwrap()
+                .and_hms_opt(9, 50, 0)
+                .unwrap(),
+            snoozed: false,
+        };
+
+        let file = SinkKind::File(log.clone());
+        assert_eq!(sinks(&record, &file).unwrap(), vec![file.clone()]);
+        send(&due, "Dentist at 10:00", &file, &Desktop::default()).unwrap();
+        send(&due, "Dentist at 10:00", &file, &Desktop::default()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap(),
+            "Dentist at 10:00\nDentist at 10:00\n"
+        );
+
+        // the record's field overrides the configured sink
+        let fields: Fields = serde_yaml::from_str(&format!(
+            "notify_sink: [\"command:cat > {}\"]",
+            json.display()
+        ))
+        .unwrap();
+        record.set_fields(fields);
+        let due = Due { record, ..due };
+        send(&due, "Dentist at 10:00", &file, &Desktop::default()).unwrap();
+
+        let event: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+        assert_eq!(event["id"], 7);
+        assert_eq!(event["detail"], "Dentist");
+        assert_eq!(event["notification"], "10m");
+        assert_eq!(event["body"], "Dentist at 10:00");
+
+        let false_sink = SinkKind::Command("false".to_string());
+        assert!(send(&due, "", &false_sink, &Desktop::default()).is_ok());
+        let failing = Due {
+            record: Record::build(),
+            ..due
+        };
+        assert!(send(&failing, "", &false_sink, &Desktop::default()).is_err());
+    }
+}
@@ -1,8 +1,70 @@
-use crate::config::Config;
+use crate::{config::Config, time::now};
 use anyhow::{anyhow, Result};
-use gcal::{oauth_listener, oauth_user_url, ClientParameters, State};
+use gcal::{
+    oauth::{AccessToken, CALENDAR_SCOPE, TOKEN_URL},
+    oauth_listener, oauth_user_url, ClientParameters, State,
+};
+use serde::Deserialize;
 use tokio::sync::Mutex;
 
+const DEVICE_CODE_URL: &str = "https://oauth2.googleapis.com/device/code";
+const DEVICE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+#[derive(Debug, Clone, Deserialize)]
+struct DeviceCode {
+    device_code: String,
+    user_code: String,
+    #[serde(alias = "verification_uri")]
+    verification_url: String,
+    expires_in: i64,
+    #[serde(default = "default_interval")]
+    interval: u64,
+}
+
+fn default_interval() -> u64 {
+    5
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OAuthError {
+    error: String,
+    error_description: Option<String>,
+}
+
+impl std::fmt::Display for OAuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.error_description {
+            Some(description) => write!(f, "{}: {}", self.error, description),
+            None => f.write_str(&self.error),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum TokenResponse {
+    Token(AccessToken),
+    Error(OAuthError),
+}
+
+// store_token keeps a newly issued token in the config. The refresh token is only replaced if a new
+// one was issued.
+pub fn store_token(config: &mut Config, token: AccessToken) {
+    config.set_access_token(Some(token.access_token));
+    config.set_access_token_expires_at(Some(
+        now().naive_utc() + chrono::TimeDelta::try_seconds(token.expires_in).unwrap_or_default(),
+    ));
+
+    if let Some(refresh_token) = token.refresh_token {
+        config.set_refresh_token(Some(refresh_token));
+        config.set_refresh_token_expires_at(Some(
+            now().naive_utc()
+                + chrono::TimeDelta::try_seconds(token.refresh_token_expires_in.unwrap_or(3600))
+                    .unwrap_or_default(),
+        ));
+    }
+}
+
 pub async fn get_access_token() -> Result<()> {
     let mut config = Config::load(None)?;
 
@@ -41,3 +103,101 @@ pub async fn get_access_token() -> Result<()> {
         tokio::time::sleep(std::time::Duration::new(1, 0)).await;
     }
 }
+
+// get_access_token_device uses the device authorization grant, for machines without a browser: the
+// code is entered on another device while the token is polled for here.
+pub async fn get_access_token_device() -> Result<()> {
+    let mut config = Config::load(None)?;
+
+    let (client_id, client_secret) = match (config.client_id(), config.client_secret()) {
+        (Some(id), Some(secret)) => (id, secret),
+        _ => {
+            return Err(anyhow!(
+                "You need to configure a client first; see `saturn config set-client`"
+            ))
+        }
+    };
+
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(DEVICE_CODE_URL)
+        .form(&[("client_id", client_id.as_str()), ("scope", CALENDAR_SCOPE)])
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let error: OAuthError = resp.json().await?;
+        return Err(anyhow!("Could not get a device code: {}", error));
+    }
+
+    let code: DeviceCode = resp.json().await?;
+
+    println!(
+        "On any device, visit {} and enter the code: {}",
+        code.verification_url, code.user_code
+    );
+
+    let expires = now() + chrono::TimeDelta::try_seconds(code.expires_in).unwrap_or_default();
+    let mut interval = code.interval;
+
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs(interval)).await;
+
+        if now() > expires {
+            return Err(anyhow!(
+                "The code expired before it was entered; run `saturn config get-token --device` again"
+            ));
+        }
+
+        let resp: TokenResponse = client
+            .post(TOKEN_URL)
+            .form(&[
+                ("client_id", client_id.as_str()),
+                ("client_secret", client_secret.as_str()),
+                ("device_code", code.device_code.as_str()),
+                ("grant_type", DEVICE_GRANT),
+            ])
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        match resp {
+            TokenResponse::Token(token) => {
+                store_token(&mut config, token);
+                config.set_redirect_url(None);
+                config.save(None)?;
+                println!("Captured. Thanks!");
+                return Ok(());
+            }
+            TokenResponse::Error(error) => match error.error.as_str() {
+                "authorization_pending" => {}
+                "slow_down" => interval += 5,
+                _ => return Err(anyhow!("Could not get a token: {}", error)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_token_response() {
+        use super::TokenResponse;
+
+        let pending: TokenResponse = serde_json::from_str(
+            r#"{"error": "authorization_pending", "error_description": "Precondition Required"}"#,
+        )
+        .unwrap();
+        assert!(matches!(pending, TokenResponse::Error(e) if e.error == "authorization_pending"));
+
+        let token: TokenResponse = serde_json::from_str(
+            r#"{"access_token": "abc", "expires_in": 3599, "refresh_token": "def", "scope": "https://www.googleapis.com/auth/calendar", "token_type": "Bearer"}"#,
+        )
+        .unwrap();
+        assert!(
+            matches!(token, TokenResponse::Token(t) if t.access_token == "abc" && t.refresh_token.as_deref() == Some("def"))
+        );
+    }
+}
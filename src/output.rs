@@ -0,0 +1,284 @@
+use crate::{
+    free::Slot,
+    record::{PresentedRecord, PresentedRecurringRecord, Record, RecurringRecord, Schedule},
+};
+use anyhow::{anyhow, Result};
+use gcal::CalendarListItem;
+use serde::Serialize;
+
+// Format is a machine-readable alternative to the tables `saturn` prints.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Yaml,
+    Csv,
+    Tsv,
+}
+
+impl std::str::FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "json" => Ok(Self::Json),
+            "yaml" => Ok(Self::Yaml),
+            "csv" => Ok(Self::Csv),
+            "tsv" => Ok(Self::Tsv),
+            _ => Err(anyhow!(
+                "Invalid format: valid formats are `json`, `yaml`, `csv` and `tsv`"
+            )),
+        }
+    }
+}
+
+// Row is something that can be written in any of the formats. JSON and YAML use its serde form;
+// CSV and TSV, which cannot nest, use the columns.
+pub trait Row: Serialize {
+    fn columns() -> Vec<&'static str>;
+    fn values(&self) -> Vec<String>;
+}
+
+// EntryOutput is an entry as it is written: the same fields `saturn show` and `saturn edit` use,
+// along with its IDs.
+#[derive(Debug, Clone, Serialize)]
+pub struct EntryOutput {
+    pub id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recurrence_id: Option<u64>,
+    #[serde(flatten)]
+    pub record: PresentedRecord,
+}
+
+impl From<Record> for EntryOutput {
+    fn from(value: Record) -> Self {
+        Self {
+            id: value.primary_key(),
+            recurrence_id: value.recurrence_key(),
+            record: value.into(),
+        }
+    }
+}
+
+fn record_columns() -> Vec<&'static str> {
+    vec![
+        "date",
+        "end_date",
+        "type",
+        "at",
+        "start",
+        "stop",
+        "detail",
+        "fields",
+        "notifications",
+        "completed",
+    ]
+}
+
+fn record_values(record: &PresentedRecord) -> Vec<String> {
+    let optional = |s: Option<String>| s.unwrap_or_default();
+    let schedule: Option<Schedule> = record.scheduled.clone().map(|s| s.into());
+
+    vec![
+        record.date.to_string(),
+        optional(record.end_date.map(|d| d.to_string())),
+        format!("{:?}", record.typ),
+        optional(record.at.map(|t| t.to_string())),
+        optional(schedule.map(|s| s.0.to_string())),
+        optional(schedule.map(|s| s.1.to_string())),
+        record.detail.clone(),
+        // key=value pairs separated by `;`, with several values for a key separated by `,`
+        record
+            .fields
+            .iter()
+            .map(|(key, values)| format!("{}={}", key, values.join(",")))
+            .collect::<Vec<String>>()
+            .join(";"),
+        record
+            .notifications
+            .iter()
+            .flatten()
+            .map(|n| n.to_string())
+            .collect::<Vec<String>>()
+            .join(","),
+        record.completed.to_string(),
+    ]
+}
+
+impl Row for EntryOutput {
+    fn columns() -> Vec<&'static str> {
+        let mut columns = vec!["id", "recurrence_id"];
+        columns.append(&mut record_columns());
+        columns
+    }
+
+    fn values(&self) -> Vec<String> {
+        let mut values = vec![
+            self.id.to_string(),
+            self.recurrence_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+        ];
+        values.append(&mut record_values(&self.record));
+        values
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecurringOutput {
+    pub id: u64,
+    #[serde(flatten)]
+    pub record: PresentedRecurringRecord,
+}
+
+impl From<RecurringRecord> for RecurringOutput {
+    fn from(value: RecurringRecord) -> Self {
+        Self {
+            id: value.recurrence_key(),
+            record: value.into(),
+        }
+    }
+}
+
+impl Row for RecurringOutput {
+    fn columns() -> Vec<&'static str> {
+        let mut columns = vec!["id", "recurrence"];
+        columns.append(&mut record_columns());
+        columns
+    }
+
+    fn values(&self) -> Vec<String> {
+        let mut values = vec![self.id.to_string(), self.record.recurrence.to_string()];
+        values.append(&mut record_values(&self.record.record));
+        values
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CalendarOutput {
+    pub id: String,
+    pub summary: String,
+}
+
+impl From<CalendarListItem> for CalendarOutput {
+    fn from(value: CalendarListItem) -> Self {
+        Self {
+            id: value.id,
+            summary: value.summary,
+        }
+    }
+}
+
+impl Row for CalendarOutput {
+    fn columns() -> Vec<&'static str> {
+        vec!["id", "summary"]
+    }
+
+    fn values(&self) -> Vec<String> {
+        vec![self.id.clone(), self.summary.clone()]
+    }
+}
+
+impl Row for Slot {
+    fn columns() -> Vec<&'static str> {
+        vec!["start", "end"]
+    }
+
+    fn values(&self) -> Vec<String> {
+        vec![self.start.to_string(), self.end.to_string()]
+    }
+}
+
+fn csv_value(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// TSV has no quoting, so the characters which would break up the table are escaped instead.
+fn tsv_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn delimited<T: Row>(rows: &[T], escape: fn(&str) -> String, delimiter: &str) -> String {
+    let mut out = T::columns().join(delimiter) + "\n";
+
+    for row in rows {
+        out += &row
+            .values()
+            .iter()
+            .map(|v| escape(v))
+            .collect::<Vec<String>>()
+            .join(delimiter);
+        out += "\n";
+    }
+
+    out
+}
+
+// render writes the rows in the format. JSON and YAML produce a list, and CSV and TSV a header line
+// followed by a line for each row.
+pub fn render<T: Row>(format: Format, rows: &[T]) -> Result<String> {
+    Ok(match format {
+        Format::Json => serde_json::to_string_pretty(rows)? + "\n",
+        Format::Yaml => serde_yaml::to_string(rows)?,
+        Format::Csv => delimited(rows, csv_value, ","),
+        Format::Tsv => delimited(rows, tsv_value, "\t"),
+    })
+}
+
+// render_one writes a single row; JSON and YAML produce an object instead of a list.
+pub fn render_one<T: Row>(format: Format, row: T) -> Result<String> {
+    Ok(match format {
+        Format::Json => serde_json::to_string_pretty(&row)? + "\n",
+        Format::Yaml => serde_yaml::to_string(&row)?,
+        Format::Csv | Format::Tsv => render(format, &[row])?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_render() {
+        use super::{render, EntryOutput, Format};
+        use crate::record::Record;
+
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 11).unwrap();
+        let time = |h, m| chrono::NaiveTime::from_hms_opt(h, m, 0).unwrap();
+
+        let mut record = Record::build();
+        record
+            .set_primary_key(3)
+            .set_date(date)
+            .set_scheduled(Some((time(9, 0), time(10, 30))))
+            .set_detail("Review, \"quarterly\"\tnumbers".to_string());
+        let rows: Vec<EntryOutput> = vec![record.into()];
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render(Format::Json, &rows).unwrap()).unwrap();
+        assert_eq!(json[0]["id"], 3);
+        assert_eq!(json[0]["type"], "Schedule");
+        assert_eq!(json[0]["scheduled"]["start"], "09:00:00");
+        assert!(json[0].get("recurrence_id").is_none());
+
+        let yaml: serde_yaml::Value =
+            serde_yaml::from_str(&render(Format::Yaml, &rows).unwrap()).unwrap();
+        assert_eq!(yaml[0]["detail"], json[0]["detail"].as_str().unwrap());
+
+        assert_eq!(
+            render(Format::Csv, &rows).unwrap(),
+            "id,recurrence_id,date,end_date,type,at,start,stop,detail,fields,notifications,completed\n\
+             3,,2024-03-11,,Schedule,,09:00:00,10:30:00,\"Review, \"\"quarterly\"\"\tnumbers\",,,false\n"
+        );
+
+        let tsv = render(Format::Tsv, &rows).unwrap();
+        let line = tsv.lines().nth(1).unwrap();
+        assert_eq!(line.split('\t').count(), 12);
+        assert!(line.contains("Review, \"quarterly\"\\tnumbers"));
+    }
+}
@@ -1,5 +1,5 @@
 use super::time::{parse_date, parse_time};
-use crate::record::{Record, RecurringRecord};
+use crate::record::{Anchor, Notification, Record, RecurringRecord};
 use anyhow::{anyhow, Result};
 use chrono::Duration;
 use fancy_duration::FancyDuration;
@@ -27,8 +27,12 @@ pub enum EntryState {
     TimeAt,
     TimeScheduled,
     TimeScheduledHalf,
+    EndDate,
     Notify,
     NotifyTime,
+    NotifyBefore,
+    NotifyAnchor,
+    NotifyAt,
     Detail,
 }
 
@@ -48,12 +52,24 @@ impl EntryRecord {
     }
 }
 
+fn add_detail(record: &mut Record, arg: &str) {
+    if record.detail().is_empty() {
+        record.set_detail(arg.to_string());
+    } else {
+        record.set_detail(format!("{} {}", record.detail(), arg));
+    }
+}
+
 fn parse_entry(args: Vec<String>, use_24h_time: bool) -> Result<EntryRecord> {
     let mut record = Record::build();
     let mut state = EntryState::Date;
 
     let mut scheduled_first: Option<chrono::NaiveTime> = None;
     let mut recurrence: Option<FancyDuration<Duration>> = None;
+    // notify <duration> [before start|end] [at <time>]; words which don't fit start the detail
+    let mut notify: Option<Duration> = None;
+    let mut notify_anchor: Option<Anchor> = None;
+    let mut notify_at: Option<chrono::NaiveTime> = None;
 
     for arg in &args {
         match state {
@@ -101,7 +117,18 @@ fn parse_entry(args: Vec<String>, use_24h_time: bool) -> Result<EntryRecord> {
                     state = EntryState::Notify;
                 }
             },
+            EntryState::EndDate => {
+                let end_date = parse_date(arg.to_string())?;
+                if end_date < record.date() {
+                    return Err(anyhow!("Event cannot end before it starts"));
+                }
+                record.set_end_date(Some(end_date));
+                state = EntryState::Notify;
+            }
             EntryState::Notify => match arg.as_str() {
+                "until" if record.all_day() && record.end_date().is_none() => {
+                    state = EntryState::EndDate
+                }
                 "notify" => state = EntryState::NotifyTime,
                 _ => {
                     record.set_detail(arg.to_string());
@@ -110,22 +137,48 @@ fn parse_entry(args: Vec<String>, use_24h_time: bool) -> Result<EntryRecord> {
             },
             EntryState::NotifyTime => match arg.as_str() {
                 "me" => {}
+                "at" => {
+                    notify = Some(Duration::zero());
+                    state = EntryState::NotifyAt;
+                }
                 _ => {
-                    let duration = FancyDuration::<Duration>::parse(arg)?;
-                    record.add_notification(duration.duration());
+                    notify = Some(FancyDuration::<Duration>::parse(arg)?.duration());
+                    state = EntryState::NotifyBefore;
+                }
+            },
+            EntryState::NotifyBefore => match arg.as_str() {
+                "before" => state = EntryState::NotifyAnchor,
+                "at" => state = EntryState::NotifyAt,
+                _ => {
+                    add_detail(&mut record, arg);
                     state = EntryState::Detail;
                 }
             },
-            EntryState::Detail => {
-                if record.detail().is_empty() {
-                    record.set_detail(arg.to_string());
+            EntryState::NotifyAnchor => {
+                if let Ok(anchor) = arg.parse() {
+                    notify_anchor = Some(anchor);
+                    state = EntryState::NotifyBefore;
+                } else {
+                    add_detail(&mut record, &format!("before {}", arg));
+                    state = EntryState::Detail;
+                }
+            }
+            EntryState::NotifyAt => {
+                if let Ok(time) = parse_time(arg.to_string(), !use_24h_time) {
+                    notify_at = Some(time);
                 } else {
-                    record.set_detail(format!("{} {}", record.detail(), arg));
+                    add_detail(&mut record, &format!("at {}", arg));
                 }
+                state = EntryState::Detail;
             }
+            EntryState::Detail => add_detail(&mut record, arg),
         }
     }
 
+    if let Some(duration) = notify {
+        record.add_notification(Notification::new(duration, notify_anchor, notify_at));
+    }
+
     Ok(EntryRecord {
         record: record.clone(),
         recurrence: recurrence.map_or_else(|| None, |x| Some(RecurringRecord::new(record, x))),
@@ -137,7 +190,10 @@ mod tests {
     #[test]
     fn test_parse_entry() {
         use super::parse_entry;
-        use crate::{record::Record, time::now};
+        use crate::{
+            record::{Anchor, Notification, Record},
+            time::now,
+        };
         use chrono::{Datelike, TimeDelta, Timelike};
 
         let pm = now().hour() >= 12;
@@ -188,6 +244,44 @@ mod tests {
             )))
             .set_detail("Christmas Morning".to_string());
 
+        let mut holidays = record.clone();
+        holidays
+            .set_date(chrono::NaiveDate::from_ymd_opt(now().year(), 12, 24).unwrap())
+            .set_all_day()
+            .set_end_date(Some(
+                chrono::NaiveDate::from_ymd_opt(now().year(), 12, 26).unwrap(),
+            ))
+            .set_detail("Holidays".to_string());
+
+        let mut deadline = record.clone();
+        deadline
+            .set_date(chrono::NaiveDate::from_ymd_opt(now().year(), 12, 24).unwrap())
+            .set_all_day()
+            .set_detail("Wrap presents until 12/26".to_string());
+
+        let mut focus = christmas.clone();
+        focus
+            .add_notification(Notification::new(
+                chrono::TimeDelta::try_minutes(5).unwrap_or_default(),
+                Some(Anchor::End),
+                None,
+            ))
+            .set_detail("Focus".to_string());
+
+        let mut report = relax.clone();
+        report
+            .add_notification(Notification::new(
+                chrono::TimeDelta::try_days(1).unwrap_or_default(),
+                Some(Anchor::End),
+                Some(chrono::NaiveTime::from_hms_opt(9, 0, 0).unwrap()),
+            ))
+            .set_detail("Send the report".to_string());
+
+        let mut lunch = relax.clone();
+        lunch
+            .add_notification(chrono::TimeDelta::try_minutes(5).unwrap_or_default())
+            .set_detail("before lunch Relax".to_string());
+
         let table = vec![
             ("today at 8 notify me 5m Test Today", today),
             ("08/05 at 8 notify me 5m Get a Soda", soda),
@@ -198,6 +292,14 @@ mod tests {
             ),
             ("1/1 at 12am Happy new year!", new_year),
             ("12/25 from 7am to 12pm Christmas Morning", christmas),
+            ("12/24 all day until 12/26 Holidays", holidays),
+            ("12/24 all day Wrap presents until 12/26", deadline),
+            ("12/25 from 7am to 12pm notify 5m before end Focus", focus),
+            (
+                "tomorrow at 4pm notify 1d before end at 9am Send the report",
+                report,
+            ),
+            ("tomorrow at 4pm notify 5m before lunch Relax", lunch),
         ];
 
         for (to_parse, t) in table {
@@ -214,5 +316,14 @@ mod tests {
                 t,
             )
         }
+
+        assert!(parse_entry(
+            "12/24 all day until 12/23 Holidays"
+                .split(" ")
+                .map(|s| s.to_string())
+                .collect::<Vec<String>>(),
+            false,
+        )
+        .is_err());
     }
 }
@@ -74,6 +74,27 @@ pub fn parse_date(s: String) -> Result<chrono::NaiveDate> {
     }
 }
 
+// parse_date_range parses a single date, or two dates separated by `..`. Both ends of the range are
+// included.
+pub fn parse_date_range(s: String) -> Result<(chrono::NaiveDate, chrono::NaiveDate)> {
+    match s.split_once("..") {
+        Some((start, end)) => {
+            let start = parse_date(start.to_string())?;
+            let end = parse_date(end.to_string())?;
+
+            if end < start {
+                return Err(anyhow!("Date range ends before it starts"));
+            }
+
+            Ok((start, end))
+        }
+        None => {
+            let date = parse_date(s)?;
+            Ok((date, date))
+        }
+    }
+}
+
 fn twelve_hour_time(pm: bool, hour: u32, minute: u32) -> chrono::NaiveTime {
     let new_hour = if pm { 12 } else { 0 };
 
@@ -190,6 +211,29 @@ pub fn parse_time(s: String, today: bool) -> Result<chrono::NaiveTime> {
     }
 }
 
+// parse_hours reads a range of hours of the day, such as working hours. On a 12-hour clock, an end
+// without am or pm which would not be after a morning start is taken to be in the afternoon, so
+// `9:00` to `5:00` is a working day.
+pub fn parse_hours(
+    start: String,
+    end: String,
+    use_24h_time: bool,
+) -> Result<(chrono::NaiveTime, chrono::NaiveTime)> {
+    let designated = end.trim().to_lowercase().ends_with(['m', 'M']);
+    let start = parse_time(start, false)?;
+    let mut end = parse_time(end, false)?;
+
+    if !use_24h_time && !designated && end <= start && start.hour() < 12 && end.hour() < 12 {
+        end += chrono::TimeDelta::try_hours(12).unwrap_or_default();
+    }
+
+    if end <= start {
+        return Err(anyhow!("Working hours must end after they start"));
+    }
+
+    Ok((start, end))
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -234,6 +278,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_date_range() {
+        use super::parse_date_range;
+        use crate::time::now;
+
+        let day = |d| chrono::NaiveDate::from_ymd_opt(2018, 10, d).unwrap();
+
+        assert_eq!(
+            parse_date_range("2018/10/23..2018/10/25".to_string()).unwrap(),
+            (day(23), day(25))
+        );
+        assert_eq!(
+            parse_date_range("2018.10.23..2018.10.23".to_string()).unwrap(),
+            (day(23), day(23))
+        );
+        assert_eq!(
+            parse_date_range("2018-10-23".to_string()).unwrap(),
+            (day(23), day(23))
+        );
+        assert_eq!(
+            parse_date_range("today..tomorrow".to_string()).unwrap(),
+            (now().date_naive(), now().date_naive().succ_opt().unwrap())
+        );
+        assert!(parse_date_range("2018/10/25..2018/10/23".to_string()).is_err());
+    }
+
     #[test]
     fn test_parse_time() {
         use super::parse_time;
@@ -318,4 +388,35 @@ mod tests {
             )
         }
     }
+
+    #[test]
+    fn test_parse_hours() {
+        use super::parse_hours;
+
+        let time = |h, m| chrono::NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let hours = |start: &str, end: &str, use_24h_time| {
+            parse_hours(start.to_string(), end.to_string(), use_24h_time).ok()
+        };
+
+        assert_eq!(
+            hours("9:00", "5:00", false),
+            Some((time(9, 0), time(17, 0)))
+        );
+        assert_eq!(hours("9am", "5pm", false), Some((time(9, 0), time(17, 0))));
+        assert_eq!(
+            hours("8:30", "16:00", false),
+            Some((time(8, 30), time(16, 0)))
+        );
+        assert_eq!(
+            hours("9:00", "11:30", false),
+            Some((time(9, 0), time(11, 30)))
+        );
+        assert_eq!(hours("9:00", "5:00", true), None);
+        assert_eq!(
+            hours("9:00", "17:00", true),
+            Some((time(9, 0), time(17, 0)))
+        );
+        assert_eq!(hours("9:00", "5am", false), None);
+        assert_eq!(hours("17:00", "9:00", false), None);
+    }
 }
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
 pub type Schedule = (chrono::NaiveTime, chrono::NaiveTime);
-pub type Notifications = Vec<fancy_duration::FancyDuration<chrono::Duration>>;
+pub type Notifications = Vec<Notification>;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum RecordType {
@@ -44,6 +44,145 @@ impl From<Schedule> for PresentedSchedule {
     }
 }
 
+// Anchor is the part of a record a notification is relative to. For all day events the end is the
+// end of their last day, and for at records, such as tasks due at a time, the end is the start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    Start,
+    End,
+}
+
+impl std::fmt::Display for Anchor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Start => "start",
+            Self::End => "end",
+        })
+    }
+}
+
+impl std::str::FromStr for Anchor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "start" => Ok(Self::Start),
+            "end" => Ok(Self::End),
+            _ => Err(anyhow!("Notifications are before the `start` or the `end`")),
+        }
+    }
+}
+
+// Notification is a duration before the record, and is written the same way in the editor and the
+// database: `5m`, `5m before end`, `1d before end at 9:00`, or `at 8:00`. Without an anchor, it is
+// before the start, except for all day events, which are notified before the end of their first
+// day. With a time, it fires at that time on the day it would have fired on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    duration: fancy_duration::FancyDuration<chrono::Duration>,
+    anchor: Option<Anchor>,
+    time: Option<chrono::NaiveTime>,
+}
+
+impl From<chrono::Duration> for Notification {
+    fn from(duration: chrono::Duration) -> Self {
+        Self::new(duration, None, None)
+    }
+}
+
+impl Notification {
+    pub fn new(
+        duration: chrono::Duration,
+        anchor: Option<Anchor>,
+        time: Option<chrono::NaiveTime>,
+    ) -> Self {
+        Self {
+            duration: fancy_duration::FancyDuration::new(duration),
+            anchor,
+            time,
+        }
+    }
+
+    pub fn duration(&self) -> chrono::Duration {
+        self.duration.duration()
+    }
+
+    pub fn anchor(&self) -> Option<Anchor> {
+        self.anchor
+    }
+
+    pub fn time(&self) -> Option<chrono::NaiveTime> {
+        self.time
+    }
+}
+
+impl std::fmt::Display for Notification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if !self.duration().is_zero() || self.anchor.is_some() || self.time.is_none() {
+            parts.push(self.duration.to_string());
+        }
+        if let Some(anchor) = self.anchor {
+            parts.push(format!("before {}", anchor));
+        }
+        if let Some(time) = self.time {
+            parts.push(format!("at {}", time.format("%H:%M")));
+        }
+
+        f.write_str(&parts.join(" "))
+    }
+}
+
+impl std::str::FromStr for Notification {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (rest, time) = match s.strip_prefix("at ") {
+            Some(time) => ("", Some(time)),
+            None => match s.rsplit_once(" at ") {
+                Some((rest, time)) => (rest, Some(time)),
+                None => (s, None),
+            },
+        };
+        let (duration, anchor) = match rest.rsplit_once(" before ") {
+            Some((duration, anchor)) => (duration, Some(anchor.trim().parse()?)),
+            None => (rest, None),
+        };
+
+        let duration = if duration.trim().is_empty() {
+            chrono::TimeDelta::zero()
+        } else {
+            fancy_duration::FancyDuration::<chrono::Duration>::parse(duration.trim())?.duration()
+        };
+        let time = time
+            .map(|t| crate::parsers::time::parse_time(t.to_string(), false))
+            .transpose()?;
+
+        Ok(Self::new(duration, anchor, time))
+    }
+}
+
+// notifications are kept as text, which also reads the plain durations they were before anchors.
+impl Serialize for Notification {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Notification {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
 pub struct Fields(BTreeMap<String, Vec<String>>);
 
@@ -55,6 +194,12 @@ impl std::ops::Deref for Fields {
     }
 }
 
+impl std::ops::DerefMut for Fields {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
 impl std::fmt::Display for Fields {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if self.is_empty() {
@@ -86,6 +231,8 @@ pub struct PresentedRecord {
     pub at: Option<chrono::NaiveTime>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub scheduled: Option<PresentedSchedule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_date: Option<chrono::NaiveDate>,
     pub detail: String,
     pub fields: Fields,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -100,6 +247,7 @@ impl From<Record> for PresentedRecord {
             typ: value.typ,
             at: value.at,
             scheduled: value.scheduled.map(|x| x.into()),
+            end_date: value.end_date,
             detail: value.detail,
             fields: value.fields,
             notifications: value.notifications,
@@ -116,6 +264,10 @@ impl PresentedRecord {
         internal_key: Option<String>,
         internal_recurrence_key: Option<String>,
     ) -> Record {
+        let end_date = self
+            .end_date
+            .filter(|d| self.typ == RecordType::AllDay && *d > self.date);
+
         Record {
             primary_key,
             recurrence_key,
@@ -125,10 +277,12 @@ impl PresentedRecord {
             typ: self.typ,
             at: self.at,
             scheduled: self.scheduled.map(|x| x.into()),
+            end_date,
             detail: self.detail,
             fields: self.fields,
             notifications: self.notifications,
             completed: self.completed,
+            etag: None,
         }
     }
 }
@@ -322,7 +476,11 @@ impl RecurringRecord {
             RecordType::At => {
                 record.set_at(Some(from.time()));
             }
-            RecordType::AllDay => {}
+            RecordType::AllDay => {
+                if let Some(end_date) = self.record.end_date() {
+                    record.set_end_date(Some(from.date() + (end_date - self.record.date())));
+                }
+            }
             RecordType::Schedule => {
                 let schedule = record.scheduled().unwrap();
                 let duration = schedule.1 - schedule.0;
@@ -347,6 +505,10 @@ pub struct Record {
     fields: Fields,
     notifications: Option<Notifications>,
     completed: bool,
+    #[serde(default)]
+    etag: Option<String>,
+    #[serde(default)]
+    end_date: Option<chrono::NaiveDate>,
 }
 
 impl Default for Record {
@@ -365,6 +527,8 @@ impl Default for Record {
             fields: Fields::default(),
             notifications: None,
             completed: false,
+            etag: None,
+            end_date: None,
         }
     }
 }
@@ -390,6 +554,15 @@ impl Record {
         self.internal_key = key
     }
 
+    // etag is the version of the remote event this record was read from, if any.
+    pub fn etag(&self) -> Option<String> {
+        self.etag.clone()
+    }
+
+    pub fn set_etag(&mut self, etag: Option<String>) {
+        self.etag = etag
+    }
+
     pub fn record_type(&self) -> RecordType {
         self.typ.clone()
     }
@@ -426,6 +599,28 @@ impl Record {
         matches!(self.typ, RecordType::AllDay)
     }
 
+    // end_date is the last day of an all day event spanning several days. It is None for events
+    // which only take up a single day.
+    pub fn end_date(&self) -> Option<chrono::NaiveDate> {
+        self.end_date
+    }
+
+    // on_date is true if the record takes place on the date; all day events spanning several days
+    // take place on each of them.
+    pub fn on_date(&self, date: chrono::NaiveDate) -> bool {
+        self.date == date || (self.date < date && self.end_date.is_some_and(|d| date <= d))
+    }
+
+    // in_range is true if the record takes place on any day from `start` to `end`, inclusive.
+    pub fn in_range(&self, start: chrono::NaiveDate, end: chrono::NaiveDate) -> bool {
+        self.date <= end && self.end_date.unwrap_or(self.date) >= start
+    }
+
+    pub fn set_end_date(&mut self, end_date: Option<chrono::NaiveDate>) -> &mut Self {
+        self.end_date = end_date.filter(|d| *d > self.date);
+        self
+    }
+
     pub fn detail(&self) -> String {
         self.detail.clone()
     }
@@ -489,6 +684,7 @@ impl Record {
     pub fn set_at(&mut self, at: Option<chrono::NaiveTime>) -> &mut Self {
         self.at = at;
         self.scheduled = None;
+        self.end_date = None;
         self.typ = RecordType::At;
         self
     }
@@ -496,6 +692,7 @@ impl Record {
     pub fn set_scheduled(&mut self, schedule: Option<Schedule>) -> &mut Self {
         self.scheduled = schedule;
         self.at = None;
+        self.end_date = None;
         self.typ = RecordType::Schedule;
         self
     }
@@ -516,8 +713,8 @@ impl Record {
         self.fields.0.get(&field).cloned()
     }
 
-    pub fn add_notification(&mut self, notification: chrono::Duration) -> &mut Self {
-        let notification = fancy_duration::FancyDuration::new(notification);
+    pub fn add_notification(&mut self, notification: impl Into<Notification>) -> &mut Self {
+        let notification = notification.into();
         if let Some(notifications) = &mut self.notifications {
             notifications.push(notification)
         } else {
@@ -574,3 +771,118 @@ pub fn sort_records(a: &Record, b: &Record) -> std::cmp::Ordering {
         cmp
     }
 }
+
+// records_on finds the records which take place on the date, including all day events spanning it.
+pub fn records_on(records: &[Record], date: chrono::NaiveDate) -> Vec<Record> {
+    records
+        .iter()
+        .filter(|r| r.on_date(date))
+        .cloned()
+        .collect()
+}
+
+// by_day groups the records by the days from `start` to `end` they take place on, leaving out days
+// with nothing on them. All day events come first on each day, followed by the rest in order of
+// their start.
+pub fn by_day(
+    records: &[Record],
+    start: chrono::NaiveDate,
+    end: chrono::NaiveDate,
+) -> Vec<(chrono::NaiveDate, Vec<Record>)> {
+    let mut days = Vec::new();
+
+    for date in start.iter_days().take_while(|d| *d <= end) {
+        let mut day = records_on(records, date);
+        if !day.is_empty() {
+            day.sort_by_key(|r| (!r.all_day(), r.datetime(), r.primary_key()));
+            days.push((date, day));
+        }
+    }
+
+    days
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_notification() {
+        use super::{Anchor, Notification};
+
+        let time = |h, m| chrono::NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let minutes = |m| chrono::TimeDelta::try_minutes(m).unwrap_or_default();
+
+        for (text, notification) in [
+            ("5m", Notification::new(minutes(5), None, None)),
+            (
+                "5m before end",
+                Notification::new(minutes(5), Some(Anchor::End), None),
+            ),
+            (
+                "1d before end at 09:00",
+                Notification::new(minutes(1440), Some(Anchor::End), Some(time(9, 0))),
+            ),
+            (
+                "at 08:30",
+                Notification::new(minutes(0), None, Some(time(8, 30))),
+            ),
+        ] {
+            assert_eq!(text.parse::<Notification>().unwrap(), notification);
+            assert_eq!(notification.to_string(), text);
+        }
+
+        // plain durations are how notifications were written before they had anchors
+        let notifications: Vec<Notification> =
+            serde_yaml::from_str("- 1h 30m\n- 10m before start at 5pm\n").unwrap();
+        assert_eq!(notifications[0].duration(), minutes(90));
+        assert_eq!(notifications[1].anchor(), Some(Anchor::Start));
+        assert_eq!(notifications[1].time(), Some(time(17, 0)));
+
+        assert!("5m before lunch".parse::<Notification>().is_err());
+    }
+
+    #[test]
+    fn test_by_day() {
+        use super::{by_day, records_on, Record};
+
+        let date = |d| chrono::NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        let time = |h| chrono::NaiveTime::from_hms_opt(h, 0, 0).unwrap();
+
+        let mut records = Vec::new();
+        for (key, day, at) in [(1, 11, Some(14)), (2, 11, Some(9)), (3, 14, Some(9))] {
+            let mut record = Record::build();
+            record
+                .set_primary_key(key)
+                .set_date(date(day))
+                .set_at(at.map(time));
+            records.push(record);
+        }
+
+        let mut conference = Record::build();
+        conference
+            .set_primary_key(4)
+            .set_date(date(10))
+            .set_all_day()
+            .set_end_date(Some(date(12)));
+        records.push(conference);
+
+        let keys = by_day(&records, date(11), date(13))
+            .into_iter()
+            .map(|(date, day)| {
+                (
+                    date,
+                    day.iter().map(|r| r.primary_key()).collect::<Vec<u64>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(keys, vec![(date(11), vec![4, 2, 1]), (date(12), vec![4])]);
+
+        // the conference is on every day it spans, which is what sui's month view shows
+        for day in 10..=12 {
+            assert!(records_on(&records, date(day))
+                .iter()
+                .any(|r| r.primary_key() == 4));
+        }
+        assert!(records_on(&records, date(13)).is_empty());
+    }
+}
@@ -0,0 +1,369 @@
+use crate::record::{Record, RecordType};
+use anyhow::{anyhow, Result};
+
+// Templates render a record as a line of text. `{name}` is replaced with an attribute of the
+// record, and filters may follow it, separated by `|`: `{detail|truncate:20|pad:23}`. `{{` and `}}`
+// stand for literal braces.
+pub const ATTRIBUTES: &[&str] = &[
+    "id",
+    "recurrence_id",
+    "date",
+    "end_date",
+    "type",
+    "time",
+    "at",
+    "start",
+    "stop",
+    "datetime",
+    "relative",
+    "detail",
+    "fields",
+    "fields.<name>",
+    "notifications",
+    "completed",
+    "done",
+];
+
+pub const FILTERS: &[&str] = &[
+    "upper",
+    "lower",
+    "truncate:<length>",
+    "pad:<width>",
+    "lpad:<width>",
+    "format:<strftime>",
+    "default:<text>",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Upper,
+    Lower,
+    Truncate(usize),
+    Pad(usize),
+    LPad(usize),
+    Format(String),
+    Default(String),
+}
+
+impl std::str::FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name.trim(), Some(arg)),
+            None => (s.trim(), None),
+        };
+
+        let number = || -> Result<usize> {
+            arg.ok_or_else(|| anyhow!("Filter `{}` needs a number, e.g. `{}:20`", name, name))?
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("Filter `{}` needs a number, e.g. `{}:20`", name, name))
+        };
+
+        let text = || -> Result<String> {
+            arg.map(|a| a.to_string())
+                .ok_or_else(|| anyhow!("Filter `{}` needs an argument", name))
+        };
+
+        Ok(match name {
+            "upper" => Self::Upper,
+            "lower" => Self::Lower,
+            "truncate" => Self::Truncate(number()?),
+            "pad" => Self::Pad(number()?),
+            "lpad" => Self::LPad(number()?),
+            "format" => Self::Format(text()?),
+            "default" => Self::Default(text()?),
+            _ => {
+                return Err(anyhow!(
+                    "Unknown filter `{}`; valid filters are {}",
+                    name,
+                    FILTERS.join(", ")
+                ))
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Attribute(String, Vec<Filter>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template(Vec<Part>);
+
+impl std::str::FromStr for Template {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut inner = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => inner.push(c),
+                            None => return Err(anyhow!("Unclosed `{{` in template: {}", s)),
+                        }
+                    }
+
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+
+                    let mut pieces = inner.split('|');
+                    let name = pieces.next().unwrap_or_default().trim().to_string();
+                    if !ATTRIBUTES.contains(&name.as_str()) && !name.starts_with("fields.") {
+                        return Err(anyhow!(
+                            "Unknown attribute `{}` in template; valid attributes are {}",
+                            name,
+                            ATTRIBUTES.join(", ")
+                        ));
+                    }
+
+                    parts.push(Part::Attribute(
+                        name,
+                        pieces.map(|f| f.parse()).collect::<Result<Vec<Filter>>>()?,
+                    ));
+                }
+                '}' => {
+                    return Err(anyhow!(
+                        "Unmatched `}}` in template; use `}}}}` for a brace"
+                    ))
+                }
+                c => text.push(c),
+            }
+        }
+
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Ok(Self(parts))
+    }
+}
+
+// Value keeps dates and times apart from text until they are printed, so `format` can apply to
+// them.
+enum Value {
+    Text(String),
+    Date(chrono::NaiveDate),
+    Time(chrono::NaiveTime),
+    DateTime(chrono::NaiveDateTime),
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text(s) => f.write_str(s),
+            Self::Date(d) => write!(f, "{}", d),
+            Self::Time(t) => write!(f, "{}", t),
+            Self::DateTime(dt) => write!(f, "{}", dt),
+        }
+    }
+}
+
+// relative describes how far the time is from now, e.g. `in 2h 5m` or `3d 4h ago`. Only the two
+// largest units are shown.
+fn relative(time: chrono::NaiveDateTime, now: chrono::NaiveDateTime) -> String {
+    let minutes = (time - now).num_minutes();
+    if minutes == 0 {
+        return "now".to_string();
+    }
+
+    let total = minutes.abs();
+    let units = [
+        (total / 1440, "d"),
+        (total % 1440 / 60, "h"),
+        (total % 60, "m"),
+    ];
+    let shown = units
+        .iter()
+        .skip_while(|(n, _)| *n == 0)
+        .take(2)
+        .filter(|(n, _)| *n != 0)
+        .map(|(n, unit)| format!("{}{}", n, unit))
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    if minutes > 0 {
+        format!("in {}", shown)
+    } else {
+        format!("{} ago", shown)
+    }
+}
+
+fn attribute(record: &Record, name: &str, now: chrono::NaiveDateTime) -> Value {
+    let text = |s: Option<String>| Value::Text(s.unwrap_or_default());
+    let time = |t: Option<chrono::NaiveTime>| t.map_or(Value::Text(String::new()), Value::Time);
+
+    match name {
+        "id" => Value::Text(record.primary_key().to_string()),
+        "recurrence_id" => text(record.recurrence_key().map(|k| k.to_string())),
+        "date" => Value::Date(record.date()),
+        "end_date" => record
+            .end_date()
+            .map_or(Value::Text(String::new()), Value::Date),
+        "type" => Value::Text(format!("{:?}", record.record_type())),
+        "time" => Value::Text(match record.record_type() {
+            RecordType::AllDay => "All Day".to_string(),
+            RecordType::At => record.at().map(|t| t.to_string()).unwrap_or_default(),
+            RecordType::Schedule => record
+                .scheduled()
+                .map(|s| format!("{} to {}", s.0, s.1))
+                .unwrap_or_default(),
+        }),
+        "at" => time(record.at()),
+        "start" => time(record.scheduled().map(|s| s.0)),
+        "stop" => time(record.scheduled().map(|s| s.1)),
+        "datetime" => Value::DateTime(record.datetime().naive_local()),
+        "relative" => Value::Text(relative(record.datetime().naive_local(), now)),
+        "detail" => Value::Text(record.detail()),
+        "fields" => Value::Text(record.fields().to_string()),
+        "notifications" => Value::Text(
+            record
+                .notifications()
+                .unwrap_or_default()
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<String>>()
+                .join(", "),
+        ),
+        "completed" => Value::Text(record.completed().to_string()),
+        "done" => Value::Text(if record.completed() { "X" } else { "" }.to_string()),
+        name => text(
+            name.strip_prefix("fields.")
+                .and_then(|field| record.fields().get(field).map(|v| v.join(", "))),
+        ),
+    }
+}
+
+fn apply(value: Value, filter: &Filter) -> Value {
+    let s = value.to_string();
+
+    Value::Text(match filter {
+        Filter::Upper => s.to_uppercase(),
+        Filter::Lower => s.to_lowercase(),
+        Filter::Truncate(length) => {
+            if s.chars().count() > *length {
+                s.chars().take(*length).collect::<String>() + "..."
+            } else {
+                s
+            }
+        }
+        Filter::Pad(width) => format!("{:<width$}", s, width = width),
+        Filter::LPad(width) => format!("{:>width$}", s, width = width),
+        Filter::Format(fmt) => {
+            use std::fmt::Write;
+
+            // a bad format string is shown as-is instead of failing the whole listing
+            let mut out = String::new();
+            let res = match value {
+                Value::Date(d) => write!(out, "{}", d.format(fmt)),
+                Value::Time(t) => write!(out, "{}", t.format(fmt)),
+                Value::DateTime(dt) => write!(out, "{}", dt.format(fmt)),
+                Value::Text(_) => return Value::Text(s),
+            };
+
+            if res.is_ok() {
+                out
+            } else {
+                s
+            }
+        }
+        Filter::Default(default) => {
+            if s.is_empty() {
+                default.clone()
+            } else {
+                s
+            }
+        }
+    })
+}
+
+impl Template {
+    pub fn render(&self, record: &Record, now: chrono::NaiveDateTime) -> String {
+        let mut out = String::new();
+
+        for part in &self.0 {
+            match part {
+                Part::Text(text) => out += text,
+                Part::Attribute(name, filters) => {
+                    out += &filters
+                        .iter()
+                        .fold(attribute(record, name, now), apply)
+                        .to_string()
+                }
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_template() {
+        use super::Template;
+        use crate::record::Record;
+
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 11).unwrap();
+        let time = |h, m| chrono::NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let now = date.and_time(time(7, 55));
+
+        let mut record = Record::build();
+        record
+            .set_primary_key(4)
+            .set_date(date)
+            .set_scheduled(Some((time(10, 0), time(11, 0))))
+            .set_detail("Quarterly planning meeting".to_string());
+
+        let render = |template: &str, record: &Record| {
+            template.parse::<Template>().unwrap().render(record, now)
+        };
+
+        assert_eq!(
+            render(
+                "{id|lpad:3} {start|format:%H:%M}-{stop|format:%H:%M} {detail|truncate:9}",
+                &record
+            ),
+            "  4 10:00-11:00 Quarterly..."
+        );
+        assert_eq!(render("{relative}", &record), "in 2h 5m");
+        assert_eq!(render("{date|format:%a %b %e}", &record), "Mon Mar 11");
+        assert_eq!(
+            render("{type|upper}|{at|default:-}|", &record),
+            "SCHEDULE|-|"
+        );
+        assert_eq!(
+            render("{{{fields.project|default:none}}}", &record),
+            "{none}"
+        );
+        assert_eq!(render("[{done|pad:1}]", &record), "[ ]");
+
+        assert!("{detial}".parse::<Template>().is_err());
+        assert!("{detail|shout}".parse::<Template>().is_err());
+        assert!("{detail|truncate:many}".parse::<Template>().is_err());
+        assert!("{detail".parse::<Template>().is_err());
+        assert!("detail}".parse::<Template>().is_err());
+
+        record.set_at(Some(time(7, 0)));
+        assert_eq!(render("{relative}", &record), "55m ago");
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    record::{PresentedRecord, PresentedRecurringRecord, Record, RecurringRecord},
+    record::{records_on, PresentedRecord, PresentedRecurringRecord, Record, RecurringRecord},
     time::now,
     ui::{
         consts::*,
@@ -875,15 +875,7 @@ pub async fn find_dates<'a>(
     state: &mut tokio::sync::MutexGuard<'_, State<'a>>,
     date: chrono::NaiveDateTime,
 ) -> Vec<Record> {
-    let mut v = Vec::new();
-
-    for item in state.records.clone() {
-        if item.date() == date.date() {
-            v.push(item);
-        }
-    }
-
-    v
+    records_on(&state.records, date.date())
 }
 
 pub async fn build_data<'a>(
@@ -41,7 +41,11 @@ impl<'a> ProtectedState<'a> {
     pub fn google_db(&self, config: Config) -> Result<RemoteDBClient<GoogleClient>> {
         let client = GoogleClient::new(config.clone())?;
 
-        Ok(RemoteDBClient::new(config.calendar_id(), client.clone()))
+        Ok(RemoteDBClient::new(
+            config.calendar_id(),
+            config.cache_ttl(),
+            client.clone(),
+        ))
     }
 
     pub fn memory_db(&self) -> Result<MemoryDB> {
@@ -81,7 +85,7 @@ impl<'a> ProtectedState<'a> {
     pub async fn command_google(&self, config: Config) -> Result<()> {
         let client = GoogleClient::new(config.clone())?;
 
-        let mut db = RemoteDBClient::new(config.calendar_id(), client.clone());
+        let mut db = RemoteDBClient::new(config.calendar_id(), config.cache_ttl(), client.clone());
         process_ui_command!(self, db, config);
         Ok(())
     }
@@ -95,7 +99,7 @@ impl<'a> ProtectedState<'a> {
     pub async fn get_google(&self, config: Config, id: u64) -> Result<Record> {
         let client = GoogleClient::new(config.clone())?;
 
-        let mut db = RemoteDBClient::new(config.calendar_id(), client.clone());
+        let mut db = RemoteDBClient::new(config.calendar_id(), config.cache_ttl(), client.clone());
         map_record!(db, id)
     }
 
@@ -107,7 +111,7 @@ impl<'a> ProtectedState<'a> {
     pub async fn get_recurring_google(&self, config: Config, id: u64) -> Result<RecurringRecord> {
         let client = GoogleClient::new(config.clone())?;
 
-        let mut db = RemoteDBClient::new(config.calendar_id(), client.clone());
+        let mut db = RemoteDBClient::new(config.calendar_id(), config.cache_ttl(), client.clone());
         map_record!(db, id, true)
     }
 
@@ -117,7 +121,13 @@ impl<'a> ProtectedState<'a> {
     }
 
     pub async fn update_state(&self) {
-        let config = Config::load(None).unwrap_or_default();
+        let config = match Config::load(None) {
+            Ok(config) => config,
+            Err(e) => {
+                self.add_error(e).await;
+                return;
+            }
+        };
 
         let typ = config.db_type();
 